@@ -0,0 +1,26 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS rating_snapshots (
+   player_id BIGINT UNSIGNED NOT NULL,
+   rating_period BIGINT UNSIGNED NOT NULL,
+
+   rating DOUBLE NOT NULL,
+   deviation DOUBLE NOT NULL,
+   volatility DOUBLE NOT NULL,
+
+   PRIMARY KEY(player_id, rating_period),
+	FOREIGN KEY(player_id) REFERENCES players(id),
+	FOREIGN KEY(rating_period) REFERENCES rating_periods(id)
+);
+
+-- Backfill the history we have; matches store both players' ratings at the start of their rating period
+INSERT IGNORE INTO rating_snapshots (player_id, rating_period, rating, deviation, volatility)
+SELECT player_a, rating_period, rating_a, deviation_a, volatility_a FROM matches;
+
+INSERT IGNORE INTO rating_snapshots (player_id, rating_period, rating, deviation, volatility)
+SELECT player_b, rating_period, rating_b, deviation_b, volatility_b FROM matches;
+
+-- Stored ratings are the ratings at the start of the latest unprocessed rating period
+INSERT IGNORE INTO rating_snapshots (player_id, rating_period, rating, deviation, volatility)
+SELECT players.id, latest.id, players.rating, players.deviation, players.volatility
+FROM players CROSS JOIN (SELECT MAX(id) AS id FROM rating_periods WHERE processed = false) AS latest
+WHERE latest.id IS NOT NULL;
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+   api_key_hash VARCHAR(64) NOT NULL,
+   idempotency_key VARCHAR(255) NOT NULL,
+
+   method VARCHAR(16) NOT NULL,
+   uri TEXT NOT NULL,
+
+   -- Null while the original request is still being processed
+   status SMALLINT UNSIGNED,
+   body MEDIUMTEXT,
+
+   epoch TIMESTAMP NOT NULL,
+
+   PRIMARY KEY(api_key_hash, idempotency_key)
+);
@@ -0,0 +1,19 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS match_reviews (
+   id BIGINT UNSIGNED NOT NULL PRIMARY KEY AUTO_INCREMENT,
+
+   -- Not foreign keys; rejected matches are removed, but their review is kept
+   match_id BIGINT UNSIGNED NOT NULL,
+   duplicate_of BIGINT UNSIGNED NOT NULL,
+
+   reason VARCHAR(255) NOT NULL,
+
+   -- pending, approved or rejected
+   status VARCHAR(16) NOT NULL DEFAULT 'pending',
+
+   epoch TIMESTAMP NOT NULL,
+   resolved_at TIMESTAMP NULL,
+
+   INDEX(status),
+   INDEX(match_id)
+);
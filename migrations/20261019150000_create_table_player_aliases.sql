@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS player_aliases (
+   id BIGINT UNSIGNED NOT NULL PRIMARY KEY AUTO_INCREMENT,
+   player_id BIGINT UNSIGNED NOT NULL,
+
+   -- A name the player used before
+   name VARCHAR(64) NOT NULL,
+
+   -- When the player stopped using the name
+   epoch TIMESTAMP NOT NULL,
+
+   INDEX(name),
+
+   FOREIGN KEY(player_id) REFERENCES players(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE players
+   -- active, suspended or banned
+   ADD COLUMN status VARCHAR(16) NOT NULL DEFAULT 'active',
+   ADD COLUMN status_reason TEXT NULL,
+   ADD COLUMN status_since TIMESTAMP NULL,
+   ADD COLUMN status_expires TIMESTAMP NULL;
@@ -0,0 +1,13 @@
+-- Add migration script here
+ALTER TABLE players
+   -- pc, playstation, xbox or switch
+   ADD COLUMN platform VARCHAR(16) NULL,
+   ADD COLUMN region VARCHAR(32) NULL,
+   ADD COLUMN clan VARCHAR(64) NULL,
+   ADD COLUMN discord_id VARCHAR(20) NULL,
+   -- Null for players added before this was recorded
+   ADD COLUMN joined_at TIMESTAMP NULL,
+
+   ADD INDEX(platform),
+   ADD INDEX(region),
+   ADD INDEX(clan);
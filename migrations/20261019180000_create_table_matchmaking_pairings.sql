@@ -0,0 +1,24 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS matchmaking_pairings (
+   id BIGINT UNSIGNED NOT NULL PRIMARY KEY AUTO_INCREMENT,
+
+   player_a BIGINT UNSIGNED NOT NULL,
+   player_b BIGINT UNSIGNED NOT NULL,
+
+   -- The pings the players queued with
+   ping_a SMALLINT UNSIGNED NOT NULL,
+   ping_b SMALLINT UNSIGNED NOT NULL,
+
+   quality DOUBLE NOT NULL,
+
+   epoch TIMESTAMP NOT NULL,
+
+   -- The match the players submitted after being paired, once they do
+   match_id BIGINT UNSIGNED NULL,
+
+   INDEX(player_a),
+   INDEX(player_b),
+
+   FOREIGN KEY(player_a) REFERENCES players(id) ON DELETE CASCADE,
+   FOREIGN KEY(player_b) REFERENCES players(id) ON DELETE CASCADE
+);
@@ -0,0 +1,37 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS ladders (
+   id BIGINT UNSIGNED NOT NULL PRIMARY KEY AUTO_INCREMENT,
+   name VARCHAR(64) NOT NULL UNIQUE,
+
+   -- The rating, deviation and volatility new players start with
+   default_rating DOUBLE NOT NULL,
+   default_deviation DOUBLE NOT NULL,
+   default_volatility DOUBLE NOT NULL,
+
+   rating_period_duration_days INT UNSIGNED NOT NULL
+);
+
+-- Everything which existed before ladders is in the default ladder
+INSERT IGNORE INTO ladders (id, name, default_rating, default_deviation, default_volatility, rating_period_duration_days)
+   VALUES (1, 'default', 1500, 350, 0.06, 21);
+
+ALTER TABLE players
+   ADD COLUMN ladder BIGINT UNSIGNED NOT NULL DEFAULT 1,
+   ADD INDEX(ladder);
+
+ALTER TABLE matches
+   ADD COLUMN ladder BIGINT UNSIGNED NOT NULL DEFAULT 1,
+   ADD INDEX(ladder);
+
+ALTER TABLE rating_periods
+   ADD COLUMN ladder BIGINT UNSIGNED NOT NULL DEFAULT 1,
+   ADD INDEX(ladder);
+
+-- Rejected matches are removed, so reviews need their own ladder
+ALTER TABLE match_reviews
+   ADD COLUMN ladder BIGINT UNSIGNED NOT NULL DEFAULT 1,
+   ADD INDEX(ladder);
+
+UPDATE match_reviews
+JOIN matches ON matches.id = match_reviews.match_id
+SET match_reviews.ladder = matches.ladder;
@@ -0,0 +1,87 @@
+-- Add migration script here
+
+-- Player names become unique within a ladder, so first resolve existing duplicates.
+--
+-- Usernames are at most 24 characters (28 with a #123 suffix), longer ones were never valid
+UPDATE players SET name = LEFT(name, 64) WHERE CHAR_LENGTH(name) > 64;
+
+-- Names are compared like the unique index below does, case and accent insensitively, whatever
+-- the column was created with
+--
+-- Every player but the first with a name is renamed to name_id, or name_(id + 1) and so on if
+-- that is taken too. Selected DISTINCT, since a name can have several older players
+CREATE TEMPORARY TABLE duplicate_players AS
+SELECT DISTINCT newer.id, newer.ladder, newer.name
+FROM players AS newer
+JOIN players AS older ON older.ladder = newer.ladder
+   AND CONVERT(older.name USING utf8mb4) COLLATE utf8mb4_0900_ai_ci = CONVERT(newer.name USING utf8mb4) COLLATE utf8mb4_0900_ai_ci
+   AND older.id < newer.id;
+
+CREATE PROCEDURE rename_duplicate_players()
+BEGIN
+   DECLARE duplicate_id BIGINT UNSIGNED;
+   DECLARE duplicate_ladder BIGINT UNSIGNED;
+   DECLARE duplicate_name VARCHAR(64) CHARACTER SET utf8mb4 COLLATE utf8mb4_0900_ai_ci;
+   DECLARE suffix BIGINT UNSIGNED;
+   DECLARE new_name VARCHAR(64) CHARACTER SET utf8mb4 COLLATE utf8mb4_0900_ai_ci;
+
+   rename_loop: LOOP
+      SET duplicate_id = (SELECT MIN(id) FROM duplicate_players);
+
+      IF duplicate_id IS NULL THEN
+         LEAVE rename_loop;
+      END IF;
+
+      SELECT ladder, name INTO duplicate_ladder, duplicate_name FROM duplicate_players WHERE id = duplicate_id;
+
+      SET suffix = duplicate_id;
+      SET new_name = CONCAT(LEFT(duplicate_name, 43), '_', suffix);
+
+      WHILE EXISTS (
+         SELECT 1 FROM players
+         WHERE ladder = duplicate_ladder
+            AND CONVERT(name USING utf8mb4) COLLATE utf8mb4_0900_ai_ci = new_name
+      ) DO
+         SET suffix = suffix + 1;
+         SET new_name = CONCAT(LEFT(duplicate_name, 43), '_', suffix);
+      END WHILE;
+
+      UPDATE players SET name = new_name WHERE id = duplicate_id;
+
+      DELETE FROM duplicate_players WHERE id = duplicate_id;
+   END LOOP;
+END;
+
+CALL rename_duplicate_players();
+
+DROP PROCEDURE rename_duplicate_players;
+
+DROP TEMPORARY TABLE duplicate_players;
+
+ALTER TABLE players
+   MODIFY COLUMN name VARCHAR(64) CHARACTER SET utf8mb4 COLLATE utf8mb4_0900_ai_ci NOT NULL,
+   ADD UNIQUE INDEX players_ladder_name (ladder, name),
+   ADD FOREIGN KEY(ladder) REFERENCES ladders(id);
+
+ALTER TABLE rating_periods
+   ADD INDEX rating_periods_ladder_start (ladder, start),
+   ADD FOREIGN KEY(ladder) REFERENCES ladders(id);
+
+-- A player's matches in a rating period, and head to head / duplicate lookups
+ALTER TABLE matches
+   ADD INDEX matches_rating_period_player_a (rating_period, player_a),
+   ADD INDEX matches_rating_period_player_b (rating_period, player_b),
+   ADD INDEX matches_players_epoch (player_a, player_b, epoch),
+   ADD FOREIGN KEY(ladder) REFERENCES ladders(id);
+
+-- Pairings may point at matches which were removed since
+UPDATE matchmaking_pairings SET match_id = NULL
+WHERE match_id IS NOT NULL AND match_id NOT IN (SELECT id FROM matches);
+
+ALTER TABLE matchmaking_pairings
+   ADD FOREIGN KEY(match_id) REFERENCES matches(id) ON DELETE SET NULL;
+
+-- Cleaned up by age
+ALTER TABLE recent_requests ADD INDEX recent_requests_epoch (epoch);
+
+ALTER TABLE idempotency_keys ADD INDEX idempotency_keys_epoch (epoch);
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+-- The sha256 hash of the request body, null if the route did not read one
+ALTER TABLE idempotency_keys ADD COLUMN body_hash VARCHAR(64) AFTER uri;
@@ -0,0 +1,16 @@
+-- Add migration script here
+ALTER TABLE players ADD COLUMN created_at TIMESTAMP NULL;
+
+-- For players added before this was recorded, use when they joined or, failing that, the start
+-- of the first rating period they were rated for or their first match
+UPDATE players SET created_at = COALESCE(
+   joined_at,
+   (SELECT MIN(rating_periods.start) FROM rating_snapshots
+      JOIN rating_periods ON rating_periods.id = rating_snapshots.rating_period
+      WHERE rating_snapshots.player_id = players.id),
+   (SELECT MIN(epoch) FROM matches WHERE matches.player_a = players.id OR matches.player_b = players.id),
+   CURRENT_TIMESTAMP
+);
+
+-- Set by the application when a player is added, from the same clock as everything else
+ALTER TABLE players MODIFY COLUMN created_at TIMESTAMP NOT NULL;
@@ -0,0 +1,121 @@
+//! Where time-dependent code gets the current time from, so tests can control it
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+/// The clock shared through Rocket's managed state
+pub type SharedClock = Arc<dyn Clock>;
+
+/// A source of the current time
+#[rocket::async_trait]
+pub trait Clock: Send + Sync {
+    /// Returns the current time
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Waits until the clock reaches the deadline
+    async fn sleep_until(&self, deadline: DateTime<Utc>);
+}
+
+/// The real clock; the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[rocket::async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        if let Ok(to_wait) = (deadline - self.now()).to_std() {
+            tokio::time::sleep(to_wait).await;
+        }
+    }
+}
+
+/// A clock which only moves when told to, for tests.
+///
+/// Anything waiting in [Clock::sleep_until] wakes up once the clock is advanced past its
+/// deadline, so season rollover can be triggered on demand.
+#[cfg(test)]
+pub struct FakeClock {
+    now: std::sync::Mutex<DateTime<Utc>>,
+    advanced: tokio::sync::Notify,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    /// Creates a clock stopped at the given time
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: std::sync::Mutex::new(now),
+            advanced: tokio::sync::Notify::new(),
+        }
+    }
+
+    /// Sets the clock to the given time
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+        self.advanced.notify_waiters();
+    }
+
+    /// Moves the clock forward
+    pub fn advance(&self, by: chrono::Duration) {
+        let now = self.now() + by;
+        self.set(now);
+    }
+}
+
+#[cfg(test)]
+#[rocket::async_trait]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        loop {
+            // Created before checking, so an advance in between isn't missed
+            let advanced = self.advanced.notified();
+
+            if self.now() >= deadline {
+                return;
+            }
+
+            advanced.await;
+        }
+    }
+}
+
+#[test]
+fn fake_clock_wakes_sleepers_when_advanced() {
+    let start = Utc::now();
+    let clock = Arc::new(FakeClock::new(start));
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let sleeping_clock = clock.clone();
+        let sleeper = tokio::spawn(async move {
+            sleeping_clock
+                .sleep_until(start + chrono::TimeDelta::days(21))
+                .await;
+
+            sleeping_clock.now()
+        });
+
+        tokio::task::yield_now().await;
+
+        clock.advance(chrono::TimeDelta::days(7));
+        tokio::task::yield_now().await;
+        assert!(!sleeper.is_finished());
+
+        clock.advance(chrono::TimeDelta::days(14));
+
+        assert_eq!(sleeper.await.unwrap(), start + chrono::TimeDelta::days(21));
+    });
+}
@@ -0,0 +1,122 @@
+use core::panic;
+
+use sqlx::mysql::MySqlQueryResult;
+
+use crate::types::entities::ladder::Ladder;
+
+use super::{is_constraint_violation, DbConnection};
+
+impl DbConnection {
+    /// Fetches all the ladders, by id
+    pub async fn get_ladders(&mut self) -> Vec<Ladder> {
+        let query_string = "SELECT * FROM ladders ORDER BY id ASC";
+
+        let query = sqlx::query_as(&query_string);
+
+        let result: Result<Vec<Ladder>, sqlx::Error> = query.fetch_all(&mut **self.inner).await;
+
+        match result {
+            Ok(vec) => return vec,
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return Vec::new(),
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches a ladder by id
+    pub async fn get_ladder_by_id(&mut self, id: u64) -> Option<Ladder> {
+        let query_string = "SELECT * FROM ladders WHERE id = ?";
+
+        let query = sqlx::query_as(&query_string).bind(id);
+
+        let result: Result<Ladder, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(ladder) => {
+                return Some(ladder);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches a ladder by name
+    pub async fn get_ladder_by_name(&mut self, name: &str) -> Option<Ladder> {
+        let query_string = "SELECT * FROM ladders WHERE name = ?";
+
+        let query = sqlx::query_as(&query_string).bind(name);
+
+        let result: Result<Ladder, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(ladder) => {
+                return Some(ladder);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches a ladder by id or name
+    ///
+    /// Id takes priority over name
+    pub async fn get_ladder_by_id_or_name(&mut self, query: &str) -> Option<Ladder> {
+        if let Ok(id) = query.parse::<u64>() {
+            if let Some(ladder) = self.get_ladder_by_id(id).await {
+                return Some(ladder);
+            }
+        }
+
+        self.get_ladder_by_name(query).await
+    }
+
+    /// Adds a ladder.
+    ///
+    /// Ignores the id field.
+    pub async fn add_ladder(&mut self, ladder: &Ladder) -> Result<MySqlQueryResult, sqlx::Error> {
+        let query_string = "INSERT INTO ladders (name, default_rating, default_deviation, default_volatility, rating_period_duration_days) VALUES (?, ?, ?, ?, ?)";
+
+        let query = sqlx::query(&query_string)
+            .bind(&ladder.name)
+            .bind(ladder.default_rating)
+            .bind(ladder.default_deviation)
+            .bind(ladder.default_volatility)
+            .bind(ladder.rating_period_duration_days as u32);
+
+        let result = query.execute(&mut **self.inner).await;
+
+        match result {
+            Ok(result) => {
+                return Ok(result);
+            }
+            Err(e) => match e {
+                e if is_constraint_violation(&e) => {
+                    log::warn!(
+                        "Database query violated a constraint {} -> {}",
+                        query_string,
+                        e
+                    );
+                    return Err(e);
+                }
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+}
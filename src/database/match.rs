@@ -1,10 +1,15 @@
 use core::panic;
 
-use sqlx::mysql::MySqlQueryResult;
+use sqlx::{mysql::MySqlQueryResult, Connection, MySqlConnection};
 
-use crate::types::entities::r#match::Match;
+use chrono::{DateTime, Utc};
 
-use super::{query::QueryParameters, DbConnection};
+use crate::types::entities::{match_review::MatchReview, r#match::Match};
+
+use super::{
+    is_constraint_violation, match_review::flag_possible_duplicate,
+    matchmaking::link_match_to_matchmaking_pairing, query::QueryParameters, DbConnection,
+};
 
 impl DbConnection {
     /// Fetches all the matches.
@@ -59,9 +64,9 @@ impl DbConnection {
 
     /// Fetches a match by id
     pub async fn get_match_by_id(&mut self, id: u64) -> Option<Match> {
-        let query_string = "SELECT * FROM matches WHERE id = ?";
+        let query_string = "SELECT * FROM matches WHERE ladder = ? AND id = ?";
 
-        let query = sqlx::query_as(&query_string).bind(id);
+        let query = sqlx::query_as(&query_string).bind(self.ladder).bind(id);
 
         let result: Result<Match, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
 
@@ -124,6 +129,32 @@ impl DbConnection {
         }
     }
 
+    /// Fetches all matches played between two players, by their ids, oldest first
+    pub async fn get_head_to_head_matches(&mut self, id_a: u64, id_b: u64) -> Vec<Match> {
+        let query_string = "SELECT * FROM matches WHERE (player_a = ? AND player_b = ?) OR (player_a = ? AND player_b = ?) ORDER BY epoch ASC, id ASC";
+
+        let query = sqlx::query_as(&query_string)
+            .bind(id_a)
+            .bind(id_b)
+            .bind(id_b)
+            .bind(id_a);
+
+        let result: Result<Vec<Match>, sqlx::Error> = query.fetch_all(&mut **self.inner).await;
+
+        match result {
+            Ok(matches) => {
+                return matches;
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return Vec::new(),
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
     /// Updates a match.
     ///
     /// Every field can be changed except id.
@@ -162,13 +193,37 @@ impl DbConnection {
         }
     }
 
-    /// Adds a match.
+    /// Removes a match.
+    ///
+    /// Does not recompute any ratings.
+    pub async fn remove_match(&mut self, id: u64) -> Result<MySqlQueryResult, sqlx::Error> {
+        let query_string = "DELETE FROM matches WHERE id = ?";
+
+        let query = sqlx::query(&query_string).bind(id);
+
+        let result = query.execute(&mut **self.inner).await;
+
+        match result {
+            Ok(result) => {
+                return Ok(result);
+            }
+            Err(e) => match e {
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Adds a match to the ladder.
     ///
     /// Ignores the id field.
     pub async fn add_match(&mut self, a_match: &Match) -> Result<MySqlQueryResult, sqlx::Error> {
-        let query_string = "INSERT INTO matches (rating_period, player_a, player_b, score_a, score_b, ping_a, ping_b, rating_a, rating_b, deviation_a, deviation_b, volatility_a, volatility_b, epoch) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+        let query_string = "INSERT INTO matches (ladder, rating_period, player_a, player_b, score_a, score_b, ping_a, ping_b, rating_a, rating_b, deviation_a, deviation_b, volatility_a, volatility_b, epoch) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
 
         let query = sqlx::query(&query_string)
+            .bind(self.ladder)
             .bind(a_match.rating_period)
             .bind(a_match.player_a)
             .bind(a_match.player_b)
@@ -191,6 +246,14 @@ impl DbConnection {
                 return Ok(result);
             }
             Err(e) => match e {
+                e if is_constraint_violation(&e) => {
+                    log::warn!(
+                        "Database query violated a constraint {} -> {}",
+                        query_string,
+                        e
+                    );
+                    return Err(e);
+                }
                 _ => {
                     log::error!("Database query failed {} -> {}", query_string, e);
                     panic!("Database query failed");
@@ -198,4 +261,130 @@ impl DbConnection {
             },
         }
     }
+
+    /// Adds several matches in a single transaction, setting their ids.
+    ///
+    /// In the same transaction, flags the ones which look like duplicates for review and links
+    /// them to their players' matchmaking pairings, so either all or none of it is written.
+    /// Returns the reviews of the flagged matches.
+    ///
+    /// Ignores the id fields.
+    pub async fn add_matches(
+        &mut self,
+        matches: &mut [Match],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<MatchReview>, sqlx::Error> {
+        let query_string = "INSERT INTO matches (ladder, rating_period, player_a, player_b, score_a, score_b, ping_a, ping_b, rating_a, rating_b, deviation_a, deviation_b, volatility_a, volatility_b, epoch) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+        let mut transaction = (&mut **self.inner).begin().await?;
+
+        for a_match in matches.iter_mut() {
+            let query = sqlx::query(&query_string)
+                .bind(self.ladder)
+                .bind(a_match.rating_period)
+                .bind(a_match.player_a)
+                .bind(a_match.player_b)
+                .bind(a_match.score_a)
+                .bind(a_match.score_b)
+                .bind(a_match.ping_a)
+                .bind(a_match.ping_b)
+                .bind(a_match.rating_a)
+                .bind(a_match.rating_b)
+                .bind(a_match.deviation_a)
+                .bind(a_match.deviation_b)
+                .bind(a_match.volatility_a)
+                .bind(a_match.volatility_b)
+                .bind(a_match.epoch);
+
+            let result = query.execute(&mut *transaction).await;
+
+            match result {
+                Ok(result) => {
+                    a_match.id = result.last_insert_id();
+                }
+                // Dropping the transaction rolls it back
+                Err(e) if is_constraint_violation(&e) => {
+                    log::warn!(
+                        "Database query violated a constraint {} -> {}",
+                        query_string,
+                        e
+                    );
+                    return Err(e);
+                }
+                Err(e) => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            }
+        }
+
+        let mut reviews = Vec::new();
+
+        for a_match in matches.iter() {
+            if let Some(review) =
+                flag_possible_duplicate(&mut *transaction, self.ladder, a_match, now).await
+            {
+                reviews.push(review);
+            }
+
+            link_match_to_matchmaking_pairing(&mut *transaction, a_match).await;
+        }
+
+        transaction.commit().await?;
+
+        Ok(reviews)
+    }
+}
+
+/// Fetches matches which look like they might be the same game as the given one:
+/// the same players with the same score, played within `window` of it, and with each player's
+/// ping differing by at most `ping_tolerance`.
+///
+/// Matches where the players are swapped also count. Only matches added before the given one
+/// (with a lower id) count, so of two copies only the later one is flagged.
+///
+/// The most recent matches are first.
+pub(super) async fn get_possible_duplicate_matches(
+    connection: &mut MySqlConnection,
+    a_match: &Match,
+    window: chrono::TimeDelta,
+    ping_tolerance: u16,
+) -> Vec<Match> {
+    let query_string = "SELECT * FROM matches WHERE id < ? AND epoch BETWEEN ? AND ? AND ((player_a = ? AND player_b = ? AND score_a = ? AND score_b = ? AND ABS(CAST(ping_a AS SIGNED) - ?) <= ? AND ABS(CAST(ping_b AS SIGNED) - ?) <= ?) OR (player_a = ? AND player_b = ? AND score_a = ? AND score_b = ? AND ABS(CAST(ping_a AS SIGNED) - ?) <= ? AND ABS(CAST(ping_b AS SIGNED) - ?) <= ?)) ORDER BY epoch DESC";
+
+    let query = sqlx::query_as(&query_string)
+        .bind(a_match.id)
+        .bind(a_match.epoch - window)
+        .bind(a_match.epoch + window)
+        .bind(a_match.player_a)
+        .bind(a_match.player_b)
+        .bind(a_match.score_a)
+        .bind(a_match.score_b)
+        .bind(a_match.ping_a)
+        .bind(ping_tolerance)
+        .bind(a_match.ping_b)
+        .bind(ping_tolerance)
+        .bind(a_match.player_b)
+        .bind(a_match.player_a)
+        .bind(a_match.score_b)
+        .bind(a_match.score_a)
+        .bind(a_match.ping_b)
+        .bind(ping_tolerance)
+        .bind(a_match.ping_a)
+        .bind(ping_tolerance);
+
+    let result: Result<Vec<Match>, sqlx::Error> = query.fetch_all(connection).await;
+
+    match result {
+        Ok(matches) => {
+            return matches;
+        }
+        Err(e) => match e {
+            sqlx::Error::RowNotFound => return Vec::new(),
+            _ => {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                panic!("Database query failed");
+            }
+        },
+    }
 }
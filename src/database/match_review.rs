@@ -0,0 +1,202 @@
+use core::panic;
+
+use chrono::{DateTime, Utc};
+use sqlx::{mysql::MySqlQueryResult, MySqlConnection};
+
+use crate::types::entities::{
+    match_review::{
+        MatchReview, MatchReviewStatus, DUPLICATE_MATCH_PING_TOLERANCE,
+        DUPLICATE_MATCH_WINDOW_MINUTES,
+    },
+    r#match::Match,
+};
+
+use super::{r#match::get_possible_duplicate_matches, DbConnection};
+
+impl DbConnection {
+    /// Fetches match reviews, optionally only ones with the given status.
+    ///
+    /// The oldest reviews are first.
+    pub async fn get_match_reviews(
+        &mut self,
+        status: Option<MatchReviewStatus>,
+    ) -> Vec<MatchReview> {
+        let query_string = match status {
+            Some(_) => {
+                "SELECT * FROM match_reviews WHERE ladder = ? AND status = ? ORDER BY id ASC"
+            }
+            None => "SELECT * FROM match_reviews WHERE ladder = ? ORDER BY id ASC",
+        };
+
+        let mut query = sqlx::query_as(&query_string).bind(self.ladder);
+
+        if let Some(status) = status {
+            query = query.bind(status.as_str());
+        }
+
+        let result: Result<Vec<MatchReview>, sqlx::Error> =
+            query.fetch_all(&mut **self.inner).await;
+
+        match result {
+            Ok(reviews) => {
+                return reviews;
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return Vec::new(),
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches a match review via its id
+    pub async fn get_match_review_by_id(&mut self, id: u64) -> Option<MatchReview> {
+        let query_string = "SELECT * FROM match_reviews WHERE ladder = ? AND id = ?";
+
+        let query = sqlx::query_as(&query_string).bind(self.ladder).bind(id);
+
+        let result: Result<MatchReview, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(review) => {
+                return Some(review);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Updates the status of a match review.
+    ///
+    /// Only status and resolved_at can be changed.
+    pub async fn modify_match_review(
+        &mut self,
+        review: &MatchReview,
+    ) -> Result<MySqlQueryResult, sqlx::Error> {
+        let query_string = "UPDATE match_reviews SET status = ?, resolved_at = ? WHERE id = ?";
+
+        let query = sqlx::query(&query_string)
+            .bind(review.status.as_str())
+            .bind(review.resolved_at)
+            .bind(review.id);
+
+        let result = query.execute(&mut **self.inner).await;
+
+        match result {
+            Ok(result) => {
+                return Ok(result);
+            }
+            Err(e) => match e {
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Checks whether an added match looks like a duplicate of an earlier one and queues it for
+    /// review by an admin if it does; see [flag_possible_duplicate]
+    pub async fn flag_possible_duplicate(
+        &mut self,
+        a_match: &Match,
+        now: DateTime<Utc>,
+    ) -> Option<MatchReview> {
+        flag_possible_duplicate(&mut **self.inner, self.ladder, a_match, now).await
+    }
+}
+
+/// Adds a match review to the ladder.
+///
+/// Ignores the id field.
+pub(super) async fn add_match_review(
+    connection: &mut MySqlConnection,
+    ladder: u64,
+    review: &MatchReview,
+) -> Result<MySqlQueryResult, sqlx::Error> {
+    let query_string = "INSERT INTO match_reviews (ladder, match_id, duplicate_of, reason, status, epoch, resolved_at) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+    let query = sqlx::query(&query_string)
+        .bind(ladder)
+        .bind(review.match_id)
+        .bind(review.duplicate_of)
+        .bind(&review.reason)
+        .bind(review.status.as_str())
+        .bind(review.epoch)
+        .bind(review.resolved_at);
+
+    let result = query.execute(connection).await;
+
+    match result {
+        Ok(result) => {
+            return Ok(result);
+        }
+        Err(e) => match e {
+            _ => {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                panic!("Database query failed");
+            }
+        },
+    }
+}
+
+/// Checks whether an added match looks like a duplicate of an earlier one; same players, same
+/// score and similar ping within a few minutes. If it does, queues it for review by an admin.
+///
+/// Only matches added before it count, so when both copies are in one bulk import only the
+/// second is flagged and rejecting its review keeps the first.
+///
+/// This is only about results that were submitted twice. Players legitimately playing several
+/// games back to back is handled by [crate::types::entities::player::Player::rate_player_for_elapsed_periods],
+/// which combines them.
+pub(super) async fn flag_possible_duplicate(
+    connection: &mut MySqlConnection,
+    ladder: u64,
+    a_match: &Match,
+    now: DateTime<Utc>,
+) -> Option<MatchReview> {
+    let duplicates = get_possible_duplicate_matches(
+        &mut *connection,
+        a_match,
+        chrono::TimeDelta::minutes(DUPLICATE_MATCH_WINDOW_MINUTES),
+        DUPLICATE_MATCH_PING_TOLERANCE,
+    )
+    .await;
+
+    let duplicate_of = duplicates.first()?;
+
+    let minutes_apart = (a_match.epoch - duplicate_of.epoch).num_minutes().abs();
+
+    let mut review = MatchReview {
+        id: 0,
+        match_id: a_match.id,
+        duplicate_of: duplicate_of.id,
+        reason: format!(
+            "Same players and score as match {}, played {} minutes apart with similar ping",
+            duplicate_of.id, minutes_apart
+        ),
+        status: MatchReviewStatus::Pending,
+        epoch: now,
+        resolved_at: None,
+    };
+
+    let result = add_match_review(connection, ladder, &review).await.unwrap();
+
+    review.id = result.last_insert_id();
+
+    log::warn!(
+        "Match {} looks like a duplicate of match {}, queued it for review ({})",
+        a_match.id,
+        duplicate_of.id,
+        review.id
+    );
+
+    Some(review)
+}
@@ -0,0 +1,129 @@
+use core::panic;
+
+use chrono::{DateTime, Utc};
+use sqlx::MySqlConnection;
+
+use crate::types::entities::{
+    matchmaking::{MatchmakingPairing, MATCHMAKING_PAIRING_LINK_HOURS},
+    r#match::Match,
+};
+
+use super::DbConnection;
+
+impl DbConnection {
+    /// Fetches a matchmaking pairing via its id
+    pub async fn get_matchmaking_pairing_by_id(&mut self, id: u64) -> Option<MatchmakingPairing> {
+        let query_string = "SELECT * FROM matchmaking_pairings WHERE player_a IN (SELECT id FROM players WHERE ladder = ?) AND id = ?";
+
+        let query = sqlx::query_as(&query_string).bind(self.ladder).bind(id);
+
+        let result: Result<MatchmakingPairing, sqlx::Error> =
+            query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(pairing) => {
+                return Some(pairing);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches a player's latest matchmaking pairing which they haven't submitted a match for
+    /// yet, if they were paired after `since`
+    pub async fn get_open_matchmaking_pairing(
+        &mut self,
+        player_id: u64,
+        since: DateTime<Utc>,
+    ) -> Option<MatchmakingPairing> {
+        let query_string = "SELECT * FROM matchmaking_pairings WHERE (player_a = ? OR player_b = ?) AND match_id IS NULL AND epoch >= ? ORDER BY epoch DESC, id DESC LIMIT 1";
+
+        let query = sqlx::query_as(&query_string)
+            .bind(player_id)
+            .bind(player_id)
+            .bind(since);
+
+        let result: Result<MatchmakingPairing, sqlx::Error> =
+            query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(pairing) => {
+                return Some(pairing);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Links a submitted match to the matchmaking pairing of its players, if they were paired
+    /// shortly before playing it.
+    ///
+    /// Returns the linked pairing.
+    pub async fn link_match_to_matchmaking_pairing(
+        &mut self,
+        a_match: &Match,
+    ) -> Option<MatchmakingPairing> {
+        link_match_to_matchmaking_pairing(&mut **self.inner, a_match).await
+    }
+}
+
+/// Links a submitted match to the matchmaking pairing of its players, if they were paired
+/// shortly before playing it.
+///
+/// Returns the linked pairing.
+pub(super) async fn link_match_to_matchmaking_pairing(
+    connection: &mut MySqlConnection,
+    a_match: &Match,
+) -> Option<MatchmakingPairing> {
+    let query_string = "SELECT * FROM matchmaking_pairings WHERE ((player_a = ? AND player_b = ?) OR (player_a = ? AND player_b = ?)) AND match_id IS NULL AND epoch BETWEEN ? AND ? ORDER BY epoch DESC, id DESC LIMIT 1";
+
+    let query = sqlx::query_as(&query_string)
+        .bind(a_match.player_a)
+        .bind(a_match.player_b)
+        .bind(a_match.player_b)
+        .bind(a_match.player_a)
+        .bind(a_match.epoch - chrono::TimeDelta::hours(MATCHMAKING_PAIRING_LINK_HOURS))
+        .bind(a_match.epoch);
+
+    let result: Result<MatchmakingPairing, sqlx::Error> = query.fetch_one(&mut *connection).await;
+
+    let mut pairing = match result {
+        Ok(pairing) => pairing,
+        Err(e) => match e {
+            sqlx::Error::RowNotFound => return None,
+            _ => {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                panic!("Database query failed");
+            }
+        },
+    };
+
+    let query_string = "UPDATE matchmaking_pairings SET match_id = ? WHERE id = ?";
+
+    let query = sqlx::query(&query_string).bind(a_match.id).bind(pairing.id);
+
+    let result = query.execute(&mut *connection).await;
+
+    match result {
+        Ok(_) => {
+            pairing.match_id = Some(a_match.id);
+            return Some(pairing);
+        }
+        Err(e) => match e {
+            _ => {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                panic!("Database query failed");
+            }
+        },
+    }
+}
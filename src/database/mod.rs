@@ -8,21 +8,60 @@ use rocket_db_pools::{Connection, Database};
 use log::{error, info};
 use season_handler::initialize_season_handler;
 
-use crate::{glicko, types::entities::season::Season, MysqlDb};
+use crate::{
+    clock::SharedClock,
+    glicko,
+    types::entities::{
+        ladder::{Ladder, DEFAULT_LADDER_ID},
+        season::Season,
+    },
+    MysqlDb,
+};
+pub mod ladder;
 pub mod r#match;
+pub mod match_review;
+pub mod matchmaking;
 pub mod player;
+pub mod player_alias;
 pub mod query;
+pub mod rating_snapshot;
 pub mod recent_request;
 pub mod season;
 pub mod season_handler;
 
+/// Returns whether a database error is a constraint violation; a duplicate of a unique value or
+/// a reference to a row which doesn't exist.
+///
+/// These are caused by the request (or one made at the same time), so queries which can cause
+/// them return the error instead of panicking, see [crate::response::ApiError::from_constraint_violation].
+pub fn is_constraint_violation(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(e) => e.is_unique_violation() || e.is_foreign_key_violation(),
+        _ => false,
+    }
+}
+
 pub struct DbConnection {
     pub inner: Connection<MysqlDb>,
+    /// Id of the ladder players, matches and rating periods are fetched from and added to
+    pub ladder: u64,
 }
 
 impl DbConnection {
+    /// Creates a connection to the default ladder
     pub fn from_inner(inner: Connection<MysqlDb>) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            ladder: DEFAULT_LADDER_ID,
+        }
+    }
+
+    /// Creates a connection to the given ladder
+    pub fn for_ladder(inner: Connection<MysqlDb>, ladder: &Ladder) -> Self {
+        Self {
+            inner,
+            ladder: ladder.id,
+        }
     }
 }
 
@@ -31,7 +70,9 @@ impl DbConnection {
 async fn handle_seasons(rocket: Rocket<Build>) -> fairing::Result {
     match MysqlDb::fetch(&rocket) {
         Some(db) => {
-            initialize_season_handler(db).await;
+            let clock = rocket.state::<SharedClock>().unwrap().clone();
+
+            initialize_season_handler(db, clock).await;
             Ok(rocket)
         }
         None => Err(rocket),
@@ -1,10 +1,11 @@
 use core::panic;
 
-use sqlx::mysql::MySqlQueryResult;
+use chrono::{DateTime, Utc};
+use sqlx::{mysql::MySqlQueryResult, Connection};
 
 use crate::types::entities::player::Player;
 
-use super::{query::QueryParameters, DbConnection};
+use super::{is_constraint_violation, query::QueryParameters, DbConnection};
 
 impl DbConnection {
     /// Fetches all the players.
@@ -35,7 +36,31 @@ impl DbConnection {
         }
     }
 
-    /// Fetches all players with a similar username to the input string.
+    /// Fetches the players who existed at the given time; who were added by then, or played a
+    /// match by then (for matches imported with an earlier epoch).
+    pub async fn get_players_existing_at(&mut self, at: DateTime<Utc>) -> Vec<Player> {
+        let query_string = "SELECT * FROM players WHERE ladder = ? AND (created_at <= ? OR EXISTS (SELECT 1 FROM matches WHERE (matches.player_a = players.id OR matches.player_b = players.id) AND matches.epoch <= ?))";
+
+        let query = sqlx::query_as(query_string)
+            .bind(self.ladder)
+            .bind(at)
+            .bind(at);
+
+        let result = query.fetch_all(&mut **self.inner).await;
+
+        match result {
+            Ok(vec) => vec,
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Vec::new(),
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches all players with a similar username, or old username, to the input string.
     ///
     /// Also uses query_parameters to set order_by, max, min, ...
     pub async fn search_players(
@@ -43,11 +68,16 @@ impl DbConnection {
         search_string: &str,
         query_parameters: QueryParameters,
     ) -> Vec<Player> {
-        let query_string = "SELECT * FROM players WHERE name LIKE ?";
+        let query_string = "SELECT * FROM players";
+
+        let query_parameters = QueryParameters {
+            name_contains: Some(search_string.to_string()),
+            ..query_parameters
+        };
 
         let (query_string, parameters) = self.add_to_query(query_string, query_parameters).await;
 
-        let mut query = sqlx::query_as(query_string.as_str()).bind(format!("%{search_string}%"));
+        let mut query = sqlx::query_as(query_string.as_str());
 
         for parameter in parameters {
             query = query.bind(parameter);
@@ -68,24 +98,18 @@ impl DbConnection {
     }
 
     /// Fetches a player by name
+    ///
+    /// Names are unique within a ladder
     pub async fn get_player_by_name(&mut self, name: &str) -> Option<Player> {
-        let query_string = "SELECT * FROM players WHERE name = ?";
+        let query_string = "SELECT * FROM players WHERE ladder = ? AND name = ?";
 
-        let query = sqlx::query_as(&query_string).bind(name);
+        let query = sqlx::query_as(&query_string).bind(self.ladder).bind(name);
 
-        let result: Result<Vec<Player>, sqlx::Error> = query.fetch_all(&mut **self.inner).await;
+        let result: Result<Player, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
 
         match result {
-            Ok(vec) => {
-                if vec.len() < 1 {
-                    return None;
-                }
-
-                if vec.len() > 1 {
-                    log::warn!("More than one player has name {}!", name);
-                }
-
-                return Some(vec[0].clone());
+            Ok(player) => {
+                return Some(player);
             }
             Err(e) => match e {
                 sqlx::Error::RowNotFound => return None,
@@ -99,9 +123,9 @@ impl DbConnection {
 
     /// Fetches a player by id
     pub async fn get_player_by_id(&mut self, id: u64) -> Option<Player> {
-        let query_string = "SELECT * FROM players WHERE id = ?";
+        let query_string = "SELECT * FROM players WHERE ladder = ? AND id = ?";
 
-        let query = sqlx::query_as(&query_string).bind(id);
+        let query = sqlx::query_as(&query_string).bind(self.ladder).bind(id);
 
         let result: Result<Player, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
 
@@ -121,7 +145,7 @@ impl DbConnection {
 
     /// Fetches a player by id or name
     ///
-    /// Id takes priority over name
+    /// Id takes priority over name, and current names take priority over old names (aliases)
     // TODO: Impose a restriction that player's name could not ever be ids
     pub async fn get_player_by_id_or_name(&mut self, query: &str) -> Option<Player> {
         let as_u64_res = query.parse::<u64>();
@@ -140,9 +164,41 @@ impl DbConnection {
             return Some(player);
         }
 
+        let as_alias = self.get_player_by_alias(query).await;
+
+        if let Some(player) = as_alias {
+            return Some(player);
+        }
+
         None
     }
 
+    /// Counts the other players who rank above the player at the given time; who are not
+    /// suspended or banned then and have a higher rating, or the same rating and were added first.
+    ///
+    /// The player's own rating may differ from the stored one, e.g. be their live rating.
+    pub async fn count_players_ranked_above(&mut self, player: &Player, at: DateTime<Utc>) -> u64 {
+        let query_string = "SELECT COUNT(*) FROM players WHERE ladder = ? AND id != ? AND (status = 'active' OR status_expires <= ?) AND (rating > ? OR (rating = ? AND id < ?))";
+
+        let query = sqlx::query_scalar(&query_string)
+            .bind(self.ladder)
+            .bind(player.id)
+            .bind(at)
+            .bind(player.rating)
+            .bind(player.rating)
+            .bind(player.id);
+
+        let result: Result<i64, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(count) => count as u64,
+            Err(e) => {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                panic!("Database query failed");
+            }
+        }
+    }
+
     /// Updates a player.
     ///
     /// Every field can be changed except id.
@@ -150,14 +206,55 @@ impl DbConnection {
         &mut self,
         player: &Player,
     ) -> Result<MySqlQueryResult, sqlx::Error> {
-        let query_string =
-            "UPDATE players SET name = ?, rating = ?, deviation = ?, volatility = ? WHERE id = ?";
+        let query_string = "UPDATE players SET name = ?, rating = ?, deviation = ?, volatility = ?, platform = ?, region = ?, clan = ?, discord_id = ?, joined_at = ? WHERE id = ?";
 
         let query = sqlx::query(&query_string)
             .bind(&player.name)
             .bind(player.rating)
             .bind(player.deviation)
             .bind(player.volatility)
+            .bind(player.profile.platform.map(|x| x.as_str()))
+            .bind(&player.profile.region)
+            .bind(&player.profile.clan)
+            .bind(&player.profile.discord_id)
+            .bind(player.profile.joined_at)
+            .bind(player.id);
+
+        let result = query.execute(&mut **self.inner).await;
+
+        match result {
+            Ok(result) => {
+                return Ok(result);
+            }
+            Err(e) => match e {
+                e if is_constraint_violation(&e) => {
+                    log::warn!(
+                        "Database query violated a constraint {} -> {}",
+                        query_string,
+                        e
+                    );
+                    return Err(e);
+                }
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Updates a player's status, along with its reason, start and expiry
+    pub async fn set_player_status(
+        &mut self,
+        player: &Player,
+    ) -> Result<MySqlQueryResult, sqlx::Error> {
+        let query_string = "UPDATE players SET status = ?, status_reason = ?, status_since = ?, status_expires = ? WHERE id = ?";
+
+        let query = sqlx::query(&query_string)
+            .bind(player.status.as_str())
+            .bind(&player.status_reason)
+            .bind(player.status_since)
+            .bind(player.status_expires)
             .bind(player.id);
 
         let result = query.execute(&mut **self.inner).await;
@@ -175,18 +272,28 @@ impl DbConnection {
         }
     }
 
-    /// Adds a player.
+    /// Adds a player to the ladder, created at the given time.
     ///
     /// Ignores the id field.
-    pub async fn add_player(&mut self, player: &Player) -> Result<MySqlQueryResult, sqlx::Error> {
-        let query_string =
-            "INSERT INTO players (name, rating, deviation, volatility) VALUES (?, ?, ?, ?)";
+    pub async fn add_player(
+        &mut self,
+        player: &Player,
+        now: DateTime<Utc>,
+    ) -> Result<MySqlQueryResult, sqlx::Error> {
+        let query_string = "INSERT INTO players (ladder, name, rating, deviation, volatility, platform, region, clan, discord_id, joined_at, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
 
         let query = sqlx::query(&query_string)
+            .bind(self.ladder)
             .bind(&player.name)
             .bind(player.rating)
             .bind(player.deviation)
-            .bind(player.volatility);
+            .bind(player.volatility)
+            .bind(player.profile.platform.map(|x| x.as_str()))
+            .bind(&player.profile.region)
+            .bind(&player.profile.clan)
+            .bind(&player.profile.discord_id)
+            .bind(player.profile.joined_at)
+            .bind(now);
 
         let result = query.execute(&mut **self.inner).await;
 
@@ -195,6 +302,14 @@ impl DbConnection {
                 return Ok(result);
             }
             Err(e) => match e {
+                e if is_constraint_violation(&e) => {
+                    log::warn!(
+                        "Database query violated a constraint {} -> {}",
+                        query_string,
+                        e
+                    );
+                    return Err(e);
+                }
                 _ => {
                     log::error!("Database query failed {} -> {}", query_string, e);
                     panic!("Database query failed");
@@ -202,4 +317,116 @@ impl DbConnection {
             },
         }
     }
+
+    /// Merges a duplicate player into another one, in a single transaction.
+    ///
+    /// All matches of the removed player are moved to the kept one, except matches between the
+    /// two which are deleted, since a player cannot play against themselves. Pending reviews of
+    /// the deleted matches are deleted too. The same goes for their matchmaking pairings. The
+    /// removed player's name and aliases become aliases of the kept one. Their rating snapshots
+    /// are kept for the rating periods the kept player has none for (because they did not exist
+    /// yet).
+    ///
+    /// The removed player's name becomes an alias from the given time.
+    ///
+    /// Does not recompute any ratings. Returns the id of the first rating period with an
+    /// affected match, if any.
+    pub async fn merge_players(
+        &mut self,
+        keep: &Player,
+        remove: &Player,
+        now: DateTime<Utc>,
+    ) -> Result<Option<u64>, sqlx::Error> {
+        let mut transaction = (&mut **self.inner).begin().await?;
+
+        let first_season: Option<u64> = sqlx::query_scalar(
+            "SELECT MIN(rating_period) FROM matches WHERE player_a = ? OR player_b = ?",
+        )
+        .bind(remove.id)
+        .bind(remove.id)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        // Their matches against each other are deleted below, so reviews of them can't be
+        // resolved anymore. Resolved reviews are kept, like for rejected matches
+        let query_string = "DELETE FROM match_reviews WHERE status = 'pending' AND (match_id IN (SELECT id FROM matches WHERE (player_a = ? AND player_b = ?) OR (player_a = ? AND player_b = ?)) OR duplicate_of IN (SELECT id FROM matches WHERE (player_a = ? AND player_b = ?) OR (player_a = ? AND player_b = ?)))";
+
+        let query = sqlx::query(&query_string)
+            .bind(keep.id)
+            .bind(remove.id)
+            .bind(remove.id)
+            .bind(keep.id)
+            .bind(keep.id)
+            .bind(remove.id)
+            .bind(remove.id)
+            .bind(keep.id);
+
+        if let Err(e) = query.execute(&mut *transaction).await {
+            // Dropping the transaction rolls it back
+            log::error!("Database query failed {} -> {}", query_string, e);
+            return Err(e);
+        }
+
+        // A player cannot play against themselves
+        for query_string in [
+            "DELETE FROM matches WHERE (player_a = ? AND player_b = ?) OR (player_a = ? AND player_b = ?)",
+            "DELETE FROM matchmaking_pairings WHERE (player_a = ? AND player_b = ?) OR (player_a = ? AND player_b = ?)",
+        ] {
+            let query = sqlx::query(query_string)
+                .bind(keep.id)
+                .bind(remove.id)
+                .bind(remove.id)
+                .bind(keep.id);
+
+            if let Err(e) = query.execute(&mut *transaction).await {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                return Err(e);
+            }
+        }
+
+        for query_string in [
+            "UPDATE matches SET player_a = ? WHERE player_a = ?",
+            "UPDATE matches SET player_b = ? WHERE player_b = ?",
+            "UPDATE matchmaking_pairings SET player_a = ? WHERE player_a = ?",
+            "UPDATE matchmaking_pairings SET player_b = ? WHERE player_b = ?",
+            "UPDATE player_aliases SET player_id = ? WHERE player_id = ?",
+            // Ignore keeps the kept player's own snapshots where both have one
+            "UPDATE IGNORE rating_snapshots SET player_id = ? WHERE player_id = ?",
+        ] {
+            let query = sqlx::query(query_string).bind(keep.id).bind(remove.id);
+
+            if let Err(e) = query.execute(&mut *transaction).await {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                return Err(e);
+            }
+        }
+
+        let query_string = "INSERT INTO player_aliases (player_id, name, epoch) VALUES (?, ?, ?)";
+
+        let query = sqlx::query(&query_string)
+            .bind(keep.id)
+            .bind(&remove.name)
+            .bind(now);
+
+        if let Err(e) = query.execute(&mut *transaction).await {
+            log::error!("Database query failed {} -> {}", query_string, e);
+            return Err(e);
+        }
+
+        for query_string in [
+            "DELETE FROM rating_snapshots WHERE player_id = ?",
+            "DELETE FROM players WHERE id = ?",
+        ] {
+            let query = sqlx::query(query_string).bind(remove.id);
+
+            if let Err(e) = query.execute(&mut *transaction).await {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                return Err(e);
+            }
+        }
+
+        transaction.commit().await?;
+
+        Ok(first_season)
+    }
 }
@@ -0,0 +1,164 @@
+use core::panic;
+
+use sqlx::Connection;
+
+use crate::{
+    search::{fuzzy_search_length_range, normalize_username},
+    types::entities::{player::Player, player_alias::PlayerAlias},
+};
+
+use super::{is_constraint_violation, DbConnection};
+
+impl DbConnection {
+    /// Fetches a player's old names, by their id, latest first
+    pub async fn get_player_aliases(&mut self, player_id: u64) -> Vec<PlayerAlias> {
+        let query_string =
+            "SELECT * FROM player_aliases WHERE player_id = ? ORDER BY epoch DESC, id DESC";
+
+        let query = sqlx::query_as(&query_string).bind(player_id);
+
+        let result: Result<Vec<PlayerAlias>, sqlx::Error> =
+            query.fetch_all(&mut **self.inner).await;
+
+        match result {
+            Ok(aliases) => {
+                return aliases;
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return Vec::new(),
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches the current and old names of the ladder's players a fuzzy search could find, as
+    /// (player id, name).
+    ///
+    /// Only names which contain the search, or whose length is in [fuzzy_search_length_range],
+    /// can be similar enough; see [crate::search::username_similarity]. The names still need to
+    /// be scored.
+    pub async fn get_fuzzy_search_candidates(&mut self, search: &str) -> Vec<(u64, String)> {
+        let search = normalize_username(search);
+
+        let (min_length, max_length) = fuzzy_search_length_range(&search);
+
+        // Wildcards in the search only find more candidates, which are scored anyway
+        let pattern = format!("%{search}%");
+
+        let query_string = "SELECT id, name FROM players WHERE ladder = ? AND (LOWER(name) LIKE ? OR CHAR_LENGTH(name) - IF(name REGEXP '.#[0-9]{3}$', 4, 0) BETWEEN ? AND ?) UNION ALL SELECT player_aliases.player_id, player_aliases.name FROM player_aliases INNER JOIN players ON players.id = player_aliases.player_id WHERE players.ladder = ? AND (LOWER(player_aliases.name) LIKE ? OR CHAR_LENGTH(player_aliases.name) - IF(player_aliases.name REGEXP '.#[0-9]{3}$', 4, 0) BETWEEN ? AND ?)";
+
+        let query = sqlx::query_as(&query_string)
+            .bind(self.ladder)
+            .bind(&pattern)
+            .bind(min_length as u64)
+            .bind(max_length as u64)
+            .bind(self.ladder)
+            .bind(&pattern)
+            .bind(min_length as u64)
+            .bind(max_length as u64);
+
+        let result: Result<Vec<(u64, String)>, sqlx::Error> =
+            query.fetch_all(&mut **self.inner).await;
+
+        match result {
+            Ok(candidates) => {
+                return candidates;
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return Vec::new(),
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches a player by one of their old names.
+    ///
+    /// If several players used the name, returns the one who used it most recently.
+    pub async fn get_player_by_alias(&mut self, name: &str) -> Option<Player> {
+        let query_string = "SELECT players.* FROM players INNER JOIN player_aliases ON player_aliases.player_id = players.id WHERE players.ladder = ? AND player_aliases.name = ? ORDER BY player_aliases.epoch DESC, player_aliases.id DESC LIMIT 1";
+
+        let query = sqlx::query_as(&query_string).bind(self.ladder).bind(name);
+
+        let result: Result<Player, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(player) => {
+                return Some(player);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Renames a player to their name in `player`, keeping their old name as an alias, in a single
+    /// transaction.
+    ///
+    /// If they go back to an old name, that alias is removed since it is their current name
+    /// again. Ignores the alias' id field.
+    pub async fn rename_player(
+        &mut self,
+        player: &Player,
+        alias: &PlayerAlias,
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = (&mut **self.inner).begin().await?;
+
+        let query_string = "UPDATE players SET name = ? WHERE id = ?";
+
+        let query = sqlx::query(&query_string)
+            .bind(&player.name)
+            .bind(player.id);
+
+        match query.execute(&mut *transaction).await {
+            Ok(_) => {}
+            // Dropping the transaction rolls it back
+            Err(e) if is_constraint_violation(&e) => {
+                log::warn!(
+                    "Database query violated a constraint {} -> {}",
+                    query_string,
+                    e
+                );
+                return Err(e);
+            }
+            Err(e) => {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                panic!("Database query failed");
+            }
+        }
+
+        let query_string = "INSERT INTO player_aliases (player_id, name, epoch) VALUES (?, ?, ?)";
+
+        let query = sqlx::query(&query_string)
+            .bind(alias.player_id)
+            .bind(&alias.name)
+            .bind(alias.epoch);
+
+        if let Err(e) = query.execute(&mut *transaction).await {
+            log::error!("Database query failed {} -> {}", query_string, e);
+            panic!("Database query failed");
+        }
+
+        let query_string = "DELETE FROM player_aliases WHERE player_id = ? AND name = ?";
+
+        let query = sqlx::query(&query_string)
+            .bind(player.id)
+            .bind(&player.name);
+
+        if let Err(e) = query.execute(&mut *transaction).await {
+            log::error!("Database query failed {} -> {}", query_string, e);
+            panic!("Database query failed");
+        }
+
+        transaction.commit().await
+    }
+}
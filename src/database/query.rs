@@ -1,7 +1,15 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use log::debug;
+use sqlx::{
+    encode::IsNull,
+    mysql::{MySql, MySqlTypeInfo},
+    Encode, Type,
+};
 
-use crate::types::entities::player::Player;
+use crate::{
+    response::ApiError,
+    types::entities::player::{Platform, Player},
+};
 
 use super::DbConnection;
 
@@ -18,6 +26,19 @@ pub struct QueryParameters {
     pub max_volatility: Option<f64>,
     pub min_volatility: Option<f64>,
 
+    /// Leave out players who are suspended or banned at this time
+    pub active_at: Option<DateTime<Utc>>,
+
+    /// Only players whose current or old name contains it
+    pub name_contains: Option<String>,
+
+    /// Only players with one of these ids
+    pub player_ids: Option<Vec<u64>>,
+
+    pub platform: Option<Platform>,
+    pub region: Option<String>,
+    pub clan: Option<String>,
+
     // Matches
     pub after: Option<DateTime<Utc>>,
     pub before: Option<DateTime<Utc>>,
@@ -34,6 +55,128 @@ pub struct QueryParameters {
     pub sort: Option<String>,
     pub limit: Option<usize>,
     pub offset: Option<usize>,
+
+    // Pagination
+    pub keyset: Option<Keyset>,
+}
+
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+/// Keyset (cursor) pagination; orders rows by unique keys and only returns the rows after
+/// a cursor.
+///
+/// Unlike offsets, cursors stay correct when rows are added while paging.
+pub enum Keyset {
+    /// Ordered by id, ascending. Used for players and rating periods.
+    Id { after: Option<u64> },
+    /// Ordered by epoch then id, latest first. Used for matches.
+    EpochDescending {
+        before: Option<(DateTime<Utc>, u64)>,
+    },
+}
+
+impl Keyset {
+    /// Creates an id keyset from the ?cursor url parameter
+    pub fn id_from_cursor(cursor: Option<&str>) -> Result<Self, ApiError> {
+        let Some(cursor) = cursor else {
+            return Ok(Keyset::Id { after: None });
+        };
+
+        match cursor.parse::<u64>() {
+            Ok(id) => Ok(Keyset::Id { after: Some(id) }),
+            Err(_) => Err(ApiError::invalid_field("Invalid cursor.")),
+        }
+    }
+
+    /// Creates an epoch keyset from the ?cursor url parameter
+    pub fn epoch_from_cursor(cursor: Option<&str>) -> Result<Self, ApiError> {
+        let Some(cursor) = cursor else {
+            return Ok(Keyset::EpochDescending { before: None });
+        };
+
+        let parsed = cursor.split_once('_').and_then(|(millis, id)| {
+            let epoch = Utc.timestamp_millis_opt(millis.parse().ok()?).single()?;
+            Some((epoch, id.parse::<u64>().ok()?))
+        });
+
+        match parsed {
+            Some(before) => Ok(Keyset::EpochDescending {
+                before: Some(before),
+            }),
+            None => Err(ApiError::invalid_field("Invalid cursor.")),
+        }
+    }
+}
+
+/// Parses the ?platform url parameter
+pub fn platform_from_parameter(platform: Option<&str>) -> Result<Option<Platform>, ApiError> {
+    let Some(platform) = platform else {
+        return Ok(None);
+    };
+
+    match Platform::from_str(platform) {
+        Some(platform) => Ok(Some(platform)),
+        None => Err(ApiError::invalid_field(
+            "Invalid platform, expected one of pc, playstation, xbox or switch.",
+        )),
+    }
+}
+
+/// Creates the ?cursor to continue after a row ordered by [Keyset::Id]
+pub fn id_cursor(id: u64) -> String {
+    id.to_string()
+}
+
+/// Creates the ?cursor to continue after a row ordered by [Keyset::EpochDescending]
+pub fn epoch_cursor(epoch: DateTime<Utc>, id: u64) -> String {
+    format!("{}_{}", epoch.timestamp_millis(), id)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// A value to bind to a query made by [DbConnection::add_to_query]
+pub enum QueryValue {
+    Text(String),
+    /// Bound as a datetime, so MySQL compares it as one rather than as a string
+    DateTime(DateTime<Utc>),
+}
+
+impl From<String> for QueryValue {
+    fn from(value: String) -> Self {
+        QueryValue::Text(value)
+    }
+}
+
+impl From<DateTime<Utc>> for QueryValue {
+    fn from(value: DateTime<Utc>) -> Self {
+        QueryValue::DateTime(value)
+    }
+}
+
+impl Type<MySql> for QueryValue {
+    fn type_info() -> MySqlTypeInfo {
+        <String as Type<MySql>>::type_info()
+    }
+
+    fn compatible(ty: &MySqlTypeInfo) -> bool {
+        <String as Type<MySql>>::compatible(ty) || <DateTime<Utc> as Type<MySql>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, MySql> for QueryValue {
+    fn encode_by_ref(&self, buf: &mut Vec<u8>) -> IsNull {
+        match self {
+            QueryValue::Text(text) => <String as Encode<MySql>>::encode_by_ref(text, buf),
+            QueryValue::DateTime(datetime) => {
+                <DateTime<Utc> as Encode<MySql>>::encode_by_ref(datetime, buf)
+            }
+        }
+    }
+
+    fn produces(&self) -> Option<MySqlTypeInfo> {
+        match self {
+            QueryValue::Text(_) => Some(<String as Type<MySql>>::type_info()),
+            QueryValue::DateTime(_) => Some(<DateTime<Utc> as Type<MySql>>::type_info()),
+        }
+    }
 }
 
 impl QueryParameters {
@@ -68,6 +211,26 @@ impl QueryParameters {
                     fits = fits & (player.volatility >= min_volatility);
                 }
 
+                if let Some(active_at) = self.active_at {
+                    fits = fits & player.is_active(active_at);
+                }
+
+                if let Some(player_ids) = &self.player_ids {
+                    fits = fits & player_ids.contains(&player.id);
+                }
+
+                if let Some(platform) = self.platform {
+                    fits = fits & (player.profile.platform == Some(platform));
+                }
+
+                if let Some(region) = &self.region {
+                    fits = fits & (player.profile.region.as_ref() == Some(region));
+                }
+
+                if let Some(clan) = &self.clan {
+                    fits = fits & (player.profile.clan.as_ref() == Some(clan));
+                }
+
                 fits
             })
             .collect::<Vec<Player>>();
@@ -125,11 +288,11 @@ impl QueryParameters {
         let mut ending_index = filtered.len();
 
         if let Some(offset) = self.offset {
-            starting_index += offset;
+            starting_index = (starting_index + offset).min(filtered.len());
         }
 
         if let Some(limit) = self.limit {
-            ending_index = starting_index + limit;
+            ending_index = (starting_index + limit).min(filtered.len());
         }
 
         filtered[starting_index..ending_index].to_vec()
@@ -229,6 +392,38 @@ pub fn parse_sort_paramter(sort_options: &str) -> Vec<(String, String)> {
 }
 
 impl DbConnection {
+    /// Counts the rows which match the query parameters, base should be something like
+    /// "SELECT COUNT(*) FROM players".
+    ///
+    /// Sorting and pagination parameters are ignored.
+    pub async fn count_rows(&mut self, base: &'static str, parameters: QueryParameters) -> u64 {
+        let parameters = QueryParameters {
+            sort: None,
+            limit: None,
+            offset: None,
+            keyset: None,
+            ..parameters
+        };
+
+        let (query_string, parameters) = self.add_to_query(base, parameters).await;
+
+        let mut query = sqlx::query_scalar(&query_string);
+
+        for parameter in parameters {
+            query = query.bind(parameter);
+        }
+
+        let result: Result<i64, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(count) => count as u64,
+            Err(e) => {
+                log::error!("Database query failed {} -> {}", query_string, e);
+                panic!("Database query failed");
+            }
+        }
+    }
+
     /// Function that takes url args (?max, ?min, ?player...) and makes an sql query
     ///
     /// Starting parameter index is the next index to use. By default, this should be 1.
@@ -261,7 +456,7 @@ impl DbConnection {
         &mut self,
         base: &'static str,
         parameters: QueryParameters,
-    ) -> (String, Vec<String>) {
+    ) -> (String, Vec<QueryValue>) {
         let mut query = base.to_string();
 
         // Added parameters we'll need to bind
@@ -269,6 +464,72 @@ impl DbConnection {
 
         let mut first_parameter = true;
 
+        // Players, matches and rating periods all belong to a ladder
+        match first_parameter {
+            true => {
+                query.push_str(" WHERE ");
+                first_parameter = false;
+            }
+            false => {
+                query.push_str(" AND ");
+            }
+        }
+
+        query.push_str("ladder = ?");
+
+        added_parameters.push(self.ladder.to_string().into());
+
+        if let Some(name_contains) = parameters.name_contains {
+            debug!("Got valid url parameter name_contains: {}", name_contains);
+
+            let mut to_add = String::new();
+
+            match first_parameter {
+                true => {
+                    to_add.push_str(" WHERE ");
+                    first_parameter = false;
+                }
+                false => {
+                    to_add.push_str(" AND ");
+                }
+            }
+
+            to_add.push_str(
+                "(name LIKE ? OR id IN (SELECT player_id FROM player_aliases WHERE name LIKE ?))",
+            );
+            query.push_str(to_add.as_str());
+
+            added_parameters.push(format!("%{name_contains}%").into());
+            added_parameters.push(format!("%{name_contains}%").into());
+        }
+
+        if let Some(player_ids) = parameters.player_ids {
+            debug!("Got valid url parameter player_ids: {:?}", player_ids);
+
+            let mut to_add = String::new();
+
+            match first_parameter {
+                true => {
+                    to_add.push_str(" WHERE ");
+                    first_parameter = false;
+                }
+                false => {
+                    to_add.push_str(" AND ");
+                }
+            }
+
+            match player_ids.len() {
+                0 => to_add.push_str("FALSE"),
+                len => to_add.push_str(&format!("id IN ({})", vec!["?"; len].join(", "))),
+            }
+
+            query.push_str(to_add.as_str());
+
+            for player_id in player_ids {
+                added_parameters.push(player_id.to_string().into());
+            }
+        }
+
         if let Some(max_rating) = parameters.max_rating {
             debug!("Got valid url parameter max_rating: {}", max_rating);
 
@@ -287,7 +548,7 @@ impl DbConnection {
             to_add.push_str("rating <= ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(max_rating.to_string());
+            added_parameters.push(max_rating.to_string().into());
         }
 
         if let Some(min_rating) = parameters.min_rating {
@@ -308,7 +569,7 @@ impl DbConnection {
             to_add.push_str("rating >= ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(min_rating.to_string());
+            added_parameters.push(min_rating.to_string().into());
         }
 
         if let Some(max_deviation) = parameters.max_deviation {
@@ -329,7 +590,7 @@ impl DbConnection {
             to_add.push_str("deviation <= ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(max_deviation.to_string());
+            added_parameters.push(max_deviation.to_string().into());
         }
 
         if let Some(min_deviation) = parameters.min_deviation {
@@ -350,7 +611,7 @@ impl DbConnection {
             to_add.push_str("deviation >= ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(min_deviation.to_string());
+            added_parameters.push(min_deviation.to_string().into());
         }
 
         if let Some(max_volatility) = parameters.max_volatility {
@@ -371,7 +632,7 @@ impl DbConnection {
             to_add.push_str("volatility <= ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(max_volatility.to_string());
+            added_parameters.push(max_volatility.to_string().into());
         }
 
         if let Some(min_volatility) = parameters.min_volatility {
@@ -392,7 +653,92 @@ impl DbConnection {
             to_add.push_str("volatility >= ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(min_volatility.to_string());
+            added_parameters.push(min_volatility.to_string().into());
+        }
+
+        if let Some(active_at) = parameters.active_at {
+            debug!("Only including players active at {}", active_at);
+
+            let mut to_add = String::new();
+
+            match first_parameter {
+                true => {
+                    to_add.push_str(" WHERE ");
+                    first_parameter = false;
+                }
+                false => {
+                    to_add.push_str(" AND ");
+                }
+            }
+
+            // Suspensions which have expired don't count
+            to_add.push_str("(status = 'active' OR status_expires <= ?)");
+            query.push_str(to_add.as_str());
+
+            added_parameters.push(active_at.into());
+        }
+
+        if let Some(platform) = parameters.platform {
+            debug!("Got valid url parameter platform: {}", platform.as_str());
+
+            let mut to_add = String::new();
+
+            match first_parameter {
+                true => {
+                    to_add.push_str(" WHERE ");
+                    first_parameter = false;
+                }
+                false => {
+                    to_add.push_str(" AND ");
+                }
+            }
+
+            to_add.push_str("platform = ?");
+            query.push_str(to_add.as_str());
+
+            added_parameters.push(platform.as_str().to_string().into());
+        }
+
+        if let Some(region) = parameters.region {
+            debug!("Got valid url parameter region: {}", region);
+
+            let mut to_add = String::new();
+
+            match first_parameter {
+                true => {
+                    to_add.push_str(" WHERE ");
+                    first_parameter = false;
+                }
+                false => {
+                    to_add.push_str(" AND ");
+                }
+            }
+
+            to_add.push_str("region = ?");
+            query.push_str(to_add.as_str());
+
+            added_parameters.push(region.into());
+        }
+
+        if let Some(clan) = parameters.clan {
+            debug!("Got valid url parameter clan: {}", clan);
+
+            let mut to_add = String::new();
+
+            match first_parameter {
+                true => {
+                    to_add.push_str(" WHERE ");
+                    first_parameter = false;
+                }
+                false => {
+                    to_add.push_str(" AND ");
+                }
+            }
+
+            to_add.push_str("clan = ?");
+            query.push_str(to_add.as_str());
+
+            added_parameters.push(clan.into());
         }
 
         if let Some(has_player_requirements) = parameters.has_player {
@@ -425,8 +771,8 @@ impl DbConnection {
                 to_add.push_str("(player_a = ? OR player_b = ?)");
                 query.push_str(to_add.as_str());
 
-                added_parameters.push(player.id.to_string());
-                added_parameters.push(player.id.to_string());
+                added_parameters.push(player.id.to_string().into());
+                added_parameters.push(player.id.to_string().into());
             }
         }
 
@@ -448,7 +794,7 @@ impl DbConnection {
             to_add.push_str("epoch < ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(before.to_string());
+            added_parameters.push(before.into());
         }
 
         if let Some(after) = parameters.after {
@@ -469,7 +815,7 @@ impl DbConnection {
             to_add.push_str("epoch > ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(after.to_string());
+            added_parameters.push(after.into());
         }
 
         if let Some(season) = parameters.season {
@@ -490,7 +836,7 @@ impl DbConnection {
             to_add.push_str("rating_period = ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(season.to_string());
+            added_parameters.push(season.to_string().into());
         }
 
         if let Some(start_before) = parameters.start_before {
@@ -511,7 +857,7 @@ impl DbConnection {
             to_add.push_str("start < ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(start_before.to_string());
+            added_parameters.push(start_before.into());
         }
 
         if let Some(start_after) = parameters.start_after {
@@ -532,7 +878,7 @@ impl DbConnection {
             to_add.push_str("start > ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(start_after.to_string());
+            added_parameters.push(start_after.into());
         }
 
         if let Some(end_before) = parameters.end_before {
@@ -553,7 +899,7 @@ impl DbConnection {
             to_add.push_str("end < ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(end_before.to_string());
+            added_parameters.push(end_before.into());
         }
 
         if let Some(end_after) = parameters.end_after {
@@ -574,7 +920,51 @@ impl DbConnection {
             to_add.push_str("end > ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(end_after.to_string());
+            added_parameters.push(end_after.into());
+        }
+
+        if let Some(keyset) = &parameters.keyset {
+            debug!("Paginating with keyset: {:?}", keyset);
+
+            let condition = match keyset {
+                Keyset::Id { after: Some(after) } => {
+                    added_parameters.push(after.to_string().into());
+                    Some("id > ?")
+                }
+                Keyset::EpochDescending {
+                    before: Some((epoch, id)),
+                } => {
+                    added_parameters.push((*epoch).into());
+                    added_parameters.push((*epoch).into());
+                    added_parameters.push(id.to_string().into());
+                    Some("(epoch < ? OR (epoch = ? AND id < ?))")
+                }
+                _ => None,
+            };
+
+            if let Some(condition) = condition {
+                match first_parameter {
+                    true => {
+                        query.push_str(" WHERE ");
+                        first_parameter = false;
+                    }
+                    false => {
+                        query.push_str(" AND ");
+                    }
+                }
+
+                query.push_str(condition);
+            }
+
+            // Sorting by anything else would break the cursors, so the routes don't allow both
+            if parameters.sort.is_none() {
+                match keyset {
+                    Keyset::Id { .. } => query.push_str(" ORDER BY id ASC"),
+                    Keyset::EpochDescending { .. } => {
+                        query.push_str(" ORDER BY epoch DESC, id DESC")
+                    }
+                }
+            }
         }
 
         if let Some(sort_options) = parameters.sort {
@@ -620,7 +1010,7 @@ impl DbConnection {
             to_add.push_str(" LIMIT ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(limit.to_string());
+            added_parameters.push(limit.to_string().into());
         }
 
         if let Some(offset) = parameters.offset {
@@ -631,7 +1021,7 @@ impl DbConnection {
             to_add.push_str(" OFFSET ?");
             query.push_str(to_add.as_str());
 
-            added_parameters.push(offset.to_string());
+            added_parameters.push(offset.to_string().into());
         }
 
         query.push_str(";");
@@ -646,3 +1036,23 @@ impl DbConnection {
         (query, added_parameters)
     }
 }
+
+#[test]
+fn cursors_round_trip() {
+    let epoch = Utc.timestamp_millis_opt(1725000000123).unwrap();
+
+    assert_eq!(
+        Keyset::epoch_from_cursor(Some(&epoch_cursor(epoch, 42))).unwrap(),
+        Keyset::EpochDescending {
+            before: Some((epoch, 42))
+        }
+    );
+
+    assert_eq!(
+        Keyset::id_from_cursor(Some(&id_cursor(7))).unwrap(),
+        Keyset::Id { after: Some(7) }
+    );
+
+    assert!(Keyset::epoch_from_cursor(Some("42")).is_err());
+    assert!(Keyset::id_from_cursor(Some("not an id")).is_err());
+}
@@ -0,0 +1,117 @@
+use core::panic;
+
+use sqlx::mysql::MySqlQueryResult;
+
+use crate::types::entities::{player::Player, rating_snapshot::RatingSnapshot, season::Season};
+
+use super::DbConnection;
+
+impl DbConnection {
+    /// Fetches a player's rating snapshot for the start of a rating period
+    pub async fn get_rating_snapshot(
+        &mut self,
+        player_id: u64,
+        rating_period: u64,
+    ) -> Option<RatingSnapshot> {
+        let query_string =
+            "SELECT * FROM rating_snapshots WHERE player_id = ? AND rating_period = ?";
+
+        let query = sqlx::query_as(&query_string)
+            .bind(player_id)
+            .bind(rating_period);
+
+        let result: Result<RatingSnapshot, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(snapshot) => {
+                return Some(snapshot);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches every player's rating snapshot for the start of a rating period
+    pub async fn get_rating_snapshots_for_season(
+        &mut self,
+        rating_period: u64,
+    ) -> Vec<RatingSnapshot> {
+        let query_string = "SELECT * FROM rating_snapshots WHERE rating_period = ?";
+
+        let query = sqlx::query_as(&query_string).bind(rating_period);
+
+        let result: Result<Vec<RatingSnapshot>, sqlx::Error> =
+            query.fetch_all(&mut **self.inner).await;
+
+        match result {
+            Ok(snapshots) => {
+                return snapshots;
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return Vec::new(),
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Returns the player with the ratings they had at the start of the rating period.
+    ///
+    /// For the unprocessed (active) rating period, this is just their stored rating.
+    ///
+    /// Returns None if we have no history for the player in that rating period.
+    pub async fn get_player_at_season_start(
+        &mut self,
+        player: &Player,
+        season: &Season,
+    ) -> Option<Player> {
+        if !season.processed {
+            return Some(player.clone());
+        }
+
+        let snapshot = self.get_rating_snapshot(player.id, season.id).await?;
+
+        let mut player_then = player.clone();
+        player_then.rating = snapshot.rating;
+        player_then.deviation = snapshot.deviation;
+        player_then.volatility = snapshot.volatility;
+
+        Some(player_then)
+    }
+
+    /// Adds a rating snapshot, or replaces the existing one for the player and rating period.
+    pub async fn add_rating_snapshot(
+        &mut self,
+        snapshot: &RatingSnapshot,
+    ) -> Result<MySqlQueryResult, sqlx::Error> {
+        let query_string = "INSERT INTO rating_snapshots (player_id, rating_period, rating, deviation, volatility) VALUES (?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE rating = VALUES(rating), deviation = VALUES(deviation), volatility = VALUES(volatility)";
+
+        let query = sqlx::query(&query_string)
+            .bind(snapshot.player_id)
+            .bind(snapshot.rating_period)
+            .bind(snapshot.rating)
+            .bind(snapshot.deviation)
+            .bind(snapshot.volatility);
+
+        let result = query.execute(&mut **self.inner).await;
+
+        match result {
+            Ok(result) => {
+                return Ok(result);
+            }
+            Err(e) => match e {
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+}
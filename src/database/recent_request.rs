@@ -1,7 +1,7 @@
 use core::panic;
 use std::net::IpAddr;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlQueryResult;
 
 use crate::types::entities::recent_request::RecentRequest;
@@ -10,8 +10,10 @@ use super::DbConnection;
 
 impl DbConnection {
     /// Removes all non-recent requests (ones that are > 1 minute old)
-    pub async fn remove_non_recent_requests(&mut self) -> Result<MySqlQueryResult, sqlx::Error> {
-        let now = Utc::now();
+    pub async fn remove_non_recent_requests(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Result<MySqlQueryResult, sqlx::Error> {
         let one_minute_ago = now - chrono::TimeDelta::minutes(1);
 
         let query_string = "DELETE FROM recent_requests WHERE epoch < ?";
@@ -22,8 +24,11 @@ impl DbConnection {
     }
 
     /// Fetches all recent requests for an ip, which occured in the last minute.
-    pub async fn get_recent_requests_for_ip(&mut self, ip: IpAddr) -> Vec<RecentRequest> {
-        let now = Utc::now();
+    pub async fn get_recent_requests_for_ip(
+        &mut self,
+        ip: IpAddr,
+        now: DateTime<Utc>,
+    ) -> Vec<RecentRequest> {
         let one_minute_ago = now - chrono::TimeDelta::minutes(1);
 
         let query_string = "SELECT * FROM recent_requests WHERE epoch > ? AND ip = ?";
@@ -53,8 +58,8 @@ impl DbConnection {
     pub async fn get_recent_requests_for_api_key_hash(
         &mut self,
         api_key_hash: String,
+        now: DateTime<Utc>,
     ) -> Vec<RecentRequest> {
-        let now = Utc::now();
         let one_minute_ago = now - chrono::TimeDelta::minutes(1);
 
         let query_string = "SELECT * FROM recent_requests WHERE epoch > ? AND api_key_hash = ?";
@@ -1,6 +1,6 @@
 use core::panic;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::mysql::MySqlQueryResult;
 
 use crate::types::entities::season::Season;
@@ -38,9 +38,9 @@ impl DbConnection {
 
     /// Fetches a rating period by id
     pub async fn get_season_by_id(&mut self, id: u64) -> Option<Season> {
-        let query_string = "SELECT * FROM rating_periods WHERE id = ?";
+        let query_string = "SELECT * FROM rating_periods WHERE ladder = ? AND id = ?";
 
-        let query = sqlx::query_as(&query_string).bind(id);
+        let query = sqlx::query_as(&query_string).bind(self.ladder).bind(id);
 
         let result: Result<Season, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
 
@@ -58,14 +58,39 @@ impl DbConnection {
         }
     }
 
-    /// Fetches the latest active rating period.
-    pub async fn get_latest_active_season(&mut self) -> Option<Season> {
-        let now = Utc::now();
+    /// Fetches the latest rating period active at now.
+    pub async fn get_latest_active_season(&mut self, now: DateTime<Utc>) -> Option<Season> {
+        let query_string = "SELECT * FROM rating_periods WHERE ladder = ? AND (start < ? AND end > ?) ORDER BY id DESC LIMIT 1";
 
-        let query_string =
-            "SELECT * FROM rating_periods WHERE (start < ? AND end > ?) ORDER BY id DESC LIMIT 1";
+        let query = sqlx::query_as(&query_string)
+            .bind(self.ladder)
+            .bind(now)
+            .bind(now);
 
-        let query = sqlx::query_as(&query_string).bind(now).bind(now);
+        let result: Result<Season, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
+
+        match result {
+            Ok(season) => {
+                return Some(season);
+            }
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => return None,
+                _ => {
+                    log::error!("Database query failed {} -> {}", query_string, e);
+                    panic!("Database query failed");
+                }
+            },
+        }
+    }
+
+    /// Fetches the rating period which contains a point in time.
+    pub async fn get_season_at(&mut self, time: DateTime<Utc>) -> Option<Season> {
+        let query_string = "SELECT * FROM rating_periods WHERE ladder = ? AND (start <= ? AND end > ?) ORDER BY id DESC LIMIT 1";
+
+        let query = sqlx::query_as(&query_string)
+            .bind(self.ladder)
+            .bind(time)
+            .bind(time);
 
         let result: Result<Season, sqlx::Error> = query.fetch_one(&mut **self.inner).await;
 
@@ -121,9 +146,11 @@ impl DbConnection {
         &mut self,
         season: &Season,
     ) -> Result<MySqlQueryResult, sqlx::Error> {
-        let query_string = "INSERT INTO rating_periods (start, end, processed) VALUES (?, ?)";
+        let query_string =
+            "INSERT INTO rating_periods (ladder, start, end, processed) VALUES (?, ?, ?, ?)";
 
         let query = sqlx::query(&query_string)
+            .bind(season.ladder)
             .bind(season.start)
             .bind(season.end)
             .bind(season.processed);
@@ -1,51 +1,394 @@
-use chrono::Utc;
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
 use log::info;
+use sqlx::MySqlConnection;
+use tokio::task::JoinHandle;
 
 use crate::{
-    glicko,
-    types::entities::{player::Player, r#match::Match, season::Season},
+    clock::{Clock, SharedClock},
+    glicko::{player_at_start_of_period, rate_matches_instantly, rating_mode, RatingMode},
+    live_ratings::clear_live_ratings,
+    types::entities::{
+        ladder::Ladder, player::Player, r#match::Match, rating_snapshot::RatingSnapshot,
+        season::Season,
+    },
     MysqlDb,
 };
 
-/// Initializes the season handler, creates an active season
-/// if there isn't one, starts the season update task
-pub async fn initialize_season_handler(db: &MysqlDb) {
-    let now = Utc::now();
+/// Name of the MySQL lock held by the instance running the season handler
+pub const SEASON_HANDLER_LOCK_NAME: &str = "lunars_season_handler";
 
-    // Get the last one, it could also have passed while the server was offline
-    let query =
-        sqlx::query_as("SELECT * FROM rating_periods WHERE start < ? ORDER BY id DESC LIMIT 1")
-            .bind(now);
-
-    let last_season_result: Result<Season, sqlx::Error> = query.fetch_one(&**db).await;
-
-    let last_season = match last_season_result {
-        Ok(season) => season,
-        Err(e) => match e {
-            sqlx::Error::RowNotFound => create_new_season(db).await,
-            _ => {
-                log::error!("Seasons handler: Encountered database error: {}", e);
-                panic!("Seasons handler encountered database error");
-            }
-        },
-    };
+/// How often an instance which isn't running the season handler tries to take over, in seconds
+pub const SEASON_HANDLER_TAKEOVER_INTERVAL_SECONDS: i64 = 10;
+
+/// How often the instance running the season handler checks that it still holds the lock and
+/// looks for new ladders, in seconds
+pub const SEASON_HANDLER_CHECK_INTERVAL_SECONDS: i64 = 10;
+
+/// How long the season handler waits before retrying after a database error, in seconds; doubled
+/// after every further error
+pub const SEASON_HANDLER_RETRY_SECONDS: i64 = 10;
+
+/// The longest the season handler waits before retrying after a database error, in seconds
+pub const SEASON_HANDLER_MAX_RETRY_SECONDS: i64 = 600;
 
-    let db_clone = db.clone();
+/// Initializes the season handler.
+///
+/// When several instances share a database, only one of them runs it; whichever holds a MySQL
+/// advisory lock. The others keep trying to take the lock, so one takes over once the instance
+/// holding it dies and its connection is closed.
+pub async fn initialize_season_handler(db: &MysqlDb, clock: SharedClock) {
+    let db = db.clone();
 
     tokio::spawn(async move {
-        season_handler_main_task(db_clone, last_season).await;
+        season_handler_leader_task(db, clock).await;
     });
 }
 
-/// Main loop of the season handler;
+/// Main loop of the season handler's leader election;
+///
+/// Take the lock, run the season handler while holding it, retry if we don't or lose it
+async fn season_handler_leader_task<S: SeasonStore>(store: S, clock: SharedClock) {
+    loop {
+        if let Some(lock) = store.try_take_season_handler_lock().await {
+            info!("Seasons handler: This instance is now running the season handler");
+
+            run_season_handler(&store, lock, clock.clone()).await;
+
+            log::warn!("Seasons handler: Lost the season handler lock, stopped the season handler");
+        }
+
+        let interval = TimeDelta::seconds(SEASON_HANDLER_TAKEOVER_INTERVAL_SECONDS);
+
+        clock.sleep_until(clock.now() + interval).await;
+    }
+}
+
+/// Runs the season update task of every ladder while this instance holds the season handler
+/// lock, also starting them for ladders added later
+async fn run_season_handler<S: SeasonStore>(store: &S, mut lock: S::Lock, clock: SharedClock) {
+    let mut handlers: HashMap<u64, JoinHandle<()>> = HashMap::new();
+
+    let holder = S::season_handler_lock_holder(&lock);
+
+    loop {
+        match store.get_ladders().await {
+            Ok(ladders) => {
+                for ladder in ladders {
+                    if handlers.contains_key(&ladder.id) {
+                        continue;
+                    }
+
+                    let ladder_id = ladder.id;
+                    let handler = tokio::spawn(season_handler_main_task(
+                        store.clone(),
+                        ladder,
+                        holder,
+                        clock.clone(),
+                    ));
+
+                    handlers.insert(ladder_id, handler);
+                }
+            }
+            Err(e) => {
+                log::error!("Seasons handler: Failed to get ladders! {}", e);
+            }
+        }
+
+        let interval = TimeDelta::seconds(SEASON_HANDLER_CHECK_INTERVAL_SECONDS);
+
+        clock.sleep_until(clock.now() + interval).await;
+
+        if !store.holds_season_handler_lock(&mut lock).await {
+            break;
+        }
+    }
+
+    // Another instance may take over. Rating periods are only processed while holding the lock,
+    // so this is just to stop waiting for them
+    for handler in handlers.values() {
+        handler.abort();
+    }
+}
+
+/// Why a season could not be processed
+#[derive(Debug)]
+pub enum SeasonProcessingError {
+    /// The season handler lock is no longer held by this instance, so another instance runs the
+    /// season handler
+    LostSeasonHandlerLock,
+    /// A query failed; nothing was written, so it can be retried
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for SeasonProcessingError {
+    fn from(e: sqlx::Error) -> Self {
+        SeasonProcessingError::Database(e)
+    }
+}
+
+/// Where the season handler keeps rating periods and its lock; the database, or a fake in tests
+#[rocket::async_trait]
+pub trait SeasonStore: Clone + Send + Sync + 'static {
+    /// Keeps the season handler lock while held
+    type Lock: Send + 'static;
+
+    /// Takes the season handler lock if no other instance holds it
+    async fn try_take_season_handler_lock(&self) -> Option<Self::Lock>;
+
+    /// Returns whether the season handler lock is still held
+    async fn holds_season_handler_lock(&self, lock: &mut Self::Lock) -> bool;
+
+    /// Identifies the holder of the lock, so writes can check it still holds it
+    fn season_handler_lock_holder(lock: &Self::Lock) -> u64;
+
+    /// Fetches all ladders
+    async fn get_ladders(&self) -> Result<Vec<Ladder>, sqlx::Error>;
+
+    /// Fetches the ladder's latest season which started before the given time
+    async fn get_last_season(
+        &self,
+        ladder: &Ladder,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Season>, sqlx::Error>;
+
+    /// Concludes a season and writes updated player ratings, if the holder still holds the
+    /// season handler lock; see [process_season]
+    async fn process_season(
+        &self,
+        season: &mut Season,
+        holder: u64,
+    ) -> Result<(), SeasonProcessingError>;
+
+    /// Creates and returns a new season of a ladder, starting now, unless it still has an
+    /// active one; see [create_new_season]
+    async fn create_new_season(
+        &self,
+        ladder: &Ladder,
+        clock: &dyn Clock,
+    ) -> Result<Season, sqlx::Error>;
+}
+
+/// The season handler lock; a MySQL advisory lock, held by the connection it was taken with
+pub struct MySqlSeasonHandlerLock {
+    connection: MySqlConnection,
+    connection_id: u64,
+}
+
+#[rocket::async_trait]
+impl SeasonStore for MysqlDb {
+    type Lock = MySqlSeasonHandlerLock;
+
+    async fn try_take_season_handler_lock(&self) -> Option<Self::Lock> {
+        try_take_season_handler_lock(self).await
+    }
+
+    async fn holds_season_handler_lock(&self, lock: &mut Self::Lock) -> bool {
+        let result = holds_season_handler_lock(&mut lock.connection, lock.connection_id).await;
+
+        match result {
+            Ok(holds) => holds,
+            Err(e) => {
+                log::error!(
+                    "Seasons handler: Failed to check the season handler lock! {}",
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    fn season_handler_lock_holder(lock: &Self::Lock) -> u64 {
+        lock.connection_id
+    }
+
+    async fn get_ladders(&self) -> Result<Vec<Ladder>, sqlx::Error> {
+        sqlx::query_as("SELECT * FROM ladders ORDER BY id ASC")
+            .fetch_all(&**self)
+            .await
+    }
+
+    async fn get_last_season(
+        &self,
+        ladder: &Ladder,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Season>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT * FROM rating_periods WHERE ladder = ? AND start < ? ORDER BY id DESC LIMIT 1",
+        )
+        .bind(ladder.id)
+        .bind(now)
+        .fetch_optional(&**self)
+        .await
+    }
+
+    async fn process_season(
+        &self,
+        season: &mut Season,
+        holder: u64,
+    ) -> Result<(), SeasonProcessingError> {
+        process_season(self, season, holder).await
+    }
+
+    async fn create_new_season(
+        &self,
+        ladder: &Ladder,
+        clock: &dyn Clock,
+    ) -> Result<Season, sqlx::Error> {
+        create_new_season(self, ladder, clock).await
+    }
+}
+
+/// Takes the season handler lock if no other instance holds it.
+///
+/// The lock is released when its connection is closed.
+async fn try_take_season_handler_lock(db: &MysqlDb) -> Option<MySqlSeasonHandlerLock> {
+    // Kept out of the pool, so the lock goes with it
+    let mut connection = match db.acquire().await {
+        Ok(connection) => connection.detach(),
+        Err(e) => {
+            log::error!(
+                "Seasons handler: Failed to get a connection for the lock! {}",
+                e
+            );
+            return None;
+        }
+    };
+
+    let query =
+        sqlx::query_as("SELECT GET_LOCK(?, 0), CONNECTION_ID()").bind(SEASON_HANDLER_LOCK_NAME);
+
+    let result: Result<(Option<i64>, u64), sqlx::Error> = query.fetch_one(&mut connection).await;
+
+    match result {
+        Ok((Some(1), connection_id)) => Some(MySqlSeasonHandlerLock {
+            connection,
+            connection_id,
+        }),
+        Ok(_) => None,
+        Err(e) => {
+            log::error!(
+                "Seasons handler: Failed to take the season handler lock! {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Returns whether the season handler lock is held by the connection with the given id; from
+/// any connection
+async fn holds_season_handler_lock(
+    connection: &mut MySqlConnection,
+    holder: u64,
+) -> Result<bool, sqlx::Error> {
+    let query = sqlx::query_scalar("SELECT IS_USED_LOCK(?) = ?")
+        .bind(SEASON_HANDLER_LOCK_NAME)
+        .bind(holder);
+
+    let holds: Option<i64> = query.fetch_one(connection).await?;
+
+    Ok(holds == Some(1))
+}
+
+/// Locks a ladder's ratings and rating periods until the transaction ends.
+///
+/// Everything which writes ratings or rating periods takes it first, so on every instance they
+/// are written one at a time, and always from what the previous writer committed.
+async fn lock_ladder_ratings(
+    connection: &mut MySqlConnection,
+    ladder_id: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT id FROM ladders WHERE id = ? FOR UPDATE")
+        .bind(ladder_id)
+        .fetch_optional(connection)
+        .await?;
+
+    Ok(())
+}
+
+/// Waits before retrying after a database error, then doubles the wait for the next one
+async fn wait_to_retry(clock: &dyn Clock, retry_in: &mut TimeDelta) {
+    clock.sleep_until(clock.now() + *retry_in).await;
+
+    *retry_in = (*retry_in * 2).min(TimeDelta::seconds(SEASON_HANDLER_MAX_RETRY_SECONDS));
+}
+
+/// Fetches the ladder's last season, or creates one if it has none; retries until it succeeds
+async fn get_or_create_season(
+    store: &impl SeasonStore,
+    ladder: &Ladder,
+    clock: &dyn Clock,
+) -> Season {
+    let mut retry_in = TimeDelta::seconds(SEASON_HANDLER_RETRY_SECONDS);
+
+    loop {
+        let result = match store.get_last_season(ladder, clock.now()).await {
+            Ok(Some(season)) => Ok(season),
+            Ok(None) => store.create_new_season(ladder, clock).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(season) => return season,
+            Err(e) => {
+                log::error!(
+                    "Seasons handler: Failed to get the season of ladder {}, retrying in {}! {}",
+                    ladder.id,
+                    retry_in,
+                    e
+                );
+
+                wait_to_retry(clock, &mut retry_in).await;
+            }
+        }
+    }
+}
+
+/// Creates a new season of the ladder; retries until it succeeds
+async fn create_new_season_retrying(
+    store: &impl SeasonStore,
+    ladder: &Ladder,
+    clock: &dyn Clock,
+) -> Season {
+    let mut retry_in = TimeDelta::seconds(SEASON_HANDLER_RETRY_SECONDS);
+
+    loop {
+        match store.create_new_season(ladder, clock).await {
+            Ok(season) => return season,
+            Err(e) => {
+                log::error!(
+                    "Seasons handler: Failed to create a new season of ladder {}, retrying in {}! {}",
+                    ladder.id,
+                    retry_in,
+                    e
+                );
+
+                wait_to_retry(clock, &mut retry_in).await;
+            }
+        }
+    }
+}
+
+/// Main loop of the season handler of a ladder;
 ///
 /// Wait until the end of this season, update all player
-/// ranks, create new season
-pub async fn season_handler_main_task(db: MysqlDb, season: Season) {
-    let mut active_season = season;
+/// ranks, create new season.
+///
+/// A season is processed again until it succeeds, before creating the next one. Stops once this
+/// instance no longer holds the season handler lock.
+async fn season_handler_main_task(
+    store: impl SeasonStore,
+    ladder: Ladder,
+    holder: u64,
+    clock: SharedClock,
+) {
+    // Get the last one, it could also have passed while the server was offline
+    let mut active_season = get_or_create_season(&store, &ladder, &*clock).await;
 
     loop {
-        let now = Utc::now();
+        let now = clock.now();
 
         if now < active_season.end {
             let to_wait = active_season.end - now;
@@ -55,7 +398,7 @@ pub async fn season_handler_main_task(db: MysqlDb, season: Season) {
                 active_season.end, to_wait
             );
 
-            tokio::time::sleep(to_wait.to_std().unwrap()).await;
+            clock.sleep_until(active_season.end).await;
         }
 
         info!(
@@ -63,117 +406,1314 @@ pub async fn season_handler_main_task(db: MysqlDb, season: Season) {
             active_season.id
         );
 
-        if !active_season.processed {
-            process_season(&db, &mut active_season).await;
+        let mut retry_in = TimeDelta::seconds(SEASON_HANDLER_RETRY_SECONDS);
+
+        while !active_season.processed {
+            match store.process_season(&mut active_season, holder).await {
+                Ok(()) => {}
+                Err(SeasonProcessingError::LostSeasonHandlerLock) => {
+                    log::warn!(
+                        "Seasons handler: Lost the season handler lock before processing season {}",
+                        active_season.id
+                    );
+                    return;
+                }
+                Err(SeasonProcessingError::Database(e)) => {
+                    log::error!(
+                        "Seasons handler: Failed to process season {}, retrying in {}! {}",
+                        active_season.id,
+                        retry_in,
+                        e
+                    );
+
+                    wait_to_retry(&*clock, &mut retry_in).await;
+                }
+            }
         }
 
-        active_season = create_new_season(&db).await;
+        active_season = create_new_season_retrying(&store, &ladder, &*clock).await;
     }
 }
 
-/// Creates an returns a new season, starting now
-pub async fn create_new_season(db: &MysqlDb) -> Season {
-    let now = Utc::now();
-    let end = now + glicko::RATING_PERIOD_DURATION;
+/// Creates and returns a new season of a ladder, starting now.
+///
+/// If the ladder still has an active season (another instance or request created it first),
+/// that one is returned instead. The season and everyone's rating snapshots at its start are
+/// written in one transaction.
+pub async fn create_new_season(
+    db: &MysqlDb,
+    ladder: &Ladder,
+    clock: &dyn Clock,
+) -> Result<Season, sqlx::Error> {
+    let now = clock.now();
+    let end = now + ladder.rating_period_duration();
     let mut new_season = Season {
         start: now,
         end,
         id: 0,
         processed: false,
+        ladder: ladder.id,
     };
 
-    let query = sqlx::query("INSERT INTO rating_periods (start, end, processed) VALUES (?, ?, ?)")
-        .bind(new_season.start)
-        .bind(new_season.end)
-        .bind(new_season.processed);
+    // Dropping the transaction on an error rolls it back
+    let mut transaction = db.begin().await?;
+
+    lock_ladder_ratings(&mut transaction, ladder.id).await?;
+
+    let query = sqlx::query_as(
+        "SELECT * FROM rating_periods WHERE ladder = ? AND end > ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(ladder.id)
+    .bind(now);
+
+    let active_season: Option<Season> = query.fetch_optional(&mut *transaction).await?;
 
-    let result = query.execute(&**db).await;
+    if let Some(active_season) = active_season {
+        return Ok(active_season);
+    }
+
+    let query = sqlx::query(
+        "INSERT INTO rating_periods (ladder, start, end, processed) VALUES (?, ?, ?, ?)",
+    )
+    .bind(new_season.ladder)
+    .bind(new_season.start)
+    .bind(new_season.end)
+    .bind(new_season.processed);
+
+    new_season.id = query.execute(&mut *transaction).await?.last_insert_id();
+
+    // Remember everyone's rating at the start of the season
+    let query = sqlx::query("INSERT INTO rating_snapshots (player_id, rating_period, rating, deviation, volatility) SELECT id, ?, rating, deviation, volatility FROM players WHERE ladder = ?")
+        .bind(new_season.id)
+        .bind(new_season.ladder);
 
-    let season_id = result.unwrap().last_insert_id();
+    query.execute(&mut *transaction).await?;
 
-    info!("Season handler: Created new season (id {})!", season_id);
+    transaction.commit().await?;
 
-    new_season.id = season_id;
+    info!(
+        "Season handler: Created new season (id {}) of ladder {}!",
+        new_season.id, ladder.name
+    );
 
-    new_season
+    Ok(new_season)
 }
 
-/// Concludes a season and writes updated player rankings
-pub async fn process_season(db: &MysqlDb, season: &mut Season) {
+/// How many players' ratings are written per statement at the end of a season
+pub const RATING_UPDATE_BATCH_SIZE: usize = 500;
+
+/// Concludes a season and writes updated player rankings.
+///
+/// The ratings are written together with marking the season processed, in one transaction; if
+/// anything fails or it is cancelled, nothing is written. A season which was processed meanwhile
+/// is not processed again, and nothing is written unless the holder still holds the season
+/// handler lock.
+pub async fn process_season(
+    db: &MysqlDb,
+    season: &mut Season,
+    holder: u64,
+) -> Result<(), SeasonProcessingError> {
     let start = std::time::Instant::now();
 
+    // Dropping the transaction on an error rolls it back
+    let mut transaction = db.begin().await?;
+
+    lock_ladder_ratings(&mut transaction, season.ladder).await?;
+
+    if !holds_season_handler_lock(&mut transaction, holder).await? {
+        return Err(SeasonProcessingError::LostSeasonHandlerLock);
+    }
+
+    let query =
+        sqlx::query_scalar("SELECT processed FROM rating_periods WHERE id = ?").bind(season.id);
+
+    let processed: bool = query.fetch_one(&mut *transaction).await?;
+
+    if processed {
+        season.processed = true;
+        return Ok(());
+    }
+
     let query = sqlx::query_as("SELECT * FROM matches WHERE rating_period = ?").bind(season.id);
 
-    let result: Result<Vec<Match>, sqlx::Error> = query.fetch_all(&**db).await;
+    let season_matches: Vec<Match> = query.fetch_all(&mut *transaction).await?;
 
-    if let Err(e) = result.as_ref() {
-        log::error!("Seasons handler: Failed to get season matches! {}", e);
+    let query = sqlx::query_as("SELECT * FROM players WHERE ladder = ?").bind(season.ladder);
+
+    let players: Vec<Player> = query.fetch_all(&mut *transaction).await?;
+
+    let player_count = players.len();
+    let match_count = season_matches.len();
+
+    let math_started = std::time::Instant::now();
+
+    // The math is CPU bound, keep it off the async runtime
+    let season_clone = season.clone();
+    let mode = rating_mode();
+
+    let rated_players = tokio::task::spawn_blocking(move || {
+        rate_players_for_season(players, &season_matches, &season_clone, mode)
+    })
+    .await
+    .unwrap();
+
+    let math_elapsed = math_started.elapsed();
+
+    save_player_ratings(&mut transaction, &rated_players).await?;
+
+    let query = sqlx::query(
+        "UPDATE rating_periods SET processed = true WHERE id = ? AND processed = false",
+    )
+    .bind(season.id);
+
+    // Nothing else writes it while we hold the ladder lock, but never save the ratings twice
+    if query.execute(&mut *transaction).await?.rows_affected() == 0 {
+        season.processed = true;
+        return Ok(());
     }
 
-    let season_matches = result.unwrap();
+    transaction.commit().await?;
+
+    season.processed = true;
 
-    let query = sqlx::query_as("SELECT * FROM players");
+    let elapsed = start.elapsed();
 
-    let result: Result<Vec<Player>, sqlx::Error> = query.fetch_all(&**db).await;
+    log::info!("Seasons handler: computed and saved ratings for season {} - {} players and {} matches - took {:?}, {:?} of that was math", season.id, player_count, match_count, elapsed, math_elapsed);
 
-    if let Err(e) = result.as_ref() {
-        log::error!("Seasons handler: Failed to get players! {}", e);
+    Ok(())
+}
+
+/// Rates every player for the end of a season and returns them; suspended and banned players'
+/// ratings are frozen, so they are left out.
+///
+/// The season's matches are grouped by player first, then the players are rated in parallel
+/// on all available cores. This is CPU bound, so call it from a blocking task.
+pub fn rate_players_for_season(
+    players: Vec<Player>,
+    season_matches: &[Match],
+    season: &Season,
+    mode: RatingMode,
+) -> Vec<Player> {
+    let mut matches_by_player: HashMap<u64, Vec<Match>> = HashMap::new();
+
+    for a_match in season_matches {
+        matches_by_player
+            .entry(a_match.player_a)
+            .or_default()
+            .push(a_match.clone());
+
+        // A match against themselves (which can't be added) only counts once, like when rating
+        // players one by one
+        if a_match.player_b != a_match.player_a {
+            matches_by_player
+                .entry(a_match.player_b)
+                .or_default()
+                .push(a_match.clone());
+        }
     }
 
-    let mut players = result.unwrap();
+    let mut players = players
+        .into_iter()
+        .filter(|player| !player.is_frozen_for(season))
+        .collect::<Vec<Player>>();
 
-    // Go through each player, find their matches, compute their rating, update it
-    for player in &mut players {
-        let player_matches = season_matches
-            .iter()
-            .filter(|a_match| a_match.player_a == player.id || a_match.player_b == player.id)
-            .cloned()
-            .collect::<Vec<Match>>();
+    let threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+    let chunk_size = players.len().div_ceil(threads).max(1);
 
-        // Note: should we use a computed completion here or just 1.0?
-        player.rate_player_for_elapsed_periods(player_matches, 1.0);
+    std::thread::scope(|scope| {
+        for chunk in players.chunks_mut(chunk_size) {
+            let matches_by_player = &matches_by_player;
 
-        let query = sqlx::query(
-            "UPDATE players SET rating = ?, deviation = ?, volatility = ? WHERE id = ?",
-        )
-        .bind(player.rating)
-        .bind(player.deviation)
-        .bind(player.volatility)
-        .bind(player.id);
+            scope.spawn(move || {
+                for player in chunk {
+                    let player_matches = matches_by_player
+                        .get(&player.id)
+                        .cloned()
+                        .unwrap_or_default();
 
-        let result = query.execute(&**db).await;
+                    match mode {
+                        // Their matches are already rated, only the rest of the period is left
+                        RatingMode::Instant => {
+                            player.rate_player_instantly_until(&player_matches, season, season.end)
+                        }
+                        // Note: should we use a computed completion here or just 1.0?
+                        RatingMode::Periodic => {
+                            player.rate_player_for_elapsed_periods(player_matches, 1.0)
+                        }
+                    }
+                }
+            });
+        }
+    });
 
-        match result {
-            Ok(_) => {}
-            Err(e) => {
-                log::error!(
-                    "Seasons handler: Failed to update player {} for end of season! {}",
-                    player.id,
-                    e
-                );
-                continue;
-            }
+    players
+}
+
+/// Writes the players' ratings, [RATING_UPDATE_BATCH_SIZE] players per statement
+async fn save_player_ratings(
+    connection: &mut MySqlConnection,
+    players: &[Player],
+) -> Result<(), sqlx::Error> {
+    for batch in players.chunks(RATING_UPDATE_BATCH_SIZE) {
+        let cases = vec!["WHEN ? THEN ?"; batch.len()].join(" ");
+        let ids = vec!["?"; batch.len()].join(", ");
+
+        let query_string = format!("UPDATE players SET rating = CASE id {cases} END, deviation = CASE id {cases} END, volatility = CASE id {cases} END WHERE id IN ({ids})");
+
+        let mut query = sqlx::query(&query_string);
+
+        for player in batch {
+            query = query.bind(player.id).bind(player.rating);
+        }
+
+        for player in batch {
+            query = query.bind(player.id).bind(player.deviation);
+        }
+
+        for player in batch {
+            query = query.bind(player.id).bind(player.volatility);
+        }
+
+        for player in batch {
+            query = query.bind(player.id);
         }
+
+        query.execute(&mut *connection).await?;
     }
 
-    season.processed = true;
+    Ok(())
+}
+
+/// Recomputes the ratings of an already processed season and all seasons after it.
+///
+/// Used when a match is added to a season after it was processed.
+///
+/// Each season is rated again from the rating snapshots at its start; the results become the
+/// snapshots of the next season, whose matches are then updated to the new ratings. Finally,
+/// the players' stored ratings are set to the snapshots of the latest unprocessed season.
+///
+/// Players without a snapshot for a season (because they did not exist yet or because the
+/// history was not recorded) are left out of it.
+///
+/// With [RatingMode::Instant], each season's matches are instead replayed in order, updating
+/// every match to the ratings from right before it; the active season can be reprocessed as well.
+/// Players without a snapshot who played in a season start from their rating before their first
+/// match in it.
+///
+/// Everything is written in one transaction, holding the ladder's ratings lock.
+pub async fn reprocess_seasons_from(db: &MysqlDb, first_season_id: u64) {
+    let start = std::time::Instant::now();
+
+    let result = db.begin().await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!("Seasons handler: Failed to start a transaction! {}", e);
+    }
+
+    let mut transaction = result.unwrap();
 
     let query =
-        sqlx::query("UPDATE rating_periods SET processed = true WHERE id = ?").bind(season.id);
+        sqlx::query_scalar("SELECT ladder FROM rating_periods WHERE id = ?").bind(first_season_id);
 
-    let result = query.execute(&**db).await;
+    let result: Result<Option<u64>, sqlx::Error> = query.fetch_optional(&mut *transaction).await;
 
-    match result {
-        Ok(_) => {}
-        Err(e) => {
-            log::error!(
-                "Seasons handler: Failed to update previous rating period as processed! {}",
-                e
-            );
-        }
+    if let Err(e) = result.as_ref() {
+        log::error!("Seasons handler: Failed to get seasons to reprocess! {}", e);
     }
 
-    let elapsed = start.elapsed();
+    let Some(ladder_id) = result.unwrap() else {
+        return;
+    };
+
+    let result = lock_ladder_ratings(&mut transaction, ladder_id).await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!(
+            "Seasons handler: Failed to lock ladder {}! {}",
+            ladder_id,
+            e
+        );
+    }
+
+    result.unwrap();
+
+    // Only the seasons of the same ladder
+    let query =
+        sqlx::query_as("SELECT * FROM rating_periods WHERE id >= ? AND ladder = ? ORDER BY id ASC")
+            .bind(first_season_id)
+            .bind(ladder_id);
+
+    let result: Result<Vec<Season>, sqlx::Error> = query.fetch_all(&mut *transaction).await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!("Seasons handler: Failed to get seasons to reprocess! {}", e);
+    }
+
+    let seasons = result.unwrap();
+
+    let Some(first_season) = seasons.first() else {
+        return;
+    };
+
+    let query = sqlx::query_as("SELECT * FROM players WHERE ladder = ?").bind(first_season.ladder);
+
+    let result: Result<Vec<Player>, sqlx::Error> = query.fetch_all(&mut *transaction).await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!("Seasons handler: Failed to get players! {}", e);
+    }
+
+    let all_players = result.unwrap();
+
+    let mut reprocessed = 0;
+
+    for (index, season) in seasons.iter().enumerate() {
+        let season_snapshots = get_season_snapshots(&mut transaction, season.id).await;
+
+        let mut season_matches = get_season_matches(&mut transaction, season.id).await;
 
-    log::info!("Seasons handler: computed and saved ratings for season {} - {} players and {} matches - took {:?}", season.id, players.len(), season_matches.len(), elapsed);
+        if rating_mode() == RatingMode::Instant {
+            let next_season = seasons.get(index + 1);
+
+            reprocess_season_instantly(
+                &mut transaction,
+                season,
+                next_season,
+                &all_players,
+                &season_snapshots,
+                season_matches,
+            )
+            .await;
+
+            reprocessed += 1;
+
+            if !season.processed {
+                break;
+            }
+
+            continue;
+        }
+
+        sync_match_ratings(&mut transaction, &mut season_matches, &season_snapshots).await;
+
+        if !season.processed {
+            // This is the active season; its start is everyone's current rating
+            for snapshot in &season_snapshots {
+                update_player_rating(&mut transaction, snapshot.player_id, snapshot).await;
+            }
+
+            break;
+        }
+
+        let next_season = seasons.get(index + 1);
+
+        for snapshot in &season_snapshots {
+            let Some(player) = all_players.iter().find(|x| x.id == snapshot.player_id) else {
+                continue;
+            };
+
+            let mut player = player.clone();
+            player.rating = snapshot.rating;
+            player.deviation = snapshot.deviation;
+            player.volatility = snapshot.volatility;
+
+            let player_matches = season_matches
+                .iter()
+                .filter(|a_match| a_match.player_a == player.id || a_match.player_b == player.id)
+                .cloned()
+                .collect::<Vec<Match>>();
+
+            // Suspended and banned players' ratings are frozen
+            if !player.is_frozen_for(season) {
+                player.rate_player_for_elapsed_periods(player_matches, 1.0);
+            }
+
+            let rated = RatingSnapshot {
+                player_id: player.id,
+                rating_period: 0,
+                rating: player.rating,
+                deviation: player.deviation,
+                volatility: player.volatility,
+            };
+
+            match next_season {
+                Some(next_season) => {
+                    save_snapshot(
+                        &mut transaction,
+                        &RatingSnapshot {
+                            rating_period: next_season.id,
+                            ..rated
+                        },
+                    )
+                    .await
+                }
+                // No season after this one yet, the result is the current rating
+                None => update_player_rating(&mut transaction, player.id, &rated).await,
+            }
+        }
+
+        reprocessed += 1;
+    }
+
+    let result = transaction.commit().await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!(
+            "Seasons handler: Failed to save reprocessed seasons starting from season {}! {}",
+            first_season_id,
+            e
+        );
+    }
+
+    result.unwrap();
+
+    // Opponents' ratings in matches may have changed too
+    clear_live_ratings().await;
+
+    let elapsed = start.elapsed();
+
+    log::info!(
+        "Seasons handler: reprocessed {} seasons starting from season {} - took {:?}",
+        reprocessed,
+        first_season_id,
+        elapsed
+    );
+}
+
+/// Rates a season again for [RatingMode::Instant], replaying its matches from everyone's
+/// rating at its start and updating the matches' ratings.
+///
+/// For a processed season, the results become the snapshots of the next season, or the
+/// current ratings if there is none yet. For the active season, they are the current ratings.
+async fn reprocess_season_instantly(
+    connection: &mut MySqlConnection,
+    season: &Season,
+    next_season: Option<&Season>,
+    all_players: &[Player],
+    season_snapshots: &[RatingSnapshot],
+    season_matches: Vec<Match>,
+) {
+    let mut players = all_players
+        .iter()
+        .filter_map(|player| player_at_start_of_period(player, season_snapshots, &season_matches))
+        .collect::<Vec<Player>>();
+
+    let mut rated_matches = season_matches.clone();
+
+    let until = match season.processed {
+        true => Some(season.end),
+        false => None,
+    };
+
+    rate_matches_instantly(&mut players, &mut rated_matches, season, until);
+
+    for a_match in &rated_matches {
+        let changed = season_matches
+            .iter()
+            .find(|x| x.id == a_match.id)
+            .map_or(true, |x| x != a_match);
+
+        if changed {
+            update_match_ratings(connection, a_match).await;
+        }
+    }
+
+    for player in &players {
+        let rated = RatingSnapshot {
+            player_id: player.id,
+            rating_period: 0,
+            rating: player.rating,
+            deviation: player.deviation,
+            volatility: player.volatility,
+        };
+
+        match next_season.filter(|_| season.processed) {
+            Some(next_season) => {
+                save_snapshot(
+                    connection,
+                    &RatingSnapshot {
+                        rating_period: next_season.id,
+                        ..rated
+                    },
+                )
+                .await
+            }
+            None => update_player_rating(connection, player.id, &rated).await,
+        }
+    }
+}
+
+/// Commits both players' new ratings right after their match was added to the active rating
+/// period, for [RatingMode::Instant], holding the ladder's ratings lock; returns the players
+/// with their new ratings.
+///
+/// The match is rated from the players' ratings when the lock was taken, which are also saved in
+/// it. If either player already has a later match in the rating period, nothing is written and
+/// [None] is returned; it has to be rated again from its start with [reprocess_seasons_from].
+pub async fn rate_match_instantly(
+    db: &MysqlDb,
+    a_match: &mut Match,
+    season: &Season,
+) -> Option<[Player; 2]> {
+    let result = db.begin().await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!("Seasons handler: Failed to start a transaction! {}", e);
+    }
+
+    let mut transaction = result.unwrap();
+
+    let result = lock_ladder_ratings(&mut transaction, season.ladder).await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!(
+            "Seasons handler: Failed to lock ladder {}! {}",
+            season.ladder,
+            e
+        );
+    }
+
+    result.unwrap();
+
+    let season_matches = get_season_matches(&mut transaction, season.id).await;
+
+    let played_later = season_matches.iter().any(|x| {
+        x.id != a_match.id
+            && x.epoch > a_match.epoch
+            && [a_match.player_a, a_match.player_b]
+                .iter()
+                .any(|player_id| x.player_a == *player_id || x.player_b == *player_id)
+    });
+
+    if played_later {
+        return None;
+    }
+
+    // Another match of theirs may have been rated since they were fetched
+    let query = sqlx::query_as("SELECT * FROM players WHERE id = ? OR id = ?")
+        .bind(a_match.player_a)
+        .bind(a_match.player_b);
+
+    let result: Result<Vec<Player>, sqlx::Error> = query.fetch_all(&mut *transaction).await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!("Seasons handler: Failed to get players! {}", e);
+    }
+
+    let players = result.unwrap();
+
+    let find_player = |player_id| players.iter().find(|x| x.id == player_id).unwrap().clone();
+
+    let mut player_a = find_player(a_match.player_a);
+    let mut player_b = find_player(a_match.player_b);
+
+    if (a_match.rating_a, a_match.deviation_a, a_match.volatility_a)
+        != (player_a.rating, player_a.deviation, player_a.volatility)
+        || (a_match.rating_b, a_match.deviation_b, a_match.volatility_b)
+            != (player_b.rating, player_b.deviation, player_b.volatility)
+    {
+        a_match.rating_a = player_a.rating;
+        a_match.deviation_a = player_a.deviation;
+        a_match.volatility_a = player_a.volatility;
+        a_match.rating_b = player_b.rating;
+        a_match.deviation_b = player_b.deviation;
+        a_match.volatility_b = player_b.volatility;
+
+        update_match_ratings(&mut transaction, a_match).await;
+    }
+
+    for player in [&mut player_a, &mut player_b] {
+        player.rate_player_instantly(a_match, &season_matches, season);
+
+        let rated = RatingSnapshot {
+            player_id: player.id,
+            rating_period: season.id,
+            rating: player.rating,
+            deviation: player.deviation,
+            volatility: player.volatility,
+        };
+
+        update_player_rating(&mut transaction, player.id, &rated).await;
+    }
+
+    let result = transaction.commit().await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!(
+            "Seasons handler: Failed to save ratings after match {}! {}",
+            a_match.id,
+            e
+        );
+    }
+
+    result.unwrap();
+
+    Some([player_a, player_b])
+}
+
+/// Fetches all rating snapshots for the start of a season
+async fn get_season_snapshots(
+    connection: &mut MySqlConnection,
+    season_id: u64,
+) -> Vec<RatingSnapshot> {
+    let query =
+        sqlx::query_as("SELECT * FROM rating_snapshots WHERE rating_period = ?").bind(season_id);
+
+    let result: Result<Vec<RatingSnapshot>, sqlx::Error> = query.fetch_all(connection).await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!(
+            "Seasons handler: Failed to get rating snapshots for season {}! {}",
+            season_id,
+            e
+        );
+    }
+
+    result.unwrap()
+}
+
+/// Fetches all matches of a season
+async fn get_season_matches(connection: &mut MySqlConnection, season_id: u64) -> Vec<Match> {
+    let query = sqlx::query_as("SELECT * FROM matches WHERE rating_period = ?").bind(season_id);
+
+    let result: Result<Vec<Match>, sqlx::Error> = query.fetch_all(connection).await;
+
+    if let Err(e) = result.as_ref() {
+        log::error!("Seasons handler: Failed to get season matches! {}", e);
+    }
+
+    result.unwrap()
+}
+
+/// Updates the players' ratings saved in matches to the rating snapshots, if they differ
+async fn sync_match_ratings(
+    connection: &mut MySqlConnection,
+    matches: &mut Vec<Match>,
+    snapshots: &[RatingSnapshot],
+) {
+    for a_match in matches.iter_mut() {
+        let snapshot_a = snapshots.iter().find(|x| x.player_id == a_match.player_a);
+        let snapshot_b = snapshots.iter().find(|x| x.player_id == a_match.player_b);
+
+        let mut changed = false;
+
+        if let Some(snapshot_a) = snapshot_a {
+            changed |= a_match.rating_a != snapshot_a.rating
+                || a_match.deviation_a != snapshot_a.deviation
+                || a_match.volatility_a != snapshot_a.volatility;
+
+            a_match.rating_a = snapshot_a.rating;
+            a_match.deviation_a = snapshot_a.deviation;
+            a_match.volatility_a = snapshot_a.volatility;
+        }
+
+        if let Some(snapshot_b) = snapshot_b {
+            changed |= a_match.rating_b != snapshot_b.rating
+                || a_match.deviation_b != snapshot_b.deviation
+                || a_match.volatility_b != snapshot_b.volatility;
+
+            a_match.rating_b = snapshot_b.rating;
+            a_match.deviation_b = snapshot_b.deviation;
+            a_match.volatility_b = snapshot_b.volatility;
+        }
+
+        if !changed {
+            continue;
+        }
+
+        update_match_ratings(connection, a_match).await;
+    }
+}
+
+/// Saves the players' ratings of a match
+async fn update_match_ratings(connection: &mut MySqlConnection, a_match: &Match) {
+    let query = sqlx::query("UPDATE matches SET rating_a = ?, rating_b = ?, deviation_a = ?, deviation_b = ?, volatility_a = ?, volatility_b = ? WHERE id = ?")
+        .bind(a_match.rating_a)
+        .bind(a_match.rating_b)
+        .bind(a_match.deviation_a)
+        .bind(a_match.deviation_b)
+        .bind(a_match.volatility_a)
+        .bind(a_match.volatility_b)
+        .bind(a_match.id);
+
+    if let Err(e) = query.execute(connection).await {
+        log::error!(
+            "Seasons handler: Failed to update ratings of match {}! {}",
+            a_match.id,
+            e
+        );
+    }
+}
+
+/// Saves a rating snapshot, replacing an existing one
+async fn save_snapshot(connection: &mut MySqlConnection, snapshot: &RatingSnapshot) {
+    let query = sqlx::query("INSERT INTO rating_snapshots (player_id, rating_period, rating, deviation, volatility) VALUES (?, ?, ?, ?, ?) ON DUPLICATE KEY UPDATE rating = VALUES(rating), deviation = VALUES(deviation), volatility = VALUES(volatility)")
+        .bind(snapshot.player_id)
+        .bind(snapshot.rating_period)
+        .bind(snapshot.rating)
+        .bind(snapshot.deviation)
+        .bind(snapshot.volatility);
+
+    if let Err(e) = query.execute(connection).await {
+        log::error!(
+            "Seasons handler: Failed to save rating snapshot of player {} for season {}! {}",
+            snapshot.player_id,
+            snapshot.rating_period,
+            e
+        );
+    }
+}
+
+/// Sets a player's stored rating
+async fn update_player_rating(
+    connection: &mut MySqlConnection,
+    player_id: u64,
+    rating: &RatingSnapshot,
+) {
+    let query =
+        sqlx::query("UPDATE players SET rating = ?, deviation = ?, volatility = ? WHERE id = ?")
+            .bind(rating.rating)
+            .bind(rating.deviation)
+            .bind(rating.volatility)
+            .bind(player_id);
+
+    if let Err(e) = query.execute(connection).await {
+        log::error!(
+            "Seasons handler: Failed to update rating of player {}! {}",
+            player_id,
+            e
+        );
+    }
+}
+
+/// Generates players and matches between them for tests and benchmarks, without randomness.
+///
+/// Nobody plays against themselves, so there need to be at least two players.
+#[cfg(test)]
+fn generate_season(player_count: u64, match_count: u64) -> (Vec<Player>, Vec<Match>, Season) {
+    let start = chrono::Utc::now();
+    let season = Season::new(start, start + chrono::TimeDelta::weeks(3));
+
+    let players = (1..=player_count)
+        .map(|id| {
+            Player::for_tests(
+                id,
+                1000.0 + (id * 37 % 1000) as f64,
+                50.0 + (id * 13 % 300) as f64,
+            )
+        })
+        .collect::<Vec<Player>>();
+
+    let matches = (0..match_count)
+        .map(|id| {
+            let player_a = &players[(id * 7919 % player_count) as usize];
+            // Never the same player; at least 1 and at most player_count - 1 further
+            let offset = 1 + id % 97 % (player_count - 1);
+            let player_b = &players[((id * 7919 + offset) % player_count) as usize];
+
+            Match {
+                id,
+                rating_period: season.id,
+                player_a: player_a.id,
+                player_b: player_b.id,
+                rating_a: player_a.rating,
+                rating_b: player_b.rating,
+                deviation_a: player_a.deviation,
+                deviation_b: player_b.deviation,
+                volatility_a: player_a.volatility,
+                volatility_b: player_b.volatility,
+                ping_a: (id % 150) as u16,
+                ping_b: (id * 3 % 150) as u16,
+                score_a: (id % 11) as u8,
+                score_b: (id * 5 % 11) as u8 + 1,
+                epoch: start + chrono::TimeDelta::minutes(id as i64),
+            }
+        })
+        .collect::<Vec<Match>>();
+
+    (players, matches, season)
+}
+
+#[test]
+fn parallel_season_rating_matches_serial() {
+    let (players, matches, season) = generate_season(50, 500);
+
+    assert!(matches.iter().all(|x| x.player_a != x.player_b));
+
+    let rated = rate_players_for_season(players.clone(), &matches, &season, RatingMode::Periodic);
+
+    assert_eq!(rated.len(), players.len());
+
+    for (mut player, rated) in players.into_iter().zip(rated) {
+        let player_matches = matches
+            .iter()
+            .filter(|a_match| a_match.player_a == player.id || a_match.player_b == player.id)
+            .cloned()
+            .collect::<Vec<Match>>();
+
+        player.rate_player_for_elapsed_periods(player_matches, 1.0);
+
+        assert_eq!(player, rated);
+    }
+}
+
+/// Run with `cargo test --release bench_season_processing -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn bench_season_processing() {
+    for (player_count, match_count) in [(1_000, 10_000), (10_000, 100_000)] {
+        let (players, matches, season) = generate_season(player_count, match_count);
+
+        let started = std::time::Instant::now();
+
+        let rated = rate_players_for_season(players, &matches, &season, RatingMode::Periodic);
+
+        println!(
+            "Rated {} players with {} matches in {:?}",
+            rated.len(),
+            match_count,
+            started.elapsed()
+        );
+    }
+}
+
+/// Keeps seasons and the season handler lock in memory, shared between several fake instances
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct FakeSeasonStore(std::sync::Arc<std::sync::Mutex<FakeSeasonStoreState>>);
+
+#[cfg(test)]
+#[derive(Default)]
+struct FakeSeasonStoreState {
+    /// Who holds the season handler lock
+    lock_holder: Option<u64>,
+    holders: u64,
+    ladders: Vec<Ladder>,
+    seasons: Vec<Season>,
+    /// Ids of processed seasons, in the order they were processed
+    processed: Vec<u64>,
+    /// How many of the next season queries fail
+    failing_queries: u32,
+}
+
+#[cfg(test)]
+impl FakeSeasonStoreState {
+    /// Fails the query if queries are set to fail
+    fn query(&mut self) -> Result<(), sqlx::Error> {
+        if self.failing_queries == 0 {
+            return Ok(());
+        }
+
+        self.failing_queries -= 1;
+
+        Err(sqlx::Error::PoolTimedOut)
+    }
+}
+
+#[cfg(test)]
+impl FakeSeasonStore {
+    fn state(&self) -> std::sync::MutexGuard<'_, FakeSeasonStoreState> {
+        self.0.lock().unwrap()
+    }
+
+    /// Adds a ladder, optionally with an active season starting at the given time
+    fn add_ladder(&self, id: u64, season_start: Option<chrono::DateTime<Utc>>) -> Ladder {
+        let ladder = Ladder {
+            id,
+            name: format!("ladder {}", id),
+            default_rating: crate::glicko::default_rating(),
+            default_deviation: crate::glicko::default_deviation(),
+            default_volatility: crate::glicko::default_volatility(),
+            rating_period_duration_days: 7,
+        };
+
+        let mut state = self.state();
+
+        state.ladders.push(ladder.clone());
+
+        if let Some(start) = season_start {
+            let season = Season {
+                id: state.seasons.len() as u64 + 1,
+                ladder: id,
+                ..Season::new(start, start + ladder.rating_period_duration())
+            };
+
+            state.seasons.push(season);
+        }
+
+        ladder
+    }
+
+    fn seasons_of(&self, ladder_id: u64) -> Vec<Season> {
+        self.state()
+            .seasons
+            .iter()
+            .filter(|x| x.ladder == ladder_id)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[rocket::async_trait]
+impl SeasonStore for FakeSeasonStore {
+    type Lock = u64;
+
+    async fn try_take_season_handler_lock(&self) -> Option<u64> {
+        let mut state = self.state();
+
+        if state.lock_holder.is_some() {
+            return None;
+        }
+
+        state.holders += 1;
+        state.lock_holder = Some(state.holders);
+
+        state.lock_holder
+    }
+
+    async fn holds_season_handler_lock(&self, lock: &mut u64) -> bool {
+        self.state().lock_holder == Some(*lock)
+    }
+
+    fn season_handler_lock_holder(lock: &u64) -> u64 {
+        *lock
+    }
+
+    async fn get_ladders(&self) -> Result<Vec<Ladder>, sqlx::Error> {
+        Ok(self.state().ladders.clone())
+    }
+
+    async fn get_last_season(
+        &self,
+        ladder: &Ladder,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Season>, sqlx::Error> {
+        self.state().query()?;
+
+        Ok(self
+            .seasons_of(ladder.id)
+            .into_iter()
+            .filter(|x| x.start < now)
+            .last())
+    }
+
+    async fn process_season(
+        &self,
+        season: &mut Season,
+        holder: u64,
+    ) -> Result<(), SeasonProcessingError> {
+        let mut state = self.state();
+
+        state.query()?;
+
+        if state.lock_holder != Some(holder) {
+            return Err(SeasonProcessingError::LostSeasonHandlerLock);
+        }
+
+        let stored = state
+            .seasons
+            .iter_mut()
+            .find(|x| x.id == season.id)
+            .unwrap();
+
+        if !stored.processed {
+            stored.processed = true;
+            state.processed.push(season.id);
+        }
+
+        season.processed = true;
+
+        Ok(())
+    }
+
+    async fn create_new_season(
+        &self,
+        ladder: &Ladder,
+        clock: &dyn Clock,
+    ) -> Result<Season, sqlx::Error> {
+        let now = clock.now();
+
+        let mut state = self.state();
+
+        state.query()?;
+
+        if let Some(active_season) = state
+            .seasons
+            .iter()
+            .find(|x| x.ladder == ladder.id && x.end > now)
+        {
+            return Ok(active_season.clone());
+        }
+
+        let season = Season {
+            id: state.seasons.len() as u64 + 1,
+            ladder: ladder.id,
+            ..Season::new(now, now + ladder.rating_period_duration())
+        };
+
+        state.seasons.push(season.clone());
+
+        Ok(season)
+    }
+}
+
+/// Lets every spawned task run until it waits again, on a current thread runtime
+#[cfg(test)]
+async fn run_pending_tasks() {
+    for _ in 0..100 {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[test]
+fn processes_season_once_clock_passes_its_end() {
+    use std::sync::Arc;
+
+    use crate::clock::FakeClock;
+
+    let start = chrono::Utc::now();
+    let clock = Arc::new(FakeClock::new(start));
+    let store = FakeSeasonStore::default();
+
+    let ladder = store.add_ladder(1, Some(start));
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let holder = store.try_take_season_handler_lock().await.unwrap();
+
+        let task = tokio::spawn(season_handler_main_task(
+            store.clone(),
+            ladder,
+            holder,
+            clock.clone(),
+        ));
+
+        run_pending_tasks().await;
+
+        clock.advance(TimeDelta::days(6));
+        run_pending_tasks().await;
+        assert!(store.state().processed.is_empty());
+
+        clock.advance(TimeDelta::days(2));
+        run_pending_tasks().await;
+        assert_eq!(store.state().processed, vec![1]);
+
+        // The next season starts when the last one was processed, and waits for its own end
+        let seasons = store.seasons_of(1);
+        assert_eq!(seasons.len(), 2);
+        assert_eq!(seasons[1].start, start + TimeDelta::days(8));
+
+        task.abort();
+    });
+}
+
+#[test]
+fn retries_season_after_database_errors() {
+    use std::sync::Arc;
+
+    use crate::clock::FakeClock;
+
+    let start = chrono::Utc::now();
+    let clock = Arc::new(FakeClock::new(start));
+    let store = FakeSeasonStore::default();
+
+    let ladder = store.add_ladder(1, Some(start));
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let holder = store.try_take_season_handler_lock().await.unwrap();
+
+        let task = tokio::spawn(season_handler_main_task(
+            store.clone(),
+            ladder,
+            holder,
+            clock.clone(),
+        ));
+        run_pending_tasks().await;
+
+        store.state().failing_queries = 2;
+
+        clock.advance(TimeDelta::days(7));
+        run_pending_tasks().await;
+
+        // The season isn't skipped
+        assert!(store.state().processed.is_empty());
+        assert_eq!(store.seasons_of(1).len(), 1);
+
+        clock.advance(TimeDelta::seconds(SEASON_HANDLER_RETRY_SECONDS));
+        run_pending_tasks().await;
+        assert!(store.state().processed.is_empty());
+
+        // Waits twice as long after the second error
+        clock.advance(TimeDelta::seconds(SEASON_HANDLER_RETRY_SECONDS));
+        run_pending_tasks().await;
+        assert!(store.state().processed.is_empty());
+
+        clock.advance(TimeDelta::seconds(SEASON_HANDLER_RETRY_SECONDS));
+        run_pending_tasks().await;
+        assert_eq!(store.state().processed, vec![1]);
+        assert_eq!(store.seasons_of(1).len(), 2);
+
+        task.abort();
+
+        // A ladder without a season gets one once the database is back
+        let ladder = store.add_ladder(2, None);
+
+        store.state().failing_queries = 1;
+
+        let task = tokio::spawn(season_handler_main_task(
+            store.clone(),
+            ladder,
+            holder,
+            clock.clone(),
+        ));
+        run_pending_tasks().await;
+        assert!(store.seasons_of(2).is_empty());
+
+        clock.advance(TimeDelta::seconds(SEASON_HANDLER_RETRY_SECONDS));
+        run_pending_tasks().await;
+        assert_eq!(store.seasons_of(2).len(), 1);
+
+        task.abort();
+    });
+}
+
+#[test]
+fn another_instance_takes_over_the_season_handler() {
+    use std::sync::Arc;
+
+    use crate::clock::FakeClock;
+
+    let start = chrono::Utc::now();
+    let clock = Arc::new(FakeClock::new(start));
+    let store = FakeSeasonStore::default();
+
+    store.add_ladder(1, Some(start));
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let first = tokio::spawn(season_handler_leader_task(store.clone(), clock.clone()));
+        run_pending_tasks().await;
+
+        let second = tokio::spawn(season_handler_leader_task(store.clone(), clock.clone()));
+        run_pending_tasks().await;
+
+        assert_eq!(store.state().lock_holder, Some(1));
+
+        // The first instance's connection drops, but it keeps running until it notices
+        store.state().lock_holder = None;
+
+        clock.advance(TimeDelta::seconds(SEASON_HANDLER_TAKEOVER_INTERVAL_SECONDS));
+        run_pending_tasks().await;
+
+        assert_eq!(store.state().lock_holder, Some(2));
+
+        // Only the new holder processes the season, and only once
+        clock.advance(TimeDelta::days(7));
+        run_pending_tasks().await;
+
+        assert_eq!(store.state().processed, vec![1]);
+        assert_eq!(store.seasons_of(1).len(), 2);
+
+        first.abort();
+        second.abort();
+    });
+}
+
+#[test]
+fn does_not_process_season_after_losing_the_lock() {
+    use std::sync::Arc;
+
+    use crate::clock::FakeClock;
+
+    let start = chrono::Utc::now();
+    let clock = Arc::new(FakeClock::new(start));
+    let store = FakeSeasonStore::default();
+
+    let ladder = store.add_ladder(1, Some(start));
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let holder = store.try_take_season_handler_lock().await.unwrap();
+
+        let task = tokio::spawn(season_handler_main_task(
+            store.clone(),
+            ladder,
+            holder,
+            clock.clone(),
+        ));
+        run_pending_tasks().await;
+
+        // Another instance took over before the season ended
+        store.state().lock_holder = Some(holder + 1);
+
+        clock.advance(TimeDelta::days(7));
+        run_pending_tasks().await;
+
+        assert!(task.is_finished());
+        assert!(store.state().processed.is_empty());
+        assert_eq!(store.seasons_of(1).len(), 1);
+    });
+}
+
+#[test]
+fn starts_season_handler_of_added_ladder() {
+    use std::sync::Arc;
+
+    use crate::clock::FakeClock;
+
+    let start = chrono::Utc::now();
+    let clock = Arc::new(FakeClock::new(start));
+    let store = FakeSeasonStore::default();
+
+    store.add_ladder(1, Some(start));
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let leader = tokio::spawn(season_handler_leader_task(store.clone(), clock.clone()));
+        run_pending_tasks().await;
+
+        // Adding a ladder creates its first rating period right away
+        let ladder = store.add_ladder(2, None);
+        let season = store.create_new_season(&ladder, &*clock).await.unwrap();
+
+        // Or fails to, then the season handler creates it
+        store.add_ladder(3, None);
+
+        clock.advance(TimeDelta::seconds(SEASON_HANDLER_CHECK_INTERVAL_SECONDS));
+        run_pending_tasks().await;
+
+        assert_eq!(store.seasons_of(2), vec![season.clone()]);
+        assert_eq!(store.seasons_of(3).len(), 1);
+
+        // Its season handler took over its first rating period
+        clock.set(season.end);
+        run_pending_tasks().await;
+
+        assert!(store.state().processed.contains(&season.id));
+        assert_eq!(store.seasons_of(2).len(), 2);
+
+        leader.abort();
+    });
 }
@@ -11,11 +11,14 @@
 
 use std::f64::consts::PI;
 
+use chrono::{DateTime, Utc};
 use rocket::form::validate::Len;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     calculations::sech,
-    types::entities::{player::*, r#match::*},
+    types::entities::{player::*, r#match::*, rating_snapshot::RatingSnapshot, season::Season},
 };
 
 /// Duration of a "season" or rating period for the system.
@@ -61,6 +64,30 @@ pub fn default_volatility() -> f64 {
     DEFAULT_VOLATILITY
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+/// When players' ratings are committed
+pub enum RatingMode {
+    /// At the end of each rating period; live ratings are computed from the period's matches
+    /// on every request
+    Periodic,
+    /// Right after each match, rating only that match over the fraction of a rating period
+    /// since the player's previous one; stored ratings are always the live ones
+    Instant,
+}
+
+/// Rating mode used if RATING_MODE is not set
+pub const DEFAULT_RATING_MODE: RatingMode = RatingMode::Periodic;
+
+/// Returns the instance's rating mode, set via RATING_MODE ("periodic" or "instant")
+pub fn rating_mode() -> RatingMode {
+    match std::env::var("RATING_MODE").ok().as_deref() {
+        Some("instant") => RatingMode::Instant,
+        Some("periodic") => RatingMode::Periodic,
+        _ => DEFAULT_RATING_MODE,
+    }
+}
+
 /// Function that normalizes a player's rating for showing
 pub fn rating_to_public(rating: f64) -> f64 {
     (rating as f64 * RATING_CONVERSION_CONSTANT) + DEFAULT_RATING as f64
@@ -81,6 +108,19 @@ pub fn deviation_from_public(deviation: f64) -> f64 {
     deviation as f64 / RATING_CONVERSION_CONSTANT
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// What a player's matches of a rating period add up to; everything but how much of the period
+/// has elapsed, so their rating can be computed again for a later time without the matches
+pub struct PeriodAggregates {
+    /// Estimated variance of the player's rating from the match outcomes (step 3)
+    pub variance: f64,
+    /// The player's new volatility (steps 4 and 5)
+    pub volatility: f64,
+    /// How much better than expected the player did in their matches, weighted by how certain
+    /// their opponents' ratings are
+    pub improvement: f64,
+}
+
 impl Player {
     /// Function that gets a player's rating for calculation
     pub fn get_private_rating(&self) -> f64 {
@@ -154,26 +194,30 @@ impl Player {
         input_matches: Vec<Match>,
         elapsed_periods: f64,
     ) {
-        // Only while we're calculating, make the inner values the private ones
-        self.rating = self.get_private_rating();
-        self.deviation = self.get_private_deviation();
+        let aggregates = self.period_aggregates(input_matches);
 
-        // If matches are empty, only apply step 6
-        if input_matches.is_empty() {
-            self.apply_pre_rating_deviation(elapsed_periods);
+        self.apply_period_aggregates(aggregates.as_ref(), elapsed_periods);
+    }
 
-            self.rating = rating_to_public(self.rating);
-            self.deviation = deviation_to_public(self.deviation);
-            return;
+    /// Calculates what the player's matches of a rating period add up to, for
+    /// [Self::apply_period_aggregates]. Returns None if there are no matches.
+    pub fn period_aggregates(&self, input_matches: Vec<Match>) -> Option<PeriodAggregates> {
+        if input_matches.is_empty() {
+            return None;
         }
 
+        // Only while we're calculating, make the inner values the private ones
+        let mut player = self.clone();
+        player.rating = self.get_private_rating();
+        player.deviation = self.get_private_deviation();
+
         // Convert the values for internal use
 
         // First also sort the matches so player_a is always us
         let mut matches = Vec::new();
 
         for game_match in input_matches {
-            matches.push(game_match.sorted_by_player_id(self.id));
+            matches.push(game_match.sorted_by_player_id(player.id));
         }
 
         // See issue #13 - concatinate similar matches into one matchup
@@ -269,25 +313,19 @@ impl Player {
         }
 
         // Step 3: Calculate anchillary variance
-        let variance = calculate_variance(&self, &matches);
+        let variance = calculate_variance(&player, &matches);
 
         // Step 4 and 5: Calculate volatility with delta
-        self.volatility = calculate_volatility(&self, &matches, variance);
+        let volatility = calculate_volatility(&player, &matches, variance);
 
-        // Step 6
-        self.apply_pre_rating_deviation(elapsed_periods);
-
-        // Step 7: Calculate our deviation
-        self.deviation = 1.0 / ((1.0 / self.deviation.powi(2)) + (1.0 / variance)).sqrt();
-
-        // Calculate our rating
-        let mut temp_sum = 0.0;
+        // The sum for our new rating
+        let mut improvement = 0.0;
 
         for game_match in matches {
-            temp_sum += calculate_g(game_match.deviation_b)
+            improvement += calculate_g(game_match.deviation_b)
                 * (calculate_match_a_score(&game_match)
                     - calculate_e(
-                        &self,
+                        &player,
                         game_match.ping_a,
                         game_match.rating_b,
                         game_match.deviation_b,
@@ -295,12 +333,202 @@ impl Player {
                     ));
         }
 
-        self.rating += self.deviation.powi(2) * temp_sum;
+        Some(PeriodAggregates {
+            variance,
+            volatility,
+            improvement,
+        })
+    }
+
+    /// Updates the player's rating+friends from what their matches of a rating period add up to
+    /// (see [Self::period_aggregates]), once elapsed_periods of it have passed.
+    ///
+    /// Without matches, only their deviation grows.
+    pub fn apply_period_aggregates(
+        &mut self,
+        aggregates: Option<&PeriodAggregates>,
+        elapsed_periods: f64,
+    ) {
+        // Only while we're calculating, make the inner values the private ones
+        self.rating = self.get_private_rating();
+        self.deviation = self.get_private_deviation();
+
+        // If matches are empty, only apply step 6
+        let Some(aggregates) = aggregates else {
+            self.apply_pre_rating_deviation(elapsed_periods);
+
+            self.rating = rating_to_public(self.rating);
+            self.deviation = deviation_to_public(self.deviation);
+            return;
+        };
+
+        self.volatility = aggregates.volatility;
+
+        // Step 6
+        self.apply_pre_rating_deviation(elapsed_periods);
+
+        // Step 7: Calculate our deviation
+        self.deviation =
+            1.0 / ((1.0 / self.deviation.powi(2)) + (1.0 / aggregates.variance)).sqrt();
+
+        // Calculate our rating
+        self.rating += self.deviation.powi(2) * aggregates.improvement;
 
         // Reset back to public ones
         self.rating = rating_to_public(self.rating);
         self.deviation = deviation_to_public(self.deviation);
     }
+
+    /// Returns when the player was last rated in a rating period under [RatingMode::Instant];
+    /// after their latest match in it up to the given time, or at its start
+    fn last_rated_at(
+        &self,
+        season_matches: &[Match],
+        season: &Season,
+        up_to: DateTime<Utc>,
+    ) -> DateTime<Utc> {
+        season_matches
+            .iter()
+            .filter(|x| x.player_a == self.id || x.player_b == self.id)
+            .map(|x| x.epoch)
+            .filter(|x| *x <= up_to)
+            .fold(season.start, |a, b| a.max(b))
+    }
+
+    /// Rates the player for a single match of a rating period, for [RatingMode::Instant].
+    ///
+    /// Their deviation grows over the fraction of the rating period since their previous
+    /// match in it (from season_matches, which may include this one), or since its start.
+    pub fn rate_player_instantly(
+        &mut self,
+        a_match: &Match,
+        season_matches: &[Match],
+        season: &Season,
+    ) {
+        let previous_matches = season_matches
+            .iter()
+            .filter(|x| x.id != a_match.id)
+            .cloned()
+            .collect::<Vec<Match>>();
+
+        let rated_at = self.last_rated_at(&previous_matches, season, a_match.epoch);
+
+        self.rate_player_for_elapsed_periods(
+            vec![a_match.clone()],
+            season.periods_between(rated_at, a_match.epoch),
+        );
+    }
+
+    /// Grows the player's deviation from their latest match of a rating period until the given
+    /// time, for [RatingMode::Instant].
+    ///
+    /// Used at the end of a rating period, so it always adds up to one full period.
+    pub fn rate_player_instantly_until(
+        &mut self,
+        season_matches: &[Match],
+        season: &Season,
+        until: DateTime<Utc>,
+    ) {
+        let rated_at = self.last_rated_at(season_matches, season, until);
+
+        self.rate_player_for_elapsed_periods(Vec::new(), season.periods_between(rated_at, until));
+    }
+}
+
+/// Returns the player with their rating at the start of a rating period, for
+/// [rate_matches_instantly].
+///
+/// That is their rating snapshot for it or, for players who joined during it, their rating
+/// before their first match in it. Returns None if there is neither.
+pub fn player_at_start_of_period(
+    player: &Player,
+    snapshots: &[RatingSnapshot],
+    season_matches: &[Match],
+) -> Option<Player> {
+    let mut player = player.clone();
+
+    if let Some(snapshot) = snapshots.iter().find(|x| x.player_id == player.id) {
+        player.rating = snapshot.rating;
+        player.deviation = snapshot.deviation;
+        player.volatility = snapshot.volatility;
+
+        return Some(player);
+    }
+
+    let first_match = season_matches
+        .iter()
+        .filter(|x| x.player_a == player.id || x.player_b == player.id)
+        .min_by(|a, b| a.epoch.cmp(&b.epoch).then_with(|| a.id.cmp(&b.id)))?
+        .clone()
+        .sorted_by_player_id(player.id);
+
+    player.rating = first_match.rating_a;
+    player.deviation = first_match.deviation_a;
+    player.volatility = first_match.volatility_a;
+
+    Some(player)
+}
+
+/// Rates a rating period's matches one at a time, in the order they were played, for
+/// [RatingMode::Instant].
+///
+/// Players should start with their ratings from the start of the period, see
+/// [player_at_start_of_period]. Each match's ratings are set to the players' ratings right
+/// before it. Players who were suspended or banned when a match was played are not rated for it.
+///
+/// If until is set, everyone's deviation then grows until it, e.g. the end of the period for
+/// players who are not frozen for it.
+pub fn rate_matches_instantly(
+    players: &mut [Player],
+    season_matches: &mut [Match],
+    season: &Season,
+    until: Option<DateTime<Utc>>,
+) {
+    season_matches.sort_by(|a, b| a.epoch.cmp(&b.epoch).then_with(|| a.id.cmp(&b.id)));
+
+    for index in 0..season_matches.len() {
+        let (previous_matches, rest) = season_matches.split_at_mut(index);
+        let a_match = &mut rest[0];
+
+        let player_a = players.iter().position(|x| x.id == a_match.player_a);
+        let player_b = players.iter().position(|x| x.id == a_match.player_b);
+
+        if let Some(player_a) = player_a {
+            a_match.rating_a = players[player_a].rating;
+            a_match.deviation_a = players[player_a].deviation;
+            a_match.volatility_a = players[player_a].volatility;
+        }
+
+        if let Some(player_b) = player_b {
+            a_match.rating_b = players[player_b].rating;
+            a_match.deviation_b = players[player_b].deviation;
+            a_match.volatility_b = players[player_b].volatility;
+        }
+
+        for player in [player_a, player_b].into_iter().flatten() {
+            let player = &mut players[player];
+
+            // Their rating was frozen
+            if player.is_frozen_at(a_match.epoch) {
+                continue;
+            }
+
+            player.rate_player_instantly(a_match, previous_matches, season);
+        }
+    }
+
+    let Some(until) = until else {
+        return;
+    };
+
+    for player in players.iter_mut() {
+        // Suspended and banned players' ratings are frozen
+        if player.is_frozen_for(season) {
+            continue;
+        }
+
+        player.rate_player_instantly_until(season_matches, season, until);
+    }
 }
 
 /// Calculates the new volatility from matches
@@ -419,7 +647,14 @@ fn calculate_g(deviation: f64) -> f64 {
 
 /// One function not stolen and not in glicko, processes a match to a 0 - 1 float of how well player a did
 fn calculate_match_a_score(game_match: &Match) -> f64 {
-    game_match.score_a as f64 / (game_match.score_a + game_match.score_b) as f64
+    let total_score = game_match.score_a as u16 + game_match.score_b as u16;
+
+    // Should be rejected when adding matches, but don't divide by zero if one slipped through
+    if total_score == 0 {
+        return 0.5;
+    }
+
+    game_match.score_a as f64 / total_score as f64
 }
 
 /// One function not stolen and not in glicko, calculates the ability of a player, given r, the player's rating, p, the player's ping, & i, ping influence, a preset value
@@ -454,16 +689,91 @@ pub fn calculate_ping_ability(ping: u16) -> f64 {
     sech(ping as f64 / PING_INFLUENCE)
 }
 
+/// Predicts how well player a will do against player b, as the share of the points player a
+/// is expected to score (0 - 1).
+///
+/// Like [calculate_e], but takes both players' deviations into account since neither of them
+/// is known exactly.
+pub fn predict_match_a_score(
+    player_a: &Player,
+    player_b: &Player,
+    ping_a: u16,
+    ping_b: u16,
+) -> f64 {
+    let ability_a = calculate_player_ability_for_glicko(player_a.get_private_rating(), ping_a);
+    let ability_b = calculate_player_ability_for_glicko(player_b.get_private_rating(), ping_b);
+
+    let deviation = (player_a.get_private_deviation().powi(2)
+        + player_b.get_private_deviation().powi(2))
+    .sqrt();
+
+    1.0 / (1.0 + (-calculate_g(deviation) * (ability_a - ability_b)).exp())
+}
+
+/// Calculates player a's expected score against player b (0 - 1), given the pings they are
+/// expected to play with.
+///
+/// Uses [calculate_e] like a rating period would, so only player b's deviation is taken into
+/// account.
+pub fn calculate_expected_a_score(
+    player_a: &Player,
+    player_b: &Player,
+    ping_a: u16,
+    ping_b: u16,
+) -> f64 {
+    // calculate_e expects the private values
+    let mut private_a = player_a.clone();
+    private_a.rating = player_a.get_private_rating();
+
+    calculate_e(
+        &private_a,
+        ping_a,
+        player_b.get_private_rating(),
+        player_b.get_private_deviation(),
+        ping_b,
+    )
+}
+
+/// How fair a match is, given player a's expected score (0 - 1).
+///
+/// 1 means both players are expected to score as much, 0 means one of them can't lose.
+pub fn calculate_match_fairness(expected_a_score: f64) -> f64 {
+    1.0 - 2.0 * (expected_a_score - 0.5).abs()
+}
+
+/// How much a match would tell us about both players (0 - 1), given player a's expected score.
+///
+/// This is the average share by which the match would shrink each player's rating variance
+/// (step 7 of glicko); uncertain players and close matches are worth more.
+pub fn calculate_match_information(
+    player_a: &Player,
+    player_b: &Player,
+    expected_a_score: f64,
+) -> f64 {
+    let information_for = |player: &Player, opponent: &Player| {
+        let inverse_variance = calculate_g(opponent.get_private_deviation()).powi(2)
+            * expected_a_score
+            * (1.0 - expected_a_score);
+
+        let relative_information = player.get_private_deviation().powi(2) * inverse_variance;
+
+        relative_information / (1.0 + relative_information)
+    };
+
+    (information_for(player_a, player_b) + information_for(player_b, player_a)) / 2.0
+}
+
+/// Combines a match's fairness and information into how good of a match it would be (0 - 1).
+///
+/// Fairness matters most; information decides between similarly fair matches.
+pub fn calculate_match_quality(fairness: f64, information: f64) -> f64 {
+    fairness * (1.0 + information) / 2.0
+}
+
 /// See <http://www.glicko.net/glicko/glicko2.pdf> (Example calculation)
 #[test]
 fn math_is_mathing() {
-    let mut test_1 = Player {
-        id: 1,
-        name: "Test1".to_string(),
-        deviation: 0.0,
-        rating: 0.0,
-        volatility: 0.06,
-    };
+    let mut test_1 = Player::for_tests(1, 0.0, 0.0);
 
     test_1.set_public_rating(1500.0);
     test_1.set_public_deviation(200.0);
@@ -569,3 +879,114 @@ fn math_is_mathing() {
         vec_matches.len()
     );
 }
+
+#[test]
+fn prediction_favours_better_player() {
+    let weaker = Player::for_tests(1, 1400.0, 80.0);
+    let stronger = Player::for_tests(2, 1700.0, 80.0);
+
+    let even = predict_match_a_score(&weaker, &weaker, 50, 50);
+    assert!((even - 0.5).abs() < 0.0001);
+
+    let prediction = predict_match_a_score(&weaker, &stronger, 50, 50);
+    let reverse = predict_match_a_score(&stronger, &weaker, 50, 50);
+
+    assert!(prediction < 0.5);
+    assert!((prediction + reverse - 1.0).abs() < 0.0001);
+
+    // Bad ping should make a player do worse
+    assert!(predict_match_a_score(&stronger, &weaker, 400, 50) < reverse);
+}
+
+#[test]
+fn close_uncertain_matches_are_better() {
+    let player = |rating: f64, deviation: f64| Player::for_tests(0, rating, deviation);
+
+    let quality = |a: &Player, b: &Player| {
+        let expected = calculate_expected_a_score(a, b, 0, 0);
+
+        calculate_match_quality(
+            calculate_match_fairness(expected),
+            calculate_match_information(a, b, expected),
+        )
+    };
+
+    let established = player(1500.0, 50.0);
+
+    assert!(
+        quality(&established, &player(1510.0, 50.0)) > quality(&established, &player(1900.0, 50.0))
+    );
+    assert!(
+        quality(&established, &player(1500.0, 300.0))
+            > quality(&established, &player(1500.0, 50.0))
+    );
+
+    // Ping makes a player weaker, so a better player with worse ping can be a fair match
+    let expected = calculate_expected_a_score(&established, &player(1600.0, 50.0), 0, 150);
+    assert!(expected > calculate_expected_a_score(&established, &player(1600.0, 50.0), 0, 0));
+}
+
+#[test]
+fn instant_ratings_add_up_to_a_period() {
+    let player = |id: u64| Player::for_tests(id, 1500.0, 200.0);
+
+    let start = Utc::now();
+    let season = Season::new(start, start + chrono::TimeDelta::days(20));
+
+    let a_match = |id: u64, days: i64| Match {
+        rating_period: 0,
+        player_a: 1,
+        player_b: 2,
+        id,
+        ping_a: 0,
+        ping_b: 0,
+        rating_a: 0.0,
+        rating_b: 0.0,
+        deviation_a: 0.0,
+        deviation_b: 0.0,
+        volatility_a: 0.0,
+        volatility_b: 0.0,
+        score_a: 10,
+        score_b: 5,
+        epoch: start + chrono::TimeDelta::days(days),
+    };
+
+    let mut players = vec![player(1), player(2), player(3)];
+    let mut matches = vec![a_match(2, 15), a_match(1, 5)];
+
+    rate_matches_instantly(&mut players, &mut matches, &season, Some(season.end));
+
+    // Played in order, each with the ratings from right before it
+    assert_eq!(matches[0].id, 1);
+    assert_eq!(matches[0].rating_a, 1500.0);
+    assert!(matches[1].rating_a > 1500.0);
+    assert!(matches[1].rating_b < 1500.0);
+    assert!(players[0].rating > matches[1].rating_a);
+
+    // Without matches, the deviation grows by exactly one period
+    let mut idle = player(3);
+    idle.rate_player_for_elapsed_periods(Vec::new(), 1.0);
+
+    assert!((players[2].deviation - idle.deviation).abs() < 0.0001);
+
+    // Rating each match as it is added gives the same result
+    let mut instant = player(1);
+    let mut added = Vec::new();
+
+    for mut a_match in [a_match(1, 5), a_match(2, 15)] {
+        a_match.rating_a = instant.rating;
+        a_match.deviation_a = instant.deviation;
+        a_match.volatility_a = instant.volatility;
+        a_match.rating_b = matches[added.len()].rating_b;
+        a_match.deviation_b = matches[added.len()].deviation_b;
+        a_match.volatility_b = matches[added.len()].volatility_b;
+
+        added.push(a_match.clone());
+        instant.rate_player_instantly(&a_match, &added, &season);
+    }
+
+    instant.rate_player_instantly_until(&added, &season, season.end);
+
+    assert!((instant.rating - players[0].rating).abs() < 0.0001);
+    assert!((instant.deviation - players[0].deviation).abs() < 0.0001);
+}
@@ -0,0 +1,294 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{uri::Origin, Method, Status},
+    request::FromRequest,
+    Data, Orbit, Request, Response, Rocket,
+};
+use rocket_db_pools::Database;
+use tokio::sync::Mutex;
+
+use crate::{
+    clock::SharedClock, database::is_constraint_violation, request_guards::api_key::ApiKey,
+    response::ApiError, types::entities::idempotency_key::IdempotencyKey, MysqlDb,
+};
+
+use super::{
+    idempotency_key_ttl, IdempotencyState, RequestBodyHash, IDEMPOTENCY_KEY_CLAIM_TIMEOUT_MINUTES,
+    IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH,
+};
+
+/// Fairing which makes mutating requests with an Idempotency-Key header idempotent.
+///
+/// The first request with a key is processed normally and its response is stored;
+/// retries are routed to [super::get_idempotent_replay], which returns the stored response.
+#[derive(Default)]
+pub struct IdempotencyKeys {
+    db: Mutex<Option<MysqlDb>>,
+    clock: Mutex<Option<SharedClock>>,
+}
+
+#[rocket::async_trait]
+impl Fairing for IdempotencyKeys {
+    fn info(&self) -> Info {
+        Info {
+            name: "Idempotency keys",
+            kind: Kind::Request | Kind::Response | Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let mut lock = self.db.lock().await;
+        *lock = Some(MysqlDb::fetch(&rocket).unwrap().clone());
+
+        let mut lock = self.clock.lock().await;
+        *lock = Some(rocket.state::<SharedClock>().unwrap().clone());
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        // Requests rerouted elsewhere (for example by the ratelimiter) don't start with /api/
+        if !request.uri().to_string().starts_with("/api/") {
+            return;
+        }
+
+        if !matches!(
+            request.method(),
+            Method::Post | Method::Put | Method::Patch | Method::Delete
+        ) {
+            return;
+        }
+
+        let Some(idempotency_key) = request
+            .headers()
+            .get_one(IDEMPOTENCY_KEY_HEADER)
+            .map(|x| x.to_string())
+        else {
+            return;
+        };
+
+        // Mutating requests need to be authorized anyway, the keys are per api key
+        let Some(api_key) = ApiKey::from_request(request).await.succeeded() else {
+            return;
+        };
+
+        if idempotency_key.is_empty() || idempotency_key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+            reject(
+                request,
+                ApiError::invalid_field(&format!(
+                    "{} must be between 1 and {} characters long.",
+                    IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH
+                )),
+            );
+            return;
+        }
+
+        let db = self.db.lock().await.clone().unwrap();
+
+        let now = self.clock.lock().await.as_ref().unwrap().now();
+
+        let query_string = "DELETE FROM idempotency_keys WHERE epoch < ?";
+
+        let query = sqlx::query(&query_string).bind(now - idempotency_key_ttl());
+
+        if let Err(e) = query.execute(&*db).await {
+            log::error!("Failed to clear idempotency keys: {} - {}", e, query_string);
+        }
+
+        let method = request.method().as_str().to_string();
+        let uri = request.uri().to_string();
+
+        let query_string =
+            "SELECT * FROM idempotency_keys WHERE api_key_hash = ? AND idempotency_key = ?";
+
+        let query = sqlx::query_as(&query_string)
+            .bind(&api_key.hash)
+            .bind(&idempotency_key);
+
+        let existing: Option<IdempotencyKey> = match query.fetch_optional(&*db).await {
+            Ok(existing) => existing,
+            Err(e) => {
+                log::error!("Failed to fetch idempotency key: {} - {}", e, query_string);
+                reject(request, ApiError::from_status(Status::InternalServerError));
+                return;
+            }
+        };
+
+        if let Some(existing) = existing {
+            if existing.method != method || existing.uri != uri {
+                log::warn!(
+                    "Idempotency key {:?} was reused for {} {}, it was first used for {} {}",
+                    idempotency_key,
+                    method,
+                    uri,
+                    existing.method,
+                    existing.uri
+                );
+                reject(request, ApiError::idempotency_key_reused());
+                return;
+            }
+
+            match (existing.status, existing.body) {
+                (Some(status), Some(body)) => {
+                    log::info!(
+                        "Replaying response for {} {} with idempotency key {:?}",
+                        method,
+                        uri,
+                        idempotency_key
+                    );
+                    request.local_cache(|| IdempotencyState::Replay {
+                        status,
+                        body,
+                        body_hash: existing.body_hash,
+                    });
+                    reroute(request);
+                    return;
+                }
+                // The request which claimed it never got a response, take it over
+                _ if existing.epoch
+                    < now - chrono::TimeDelta::minutes(IDEMPOTENCY_KEY_CLAIM_TIMEOUT_MINUTES) =>
+                {
+                    // Matching on the epoch too, so only one retry takes it over
+                    let query_string = "UPDATE idempotency_keys SET epoch = ? WHERE api_key_hash = ? AND idempotency_key = ? AND status IS NULL AND epoch = ?";
+
+                    let query = sqlx::query(&query_string)
+                        .bind(now)
+                        .bind(&api_key.hash)
+                        .bind(&idempotency_key)
+                        .bind(existing.epoch);
+
+                    match query.execute(&*db).await {
+                        Ok(result) if result.rows_affected() == 1 => {
+                            log::warn!(
+                                "Idempotency key {:?} for {} {} was claimed since {} without a response, processing it again",
+                                idempotency_key,
+                                method,
+                                uri,
+                                existing.epoch
+                            );
+                        }
+                        Ok(_) => {
+                            reject(request, ApiError::idempotency_key_in_use());
+                            return;
+                        }
+                        Err(e) => {
+                            log::error!(
+                                "Failed to claim idempotency key: {} - {}",
+                                e,
+                                query_string
+                            );
+                            reject(request, ApiError::from_status(Status::InternalServerError));
+                            return;
+                        }
+                    }
+                }
+                _ => {
+                    reject(request, ApiError::idempotency_key_in_use());
+                    return;
+                }
+            }
+        } else {
+            // Claim the key, so a retry sent while we're still processing this one doesn't go through
+            let query_string = "INSERT INTO idempotency_keys (api_key_hash, idempotency_key, method, uri, epoch) VALUES (?, ?, ?, ?, ?)";
+
+            let query = sqlx::query(&query_string)
+                .bind(&api_key.hash)
+                .bind(&idempotency_key)
+                .bind(&method)
+                .bind(&uri)
+                .bind(now);
+
+            match query.execute(&*db).await {
+                Ok(_) => {}
+                // A retry claimed it right before us
+                Err(e) if is_constraint_violation(&e) => {
+                    reject(request, ApiError::idempotency_key_in_use());
+                    return;
+                }
+                Err(e) => {
+                    log::error!("Failed to claim idempotency key: {} - {}", e, query_string);
+                    reject(request, ApiError::from_status(Status::InternalServerError));
+                    return;
+                }
+            }
+        }
+
+        request.local_cache(|| IdempotencyState::New {
+            api_key_hash: api_key.hash,
+            idempotency_key,
+        });
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let IdempotencyState::New {
+            api_key_hash,
+            idempotency_key,
+        } = request.local_cache(IdempotencyState::default)
+        else {
+            return;
+        };
+
+        let db = self.db.lock().await.clone().unwrap();
+
+        // Don't remember server errors, the client should be able to retry those
+        if response.status().code >= 500 {
+            let query_string =
+                "DELETE FROM idempotency_keys WHERE api_key_hash = ? AND idempotency_key = ?";
+
+            let query = sqlx::query(&query_string)
+                .bind(api_key_hash)
+                .bind(idempotency_key);
+
+            if let Err(e) = query.execute(&*db).await {
+                log::error!(
+                    "Failed to release idempotency key: {} - {}",
+                    e,
+                    query_string
+                );
+            }
+
+            return;
+        }
+
+        let body = match response.body_mut().to_string().await {
+            Ok(body) => body,
+            Err(e) => {
+                log::error!("Failed to read response body for idempotency key: {}", e);
+                return;
+            }
+        };
+
+        // Routes which read their body through HashedJson set its hash
+        let RequestBodyHash(body_hash) = request.local_cache(RequestBodyHash::default);
+
+        let query_string = "UPDATE idempotency_keys SET status = ?, body = ?, body_hash = ? WHERE api_key_hash = ? AND idempotency_key = ?";
+
+        let query = sqlx::query(&query_string)
+            .bind(response.status().code)
+            .bind(&body)
+            .bind(body_hash)
+            .bind(api_key_hash)
+            .bind(idempotency_key);
+
+        if let Err(e) = query.execute(&*db).await {
+            log::error!(
+                "Failed to save idempotent response: {} - {}",
+                e,
+                query_string
+            );
+        }
+
+        // We consumed the body while reading it
+        response.set_sized_body(body.len(), std::io::Cursor::new(body));
+    }
+}
+
+/// Routes the request to the stored response
+fn reroute(request: &mut Request<'_>) {
+    request.set_method(Method::Post);
+    request.set_uri(Origin::parse("/.super_secret/idempotent_replay").unwrap());
+}
+
+/// Responds to the request with an error instead of processing it
+fn reject(request: &mut Request<'_>, error: ApiError) {
+    request.local_cache(|| IdempotencyState::Rejected(error));
+    reroute(request);
+}
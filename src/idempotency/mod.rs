@@ -0,0 +1,203 @@
+//! Support for the Idempotency-Key header on mutating requests.
+//!
+//! If a client retries a request with the same key, it gets the original response back instead
+//! of the request being processed again.
+
+use std::ops::Deref;
+
+use rocket::{
+    data::{self, FromData, Limits},
+    http::{ContentType, Header, Status},
+    post,
+    response::{self, Responder},
+    serde::json::{self, Json},
+    Data, Request, Response,
+};
+use rocket_okapi::{
+    gen::OpenApiGenerator,
+    okapi::openapi3::{RequestBody, Responses},
+    openapi,
+    request::OpenApiFromData,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::Digest;
+
+use crate::response::ApiError;
+
+pub mod fairing;
+
+/// The header clients set to make a request idempotent
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The header we set on responses which were replayed from an earlier request
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// The longest idempotency key we accept
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// How long keys are remembered, if IDEMPOTENCY_KEY_TTL_HOURS is not set
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+
+/// Returns how long idempotency keys and their responses are remembered
+pub fn idempotency_key_ttl() -> chrono::Duration {
+    let hours = std::env::var("IDEMPOTENCY_KEY_TTL_HOURS")
+        .ok()
+        .and_then(|x| x.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_IDEMPOTENCY_KEY_TTL_HOURS);
+
+    chrono::TimeDelta::hours(hours)
+}
+
+/// How long a key stays claimed by a request which never got a response, for example because
+/// the server was restarted while processing it. Retries after that are processed again.
+pub const IDEMPOTENCY_KEY_CLAIM_TIMEOUT_MINUTES: i64 = 5;
+
+/// Returns the sha256 hash of a request body, as hex
+pub fn body_hash(body: &[u8]) -> String {
+    let mut hasher = sha2::Sha256::new();
+
+    hasher.update(body);
+
+    hex::encode(hasher.finalize())
+}
+
+#[derive(Debug, Clone, Default)]
+/// The hash of the request body, if the route read it through [HashedJson].
+///
+/// Kept in the request's local cache, so it can be stored along with the response.
+pub struct RequestBodyHash(pub Option<String>);
+
+/// Json data guard which also hashes the request body, so a retry with the same
+/// Idempotency-Key can be checked to have the same body.
+///
+/// Used like rocket's Json for the bodies of mutating routes.
+pub struct HashedJson<T>(pub T);
+
+impl<T> Deref for HashedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: Deserialize<'r>> FromData<'r> for HashedJson<T> {
+    type Error = json::Error<'r>;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = req.limits().get("json").unwrap_or(Limits::JSON);
+
+        // Read the same way rocket's Json does, so errors are the same
+        let body = match data.open(limit).into_string().await {
+            Ok(body) if body.is_complete() => body.into_inner(),
+            Ok(_) => {
+                let eof =
+                    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "data limit exceeded");
+                return data::Outcome::Error((Status::PayloadTooLarge, json::Error::Io(eof)));
+            }
+            Err(e) => return data::Outcome::Error((Status::BadRequest, json::Error::Io(e))),
+        };
+
+        req.local_cache(|| RequestBodyHash(Some(body_hash(body.as_bytes()))));
+
+        let body: &'r str = &req.local_cache(|| ReadRequestBody(body)).0;
+
+        match serde_json::from_str(body) {
+            Ok(value) => data::Outcome::Success(HashedJson(value)),
+            Err(e) if e.classify() == serde_json::error::Category::Data => {
+                data::Outcome::Error((Status::UnprocessableEntity, json::Error::Parse(body, e)))
+            }
+            Err(e) => data::Outcome::Error((Status::BadRequest, json::Error::Parse(body, e))),
+        }
+    }
+}
+
+impl<'r, T: JsonSchema + Deserialize<'r>> OpenApiFromData<'r> for HashedJson<T> {
+    fn request_body(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<RequestBody> {
+        Json::<T>::request_body(gen)
+    }
+}
+
+/// The request body read by [HashedJson], kept in the request's local cache so the
+/// deserialized value can borrow from it
+struct ReadRequestBody(String);
+
+#[derive(Debug, Clone, Default)]
+/// What the idempotency fairing decided to do with a request.
+///
+/// Kept in the request's local cache.
+pub enum IdempotencyState {
+    /// The request has no idempotency key or is not a mutating request
+    #[default]
+    None,
+    /// This is the first request with this key; its response should be stored
+    New {
+        api_key_hash: String,
+        idempotency_key: String,
+    },
+    /// The request was already processed, respond with the stored response
+    Replay {
+        status: u16,
+        body: String,
+        /// The hash of the original request's body, None if its route didn't read it
+        body_hash: Option<String>,
+    },
+    /// The key cannot be used for this request
+    Rejected(ApiError),
+}
+
+/// Responds with the stored response of a retried request
+pub struct IdempotentReplay {
+    /// The hash of the retry's body
+    body_hash: Option<String>,
+}
+
+impl<'r> Responder<'r, 'static> for IdempotentReplay {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        match req.local_cache(IdempotencyState::default) {
+            IdempotencyState::Replay {
+                status,
+                body,
+                body_hash,
+            } => {
+                // Only compared if the original request's route read its body
+                if body_hash.is_some() && *body_hash != self.body_hash {
+                    log::warn!("Idempotency key was reused with a different body");
+                    return ApiError::idempotency_key_reused().respond_to(req);
+                }
+
+                Response::build_from(body.clone().respond_to(req)?)
+                    .header(ContentType::JSON)
+                    .header(Header::new(IDEMPOTENT_REPLAYED_HEADER, "true"))
+                    .status(Status::new(*status))
+                    .ok()
+            }
+            IdempotencyState::Rejected(error) => error.clone().respond_to(req),
+            _ => Err(Status::NotFound),
+        }
+    }
+}
+
+impl rocket_okapi::response::OpenApiResponderInner for IdempotentReplay {
+    fn responses(
+        _gen: &mut rocket_okapi::gen::OpenApiGenerator,
+    ) -> rocket_okapi::Result<rocket_okapi::okapi::openapi3::Responses> {
+        Ok(Responses::default())
+    }
+}
+
+#[openapi(skip)]
+#[post("/.super_secret/idempotent_replay", data = "<data>")]
+/// Gets the stored response of a request that was retried with the same Idempotency-Key
+pub async fn get_idempotent_replay(limits: &Limits, data: Data<'_>) -> IdempotentReplay {
+    let limit = limits.get("json").unwrap_or(Limits::JSON);
+
+    let body_hash = match data.open(limit).into_bytes().await {
+        Ok(body) if body.is_complete() => Some(body_hash(&body)),
+        _ => None,
+    };
+
+    IdempotentReplay { body_hash }
+}
@@ -0,0 +1,42 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::uri::Origin,
+    Data, Request,
+};
+
+use super::{split_ladder_path, RequestedLadder};
+
+/// Fairing which routes /api/ladders/{ladder}/... requests to the normal routes.
+///
+/// The ladder is remembered in the request's local cache, where the [crate::types::entities::ladder::Ladder]
+/// request guard finds it.
+#[derive(Default)]
+pub struct LadderRouter;
+
+#[rocket::async_trait]
+impl Fairing for LadderRouter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Ladder router",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let Some((ladder, path)) = split_ladder_path(request.uri().path().as_str()) else {
+            return;
+        };
+
+        let uri = match request.uri().query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        };
+
+        let Ok(origin) = Origin::parse_owned(uri) else {
+            return;
+        };
+
+        request.local_cache(|| RequestedLadder(Some(ladder)));
+        request.set_uri(origin);
+    }
+}
@@ -0,0 +1,52 @@
+//! Support for several independent ladders in one instance.
+//!
+//! Every route can be used for a specific ladder by prefixing it with /api/ladders/{ladder},
+//! e.g. /api/ladders/tournament/players. Routes without the prefix use the default ladder.
+
+use rocket::http::RawStr;
+
+pub mod fairing;
+
+/// The prefix of routes for a specific ladder
+pub const LADDER_ROUTE_PREFIX: &str = "/api/ladders/";
+
+/// The ladder a request was made for, as given in its uri; None for the default ladder.
+///
+/// Set by [fairing::LadderRouter] in the request's local cache.
+#[derive(Debug, Clone, Default)]
+pub struct RequestedLadder(pub Option<String>);
+
+/// Splits a /api/ladders/{ladder}/{route} path into the ladder and the path of the route,
+/// /api/{route}.
+///
+/// Returns None for other paths, including /api/ladders/{ladder} itself.
+pub fn split_ladder_path(path: &str) -> Option<(String, String)> {
+    let rest = path.strip_prefix(LADDER_ROUTE_PREFIX)?;
+
+    let (ladder, route) = rest.split_once('/')?;
+
+    if ladder.is_empty() || route.is_empty() {
+        return None;
+    }
+
+    let ladder = RawStr::new(ladder).percent_decode_lossy().to_string();
+
+    Some((ladder, format!("/api/{}", route)))
+}
+
+#[test]
+fn splits_ladder_paths() {
+    assert_eq!(
+        split_ladder_path("/api/ladders/casual/players/live"),
+        Some(("casual".to_string(), "/api/players/live".to_string()))
+    );
+    assert_eq!(
+        split_ladder_path("/api/ladders/ranked%201v1/matches"),
+        Some(("ranked 1v1".to_string(), "/api/matches".to_string()))
+    );
+
+    assert_eq!(split_ladder_path("/api/ladders/casual"), None);
+    assert_eq!(split_ladder_path("/api/ladders/casual/"), None);
+    assert_eq!(split_ladder_path("/api/ladders"), None);
+    assert_eq!(split_ladder_path("/api/players"), None);
+}
@@ -0,0 +1,236 @@
+//! Cache of what players' matches of the active rating period add up to, so live ratings aren't
+//! computed again from all of the period's matches on every request.
+//!
+//! A player's entry is forgotten when one of their matches is added or removed, and is only
+//! used while their stored rating and the rating period are the same. The deviation keeps
+//! growing as the rating period goes on; that part is applied on every request, from the cached
+//! aggregates and how much of the period has elapsed.
+
+use std::collections::BTreeMap;
+
+use tokio::sync::Mutex;
+
+use crate::{
+    clock::Clock,
+    database::DbConnection,
+    glicko::PeriodAggregates,
+    types::entities::{player::Player, r#match::Match, season::Season},
+};
+
+/// Cached match aggregates by player id.
+///
+/// Kept in memory; the cache is empty after a restart.
+pub static LIVE_RATINGS: Mutex<LiveRatingCache> = Mutex::const_new(LiveRatingCache {
+    entries: BTreeMap::new(),
+    generation: 0,
+});
+
+#[derive(Debug, Default)]
+/// Cached match aggregates by player id
+pub struct LiveRatingCache {
+    pub entries: BTreeMap<u64, CachedLiveRating>,
+    /// Counts invalidations, so aggregates computed from matches fetched before one aren't cached
+    pub generation: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// What a player's matches of a rating period add up to, cached
+pub struct CachedLiveRating {
+    /// Id of the rating period it was computed for
+    pub rating_period: u64,
+    /// The player's stored rating, deviation and volatility it was computed from
+    pub stored: (f64, f64, f64),
+    /// What the player's matches add up to; None if they haven't played in the period
+    pub aggregates: Option<PeriodAggregates>,
+}
+
+impl CachedLiveRating {
+    /// Returns whether the cached aggregates can still be used for the player in the rating
+    /// period
+    pub fn is_valid_for(&self, player: &Player, season: &Season) -> bool {
+        self.rating_period == season.id
+            && self.stored == (player.rating, player.deviation, player.volatility)
+    }
+}
+
+/// Sets the players' live ratings, if the rating period hypothetically ended right now.
+///
+/// Cached match aggregates are used where possible; the others are computed from the rating
+/// period's matches and cached. The ratings of players who are suspended or banned at the end of
+/// the rating period do not change, like when it is processed.
+pub async fn apply_live_ratings(
+    database_connection: &mut DbConnection,
+    players: &mut [Player],
+    season: &Season,
+    clock: &dyn Clock,
+) {
+    // Their rating is frozen, checked at the end of the rating period like when it is processed
+    let unfrozen = players
+        .iter()
+        .enumerate()
+        .filter(|(_, player)| !player.is_frozen_for(season))
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+
+    let mut aggregates: BTreeMap<u64, Option<PeriodAggregates>> = BTreeMap::new();
+
+    let generation = {
+        let cache = LIVE_RATINGS.lock().await;
+
+        for &index in &unfrozen {
+            let player = &players[index];
+
+            if let Some(cached) = cache.entries.get(&player.id) {
+                if cached.is_valid_for(player, season) {
+                    aggregates.insert(player.id, cached.aggregates);
+                }
+            }
+        }
+
+        cache.generation
+    };
+
+    let to_compute = unfrozen
+        .iter()
+        .map(|&index| players[index].id)
+        .filter(|id| !aggregates.contains_key(id))
+        .collect::<Vec<u64>>();
+
+    let season_matches = match to_compute.as_slice() {
+        [] => Vec::new(),
+        [player_id] => {
+            database_connection
+                .get_player_matches_for_season(*player_id, season.id)
+                .await
+        }
+        _ => database_connection.get_matches_for_season(season.id).await,
+    };
+
+    let mut computed = Vec::new();
+
+    for &index in &unfrozen {
+        let player = &players[index];
+
+        if aggregates.contains_key(&player.id) {
+            continue;
+        }
+
+        let player_matches = season_matches
+            .iter()
+            .filter(|a_match| a_match.player_a == player.id || a_match.player_b == player.id)
+            .cloned()
+            .collect::<Vec<Match>>();
+
+        let player_aggregates = player.period_aggregates(player_matches);
+
+        aggregates.insert(player.id, player_aggregates);
+
+        computed.push((
+            player.id,
+            CachedLiveRating {
+                rating_period: season.id,
+                stored: (player.rating, player.deviation, player.volatility),
+                aggregates: player_aggregates,
+            },
+        ));
+    }
+
+    if !computed.is_empty() {
+        let mut cache = LIVE_RATINGS.lock().await;
+
+        // Matches changed while we were fetching them, what we computed may be outdated
+        if cache.generation == generation {
+            cache.entries.extend(computed);
+        }
+    }
+
+    let season_completion = season.completion(clock);
+
+    for index in unfrozen {
+        let player = &mut players[index];
+
+        player.apply_period_aggregates(aggregates[&player.id].as_ref(), season_completion);
+    }
+}
+
+/// Forgets the cached aggregates of the players, after one of their matches was added or
+/// removed
+pub async fn invalidate_live_ratings(player_ids: &[u64]) {
+    let mut cache = LIVE_RATINGS.lock().await;
+
+    cache.generation += 1;
+
+    for player_id in player_ids {
+        cache.entries.remove(player_id);
+    }
+}
+
+/// Forgets all cached aggregates, after rating periods were reprocessed
+pub async fn clear_live_ratings() {
+    let mut cache = LIVE_RATINGS.lock().await;
+
+    cache.generation += 1;
+    cache.entries.clear();
+}
+
+#[test]
+fn cached_live_ratings_follow_the_clock() {
+    use chrono::Utc;
+
+    let now = Utc::now();
+    let season = Season {
+        id: 3,
+        ..Season::new(now, now + chrono::TimeDelta::days(21))
+    };
+
+    let mut player = Player::for_tests(1, 1500.0, 200.0);
+    let opponent = Player::for_tests(2, 1400.0, 100.0);
+
+    let a_match = Match {
+        id: 1,
+        rating_period: 3,
+        player_a: 1,
+        player_b: 2,
+        ping_a: 0,
+        ping_b: 0,
+        rating_a: player.rating,
+        rating_b: opponent.rating,
+        deviation_a: player.deviation,
+        deviation_b: opponent.deviation,
+        volatility_a: player.volatility,
+        volatility_b: opponent.volatility,
+        score_a: 10,
+        score_b: 5,
+        epoch: now,
+    };
+
+    let cached = CachedLiveRating {
+        rating_period: 3,
+        stored: (player.rating, player.deviation, player.volatility),
+        aggregates: player.period_aggregates(vec![a_match.clone()]),
+    };
+
+    // Applying the cached aggregates later gives the same rating as rating the matches again
+    for completion in [0.1, 0.5, 0.9] {
+        let mut from_cache = player.clone();
+        from_cache.apply_period_aggregates(cached.aggregates.as_ref(), completion);
+
+        let mut from_matches = player.clone();
+        from_matches.rate_player_for_elapsed_periods(vec![a_match.clone()], completion);
+
+        assert_eq!(from_cache, from_matches);
+    }
+
+    assert!(cached.is_valid_for(&player, &season));
+
+    // A new rating period needs them computed again
+    let next_season = Season {
+        id: 4,
+        ..season.clone()
+    };
+    assert!(!cached.is_valid_for(&player, &next_season));
+
+    // As does a changed stored rating
+    player.rating = 1490.0;
+    assert!(!cached.is_valid_for(&player, &season));
+}
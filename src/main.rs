@@ -1,7 +1,7 @@
 // Main file: Hosts server that connects to database.
 // Make sure to first configure .env and your json file with key hashes.
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use dotenv::dotenv;
 use log::info;
@@ -16,20 +16,33 @@ use rocket_okapi::{
 use simplelog::{TermLogger, WriteLogger};
 
 mod calculations;
+mod clock;
 mod database;
 mod glicko;
+mod idempotency;
+mod ladders;
+mod live_ratings;
+mod matchmaking;
 mod rate_limits;
 mod request_guards;
 mod response;
 mod routes;
+mod search;
 mod types;
+mod validation;
 
+use clock::{SharedClock, SystemClock};
+use idempotency::*;
 use rate_limits::*;
 
 use routes::{
     catchers::default_catcher,
-    matches::{add::*, get::*},
-    players::{add::*, get::*},
+    ladders::{add::*, get::*},
+    leaderboard::get::*,
+    matches::{add::*, get::*, reviews::*},
+    matchmaking::queue::*,
+    players::{add::*, get::*, modify::*, opponents::*, stats::*, status::*},
+    system::distribution::*,
     system::get_constants::*,
     system::seasons::get::*,
 };
@@ -64,13 +77,20 @@ async fn main() -> Result<(), rocket::Error> {
     ])
     .unwrap();
 
+    let clock: SharedClock = Arc::new(SystemClock);
+
     let _rocket = rocket::build()
+        .manage(clock)
         .attach(AdHoc::on_liftoff("Necessary log", |_rocket| {
             Box::pin(async { log_logo() })
         }))
         .attach(database::stage())
         .attach(CorsOptions::default().to_cors().unwrap())
         .attach(rate_limits::fairing::RateLimiter::default())
+        .attach(idempotency::fairing::IdempotencyKeys::default())
+        // After the idempotency keys, which should tell apart the same route of different ladders
+        .attach(ladders::fairing::LadderRouter)
+        .attach(matchmaking::stage())
         .mount(
             "/swagger-ui/",
             make_swagger_ui(&SwaggerUIConfig {
@@ -86,17 +106,41 @@ async fn main() -> Result<(), rocket::Error> {
                 get_players_live,
                 get_player,
                 get_player_live,
+                get_head_to_head,
+                get_player_stats,
                 add_player,
+                modify_player,
+                get_player_aliases,
+                merge_players,
+                set_player_status,
                 search_players,
+                get_leaderboard_around,
+                get_suggested_opponents,
+                fuzzy_search_players,
                 get_matches,
                 get_match,
                 add_match,
                 add_match_dummy,
+                add_matches_bulk,
+                get_match_reviews,
+                get_match_review,
+                approve_match_review,
+                reject_match_review,
+                join_matchmaking_queue,
+                leave_matchmaking_queue,
+                get_matchmaking_queue,
+                get_matchmaking_status,
+                get_matchmaking_pairing,
                 get_seasons,
                 get_season,
                 get_latest_season,
                 get_system_constants,
+                get_rating_distribution,
+                get_ladders,
+                get_ladder,
+                add_ladder,
                 get_ratelimited_error,
+                get_idempotent_replay,
             ],
         )
         .launch()
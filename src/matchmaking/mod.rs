@@ -0,0 +1,325 @@
+//! The matchmaking queue; players join it and are paired with a fair opponent by a background
+//! task
+
+use chrono::{DateTime, Utc};
+use log::info;
+use rocket::{fairing::AdHoc, Orbit, Rocket};
+use rocket_db_pools::Database;
+use tokio::sync::Mutex;
+
+use crate::{
+    clock::SharedClock,
+    glicko::{
+        calculate_expected_a_score, calculate_match_fairness, calculate_match_information,
+        calculate_match_quality,
+    },
+    types::entities::{
+        matchmaking::{
+            QueueEntry, MATCHMAKING_INTERVAL_SECONDS, MATCHMAKING_QUALITY_RELAXATION_PER_MINUTE,
+            MATCHMAKING_REGION_WAIT_MINUTES, MIN_MATCHMAKING_QUALITY,
+        },
+        player::Player,
+    },
+    MysqlDb,
+};
+
+/// The players waiting to be paired, first to join first.
+///
+/// Kept in memory; the queue is empty after a restart.
+pub static MATCHMAKING_QUEUE: Mutex<Vec<QueueEntry>> = Mutex::const_new(Vec::new());
+
+/// Matchmaker "fairing"; starts the task which pairs queued players
+pub fn stage() -> AdHoc {
+    AdHoc::on_liftoff("Spawn matchmaker", |rocket| {
+        Box::pin(async move { initialize_matchmaker(rocket) })
+    })
+}
+
+/// Starts the matchmaker task
+fn initialize_matchmaker(rocket: &Rocket<Orbit>) {
+    let db = MysqlDb::fetch(rocket).unwrap().clone();
+    let clock = rocket.state::<SharedClock>().unwrap().clone();
+
+    tokio::spawn(async move {
+        matchmaker_main_task(db, clock).await;
+    });
+}
+
+/// Main loop of the matchmaker;
+///
+/// Every few seconds, pair the queued players who make good matches
+pub async fn matchmaker_main_task(db: MysqlDb, clock: SharedClock) {
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(MATCHMAKING_INTERVAL_SECONDS)).await;
+
+        run_matchmaking_round(&db, clock.now()).await;
+    }
+}
+
+/// Pairs the players in the queue who make good matches and removes them from it.
+///
+/// The queue is only locked to copy it and to take out the paired players, not while waiting
+/// on the database, so players can keep joining and leaving during a round.
+async fn run_matchmaking_round(db: &MysqlDb, now: DateTime<Utc>) {
+    let snapshot = MATCHMAKING_QUEUE.lock().await.clone();
+
+    if snapshot.len() < 2 {
+        return;
+    }
+
+    // Only the queued players, not every player on every round
+    let mut player_ids = snapshot.iter().map(|x| x.player_id).collect::<Vec<u64>>();
+    player_ids.sort_unstable();
+    player_ids.dedup();
+
+    let query_string = format!(
+        "SELECT * FROM players WHERE id IN ({})",
+        vec!["?"; player_ids.len()].join(", ")
+    );
+
+    let mut query = sqlx::query_as(&query_string);
+
+    for player_id in &player_ids {
+        query = query.bind(player_id);
+    }
+
+    let players: Vec<Player> = match query.fetch_all(&**db).await {
+        Ok(players) => players,
+        Err(e) => {
+            log::error!("Matchmaker: Failed to fetch players -> {}", e);
+            return;
+        }
+    };
+
+    // Players who were removed, suspended or banned while waiting can't play
+    let (snapshot, inactive): (Vec<QueueEntry>, Vec<QueueEntry>) =
+        snapshot.into_iter().partition(|entry| {
+            players
+                .iter()
+                .any(|player| player.id == entry.player_id && player.is_active(now))
+        });
+
+    let pairings = find_pairings(&snapshot, &players, now);
+
+    // Take the paired players out of the queue, unless they left it since the snapshot
+    let claimed = {
+        let mut queue = MATCHMAKING_QUEUE.lock().await;
+
+        // Their region and ping may have been updated since, but not when they joined
+        let is_same_entry = |a: &QueueEntry, b: &QueueEntry| {
+            a.player_id == b.player_id && a.joined_at == b.joined_at
+        };
+
+        queue.retain(|entry| !inactive.iter().any(|x| is_same_entry(x, entry)));
+
+        let mut claimed = Vec::new();
+
+        for (index_a, index_b, quality) in pairings {
+            let entry_a = &snapshot[index_a];
+            let entry_b = &snapshot[index_b];
+
+            let is_queued = |entry: &QueueEntry| queue.iter().any(|x| is_same_entry(x, entry));
+
+            if !is_queued(entry_a) || !is_queued(entry_b) {
+                continue;
+            }
+
+            queue.retain(|entry| !is_same_entry(entry, entry_a) && !is_same_entry(entry, entry_b));
+
+            claimed.push((entry_a.clone(), entry_b.clone(), quality));
+        }
+
+        claimed
+    };
+
+    let mut failed = Vec::new();
+
+    for (entry_a, entry_b, quality) in claimed {
+        let query = sqlx::query("INSERT INTO matchmaking_pairings (player_a, player_b, ping_a, ping_b, quality, epoch) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(entry_a.player_id)
+            .bind(entry_b.player_id)
+            .bind(entry_a.ping)
+            .bind(entry_b.ping)
+            .bind(quality)
+            .bind(now);
+
+        match query.execute(&**db).await {
+            Ok(result) => {
+                info!(
+                    "Matchmaker: Paired players {} and {} (quality {:.3}) as pairing {}",
+                    entry_a.player_id,
+                    entry_b.player_id,
+                    quality,
+                    result.last_insert_id()
+                );
+            }
+            Err(e) => {
+                log::error!(
+                    "Matchmaker: Failed to pair players {} and {} -> {}",
+                    entry_a.player_id,
+                    entry_b.player_id,
+                    e
+                );
+
+                failed.push(entry_a);
+                failed.push(entry_b);
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        return;
+    }
+
+    // Put players who could not be paired back in their place, unless they joined again already
+    let mut queue = MATCHMAKING_QUEUE.lock().await;
+
+    for entry in failed {
+        if !queue.iter().any(|x| x.player_id == entry.player_id) {
+            queue.push(entry);
+        }
+    }
+
+    queue.sort_by_key(|entry| entry.joined_at);
+}
+
+/// The lowest match quality a player who joined the queue at joined_at accepts
+pub fn accepted_match_quality(joined_at: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    let waited_minutes = (now - joined_at).num_seconds().max(0) as f64 / 60.0;
+
+    (MIN_MATCHMAKING_QUALITY - waited_minutes * MATCHMAKING_QUALITY_RELAXATION_PER_MINUTE).max(0.0)
+}
+
+/// Finds which queued players should play each other.
+///
+/// Every pair of players of the same ladder is scored by match quality (see
+/// [calculate_match_quality]), with the pings they queued with. A pair is acceptable if its
+/// quality is above what the player who waited longest accepts, and if the players are from the
+/// same region or have waited a while. The best acceptable pairs are picked first.
+///
+/// Returns the indices of the paired entries and the quality of their match.
+pub fn find_pairings(
+    queue: &[QueueEntry],
+    players: &[Player],
+    now: DateTime<Utc>,
+) -> Vec<(usize, usize, f64)> {
+    let find_player = |id: u64| players.iter().find(|x| x.id == id);
+
+    let mut candidates = Vec::new();
+
+    for (index_a, entry_a) in queue.iter().enumerate() {
+        let Some(player_a) = find_player(entry_a.player_id) else {
+            continue;
+        };
+
+        for (index_b, entry_b) in queue.iter().enumerate().skip(index_a + 1) {
+            if entry_a.ladder != entry_b.ladder {
+                continue;
+            }
+
+            let Some(player_b) = find_player(entry_b.player_id) else {
+                continue;
+            };
+
+            let first_joined = entry_a.joined_at.min(entry_b.joined_at);
+
+            let same_region = match (&entry_a.region, &entry_b.region) {
+                (Some(region_a), Some(region_b)) => region_a == region_b,
+                _ => true,
+            };
+
+            let waited_for_other_regions =
+                now - first_joined >= chrono::TimeDelta::minutes(MATCHMAKING_REGION_WAIT_MINUTES);
+
+            if !same_region && !waited_for_other_regions {
+                continue;
+            }
+
+            let expected_score =
+                calculate_expected_a_score(player_a, player_b, entry_a.ping, entry_b.ping);
+
+            let quality = calculate_match_quality(
+                calculate_match_fairness(expected_score),
+                calculate_match_information(player_a, player_b, expected_score),
+            );
+
+            if quality < accepted_match_quality(first_joined, now) {
+                continue;
+            }
+
+            candidates.push((index_a, index_b, quality));
+        }
+    }
+
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let mut paired = vec![false; queue.len()];
+    let mut pairings = Vec::new();
+
+    for (index_a, index_b, quality) in candidates {
+        if paired[index_a] || paired[index_b] {
+            continue;
+        }
+
+        paired[index_a] = true;
+        paired[index_b] = true;
+
+        pairings.push((index_a, index_b, quality));
+    }
+
+    pairings
+}
+
+#[test]
+fn pairs_closest_players() {
+    let player = |id: u64, rating: f64| Player::for_tests(id, rating, 60.0);
+
+    let now = Utc::now();
+
+    let entry = |player_id: u64, region: &str, waited_minutes: i64| QueueEntry {
+        player_id,
+        ladder: 1,
+        region: Some(region.to_string()),
+        ping: 50,
+        joined_at: now - chrono::TimeDelta::minutes(waited_minutes),
+    };
+
+    let players = vec![
+        player(1, 1500.0),
+        player(2, 2400.0),
+        player(3, 1520.0),
+        player(4, 2380.0),
+        player(5, 1500.0),
+    ];
+
+    let queue = vec![
+        entry(1, "eu", 0),
+        entry(2, "eu", 0),
+        entry(3, "eu", 0),
+        entry(4, "eu", 0),
+        // Would be the best match for 1, but is in another region
+        entry(5, "na", 0),
+    ];
+
+    let pairings = find_pairings(&queue, &players, now)
+        .into_iter()
+        .map(|(a, b, _)| (queue[a].player_id, queue[b].player_id))
+        .collect::<Vec<(u64, u64)>>();
+
+    assert!(pairings.contains(&(1, 3)));
+    assert!(pairings.contains(&(2, 4)));
+    assert_eq!(pairings.len(), 2);
+
+    // After waiting long enough, anyone is an acceptable match
+    let queue = vec![entry(1, "eu", 30), entry(2, "na", 30)];
+
+    assert_eq!(find_pairings(&queue, &players, now).len(), 1);
+
+    // Players of different ladders are never paired
+    let mut other_ladder = entry(5, "eu", 30);
+    other_ladder.ladder = 2;
+
+    let queue = vec![entry(1, "eu", 30), other_ladder];
+
+    assert!(find_pairings(&queue, &players, now).is_empty());
+}
@@ -1,6 +1,5 @@
 use std::{net::IpAddr, str::FromStr};
 
-use chrono::Utc;
 use rocket::{
     fairing::{Fairing, Info, Kind},
     http::uri::Origin,
@@ -11,6 +10,7 @@ use rocket_db_pools::Database;
 use tokio::sync::Mutex;
 
 use crate::{
+    clock::SharedClock,
     request_guards::{api_key::ApiKey, ip::RealIpAddr},
     types::entities::recent_request::RecentRequest,
     MysqlDb,
@@ -21,6 +21,7 @@ use super::{API_KEY_ALLOWED_REQUESTS_PER_MINUTE, UNAUTHENTICATED_ALLOWED_REQUEST
 #[derive(Default)]
 pub struct RateLimiter {
     db: Mutex<Option<MysqlDb>>,
+    clock: Mutex<Option<SharedClock>>,
 }
 
 #[rocket::async_trait]
@@ -35,6 +36,9 @@ impl Fairing for RateLimiter {
     async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
         let mut lock = self.db.lock().await;
         *lock = Some(MysqlDb::fetch(&rocket).unwrap().clone());
+
+        let mut lock = self.clock.lock().await;
+        *lock = Some(rocket.state::<SharedClock>().unwrap().clone());
     }
 
     // Increment the counter for `GET` and `POST` requests.
@@ -46,7 +50,7 @@ impl Fairing for RateLimiter {
 
         let db = self.db.lock().await.clone().unwrap();
 
-        let now = Utc::now();
+        let now = self.clock.lock().await.as_ref().unwrap().now();
         let one_minute_ago = now - chrono::TimeDelta::minutes(1);
 
         // Check if it is authenticated or not
@@ -95,7 +99,7 @@ impl Fairing for RateLimiter {
 
         let db = self.db.lock().await.clone().unwrap();
 
-        let now = Utc::now();
+        let now = self.clock.lock().await.as_ref().unwrap().now();
 
         // Check if it is authenticated or not
         let api_key_option = ApiKey::from_request(request).await.succeeded();
@@ -0,0 +1,74 @@
+use rocket::{
+    async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+use rocket_okapi::{
+    gen::OpenApiGenerator,
+    okapi::openapi3::{MediaType, RefOr, Responses},
+    request::{OpenApiFromRequest, RequestHeaderInput},
+};
+
+use crate::response::ApiError;
+
+use super::api_key::ApiKey;
+
+/// Struct representing a requester with a valid api key that is marked as admin in the keyfile.
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Hash)]
+pub struct AdminApiKey(pub ApiKey);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for AdminApiKey {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let api_key = match ApiKey::from_request(request).await {
+            Outcome::Success(api_key) => api_key,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        if !api_key.admin {
+            return Outcome::Error((Status::Forbidden, ApiError::insufficient_permissions()));
+        }
+
+        Outcome::Success(AdminApiKey(api_key))
+    }
+}
+
+impl<'a> OpenApiFromRequest<'a> for AdminApiKey {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        name: String,
+        required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        // Same security scheme, the difference is only in which keys are accepted
+        ApiKey::from_request_input(gen, name, required)
+    }
+
+    fn get_responses(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = ApiKey::get_responses(gen)?;
+
+        let schema = gen.json_schema::<ApiError>();
+        let schema_object = rocket_okapi::okapi::openapi3::Response {
+            description: "\
+        # 403 Forbidden\n\
+        The api key is valid, but it is not an admin key. \
+        "
+            .to_owned(),
+            content: rocket_okapi::okapi::map! {
+                "application/json".to_owned() => MediaType {
+                    schema: Some(schema),
+                    ..Default::default()
+                }
+            },
+            ..Default::default()
+        };
+
+        responses
+            .responses
+            .insert("403".to_owned(), RefOr::Object(schema_object));
+
+        Ok(responses)
+    }
+}
@@ -1,6 +1,5 @@
 use rocket::{
     async_trait,
-    form::validate::Contains,
     http::Status,
     request::{FromRequest, Outcome},
 };
@@ -18,10 +17,12 @@ use sha2::Digest;
 use crate::response::ApiError;
 
 /// Struct representing a requester with a valid api key.
-// TODO: add perms
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Hash)]
 pub struct ApiKey {
     pub hash: String,
+    /// Whether the key can use admin endpoints, false if not set in the keyfile
+    #[serde(default)]
+    pub admin: bool,
 }
 
 #[async_trait]
@@ -44,11 +45,6 @@ impl<'r> FromRequest<'r> for ApiKey {
         let deserialized: Vec<ApiKey> =
             serde_json::from_str(&file_contents).expect("Failed to deserialize keyfile");
 
-        let hashes = deserialized
-            .iter()
-            .map(|x| &x.hash)
-            .collect::<Vec<&String>>();
-
         let mut hasher = sha2::Sha256::new();
 
         hasher.update(auth_header.as_bytes());
@@ -57,8 +53,8 @@ impl<'r> FromRequest<'r> for ApiKey {
 
         let hash_as_hex = hex::encode(auth_header_hash);
 
-        if hashes.contains(&hash_as_hex) {
-            return Outcome::Success(ApiKey { hash: hash_as_hex });
+        if let Some(api_key) = deserialized.into_iter().find(|x| x.hash == hash_as_hex) {
+            return Outcome::Success(api_key);
         }
 
         Outcome::Error((Status::Unauthorized, ApiError::invalid_auth()))
@@ -0,0 +1,71 @@
+use rocket::{
+    async_trait,
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use rocket_db_pools::Connection;
+use rocket_okapi::{okapi::openapi3::Responses, request::OpenApiFromRequest};
+
+use crate::{
+    database::DbConnection,
+    ladders::RequestedLadder,
+    response::ApiError,
+    types::entities::ladder::{Ladder, DEFAULT_LADDER_ID},
+    MysqlDb,
+};
+
+/// Provides the ladder a request was made for.
+///
+/// That is the {ladder} of /api/ladders/{ladder}/... routes, found via an id or name, or the
+/// default ladder for other routes.
+#[async_trait]
+impl<'r> FromRequest<'r> for Ladder {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let requested = request.local_cache(RequestedLadder::default);
+
+        let db = match request.guard::<Connection<MysqlDb>>().await {
+            Outcome::Success(db) => db,
+            _ => {
+                return Outcome::Error((
+                    Status::ServiceUnavailable,
+                    ApiError::from_status(Status::ServiceUnavailable),
+                ))
+            }
+        };
+
+        let mut database_connection = DbConnection::from_inner(db);
+
+        let ladder = match &requested.0 {
+            Some(query) => database_connection.get_ladder_by_id_or_name(query).await,
+            None => {
+                database_connection
+                    .get_ladder_by_id(DEFAULT_LADDER_ID)
+                    .await
+            }
+        };
+
+        match ladder {
+            Some(ladder) => Outcome::Success(ladder),
+            None => Outcome::Error((Status::NotFound, ApiError::from_status(Status::NotFound))),
+        }
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for Ladder {
+    fn get_responses(
+        _gen: &mut rocket_okapi::gen::OpenApiGenerator,
+    ) -> rocket_okapi::Result<Responses> {
+        Ok(Responses::default())
+    }
+
+    fn from_request_input(
+        _gen: &mut rocket_okapi::gen::OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<rocket_okapi::request::RequestHeaderInput> {
+        Ok(rocket_okapi::request::RequestHeaderInput::None)
+    }
+}
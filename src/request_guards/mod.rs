@@ -1,3 +1,5 @@
+pub mod admin_api_key;
 pub mod api_key;
 pub mod chrono;
 pub mod ip;
+pub mod ladder;
@@ -20,6 +20,29 @@ fn example_api_error_message() -> String {
     ApiError::username_already_taken().message
 }
 
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug, JsonSchema)]
+/// Further information about one part of an [ApiError],
+/// such as one invalid item in a bulk request.
+pub struct ApiErrorDetail {
+    /// Where the error occured, for example the index of an item in a bulk request.
+    pub location: String,
+    /// Code of this specific error, same as [ApiError::code]
+    pub code: u16,
+    /// A user readable message of what went wrong.
+    pub message: String,
+}
+
+impl ApiErrorDetail {
+    /// Creates a detail at the given location from an existing error
+    pub fn from_error(location: String, error: &ApiError) -> Self {
+        ApiErrorDetail {
+            location,
+            code: error.code,
+            message: error.message.clone(),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug, JsonSchema)]
 /// Structure representing an api error.
 ///
@@ -33,6 +56,9 @@ pub struct ApiError {
     pub code: u16,
     /// A user readable message of what went wrong.
     pub message: String,
+    /// Additional errors which caused this one, if there were several.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<ApiErrorDetail>,
     #[schemars(skip)]
     pub status: Status,
 }
@@ -43,6 +69,7 @@ impl ApiError {
             message,
             code,
             status,
+            details: Vec::new(),
         }
     }
 
@@ -52,6 +79,7 @@ impl ApiError {
             status,
             code: 0, // Here the code is set to 0, which means we should look at the http status
             message: status.reason().unwrap_or_default().to_string(),
+            details: Vec::new(),
         }
     }
 
@@ -61,6 +89,7 @@ impl ApiError {
             status: Status::Unauthorized,
             code: 1,
             message: "Invalid credentials.".to_string(),
+            details: Vec::new(),
         }
     }
 
@@ -70,6 +99,7 @@ impl ApiError {
             status: Status::TooManyRequests,
             code: 2,
             message: "You are sending requests too quickly, chill out a bit.".to_string(),
+            details: Vec::new(),
         }
     }
 
@@ -79,15 +109,17 @@ impl ApiError {
             status: Status::BadRequest,
             code: 3,
             message: "A user with that username already exists.".to_string(),
+            details: Vec::new(),
         }
     }
 
-    /// Returns an error for when we tried to add a match where one player played against himself
+    /// Returns an error for when we tried to add a player with an invalid username
     pub fn invalid_username(error: &str) -> Self {
         ApiError {
             status: Status::BadRequest,
             code: 4,
             message: error.to_string(),
+            details: Vec::new(),
         }
     }
 
@@ -99,6 +131,153 @@ impl ApiError {
             message:
                 "Player a cannot be player b; a player cannot play a ranked match against themself"
                     .to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when we tried to add a match with invalid data, such as a score
+    /// out of bounds
+    pub fn invalid_match(error: &str) -> Self {
+        ApiError {
+            status: Status::BadRequest,
+            code: 6,
+            message: error.to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when one or more items of a bulk request were invalid.
+    ///
+    /// Each invalid item is included in details, with its index as the location.
+    pub fn bulk_request_invalid(details: Vec<ApiErrorDetail>) -> Self {
+        ApiError {
+            status: Status::BadRequest,
+            code: 7,
+            message: "One or more items of the request were invalid; nothing was added."
+                .to_string(),
+            details,
+        }
+    }
+
+    /// Returns an error for when the value of one field of a request is invalid.
+    ///
+    /// Usually used as a detail of [Self::validation_failed]
+    pub fn invalid_field(error: &str) -> Self {
+        ApiError {
+            status: Status::BadRequest,
+            code: 8,
+            message: error.to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when a request did not pass validation.
+    ///
+    /// Each broken rule is included in details, with the field as the location.
+    pub fn validation_failed(details: Vec<ApiErrorDetail>) -> Self {
+        ApiError {
+            status: Status::BadRequest,
+            code: 8,
+            message: "The request is invalid; see details for what needs to be changed."
+                .to_string(),
+            details,
+        }
+    }
+
+    /// Returns an error for when an Idempotency-Key was already used for a different request
+    pub fn idempotency_key_reused() -> Self {
+        ApiError {
+            status: Status::UnprocessableEntity,
+            code: 9,
+            message: "This Idempotency-Key was already used for a different request.".to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when the original request with an Idempotency-Key
+    /// is still being processed
+    pub fn idempotency_key_in_use() -> Self {
+        ApiError {
+            status: Status::Conflict,
+            code: 10,
+            message:
+                "A request with this Idempotency-Key is still being processed, try again later."
+                    .to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when a valid api key tried to use an endpoint it is not allowed to,
+    /// such as admin endpoints
+    pub fn insufficient_permissions() -> Self {
+        ApiError {
+            status: Status::Forbidden,
+            code: 11,
+            message: "Your api key is not allowed to do that.".to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when an admin tried to approve or reject a match review which was
+    /// already approved or rejected
+    pub fn match_review_already_resolved() -> Self {
+        ApiError {
+            status: Status::Conflict,
+            code: 12,
+            message: "This match review was already resolved.".to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when we tried to merge a player into themselves
+    pub fn merge_player_into_itself() -> Self {
+        ApiError {
+            status: Status::BadRequest,
+            code: 13,
+            message: "A player cannot be merged into themselves.".to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when we tried to add a match with a suspended or banned player
+    pub fn player_not_active(name: &str) -> Self {
+        ApiError {
+            status: Status::BadRequest,
+            code: 14,
+            message: format!(
+                "Player {} is suspended or banned and cannot play ranked matches.",
+                name
+            ),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for when we try to add a ladder with an existing name
+    pub fn ladder_name_already_taken() -> Self {
+        ApiError {
+            status: Status::BadRequest,
+            code: 15,
+            message: "A ladder with that name already exists.".to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns an error for a database constraint violation caused by the request, see
+    /// [crate::database::is_constraint_violation].
+    ///
+    /// A duplicate of a unique value becomes already_exists, e.g. [Self::username_already_taken];
+    /// a reference to a row which doesn't exist (anymore) a 404. Any other error is logged and
+    /// becomes a 500.
+    pub fn from_constraint_violation(e: &sqlx::Error, already_exists: ApiError) -> Self {
+        match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => already_exists,
+            sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+                ApiError::from_status(Status::NotFound)
+            }
+            _ => {
+                log::error!("Database query failed -> {}", e);
+                ApiError::from_status(Status::InternalServerError)
+            }
         }
     }
 }
@@ -114,11 +293,13 @@ impl std::fmt::Display for ApiError {
 
 impl<'r> Responder<'r, 'static> for ApiError {
     fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
-        let string = format!(
-            r#"{{ "code": {}, "message": {:?} }}"#,
-            self.code, self.message
-        );
-        Response::build_from(string.respond_to(req)?)
+        let mut body = serde_json::json!({ "code": self.code, "message": self.message });
+
+        if !self.details.is_empty() {
+            body["details"] = serde_json::to_value(&self.details).unwrap();
+        }
+
+        Response::build_from(body.to_string().respond_to(req)?)
             .header(ContentType::JSON)
             .status(self.status)
             .ok()
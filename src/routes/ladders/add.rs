@@ -0,0 +1,72 @@
+use log::info;
+use rocket::{post, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::{season_handler::create_new_season, DbConnection},
+    idempotency::HashedJson,
+    request_guards::admin_api_key::AdminApiKey,
+    response::ApiError,
+    types::{entities::ladder::Ladder, schema::ladder::AddLadderSchema},
+    validation::Validate,
+    MysqlDb,
+};
+
+#[openapi(ignore = "db", tag = "Ladders")]
+#[post("/api/ladders", data = "<schema>")]
+#[allow(unused)]
+/// Adds a ladder, with its own players, matches and rating periods.
+///
+/// Requires an admin api key.
+///
+/// Its first rating period starts right away.
+///
+/// Returns the added ladder.
+///
+/// Returns an error with code 15 if the name is already taken.
+///
+/// Returns an error with code 8 if the request is invalid; its details list each invalid field.
+pub async fn add_ladder(
+    db: Connection<MysqlDb>,
+    db_pool: &State<MysqlDb>,
+    clock: &State<SharedClock>,
+    admin_key: AdminApiKey,
+    schema: HashedJson<AddLadderSchema>,
+) -> Result<Json<Ladder>, ApiError> {
+    schema.validate(clock.now())?;
+
+    let mut database_connection = DbConnection::from_inner(db);
+
+    if database_connection
+        .get_ladder_by_name(&schema.name)
+        .await
+        .is_some()
+    {
+        return Err(ApiError::ladder_name_already_taken());
+    }
+
+    let mut ladder = schema.to_ladder();
+
+    // Someone may have taken the name since we checked
+    let result = database_connection.add_ladder(&ladder).await.map_err(|e| {
+        ApiError::from_constraint_violation(&e, ApiError::ladder_name_already_taken())
+    })?;
+
+    ladder.id = result.last_insert_id();
+
+    info!("Added ladder {} ({})", ladder.id, ladder.name);
+
+    // The instance running the season handler picks it up from here, and creates the first
+    // season itself if this fails
+    if let Err(e) = create_new_season(db_pool, &ladder, clock.as_ref()).await {
+        log::error!(
+            "Failed to create the first season of ladder {}! {}",
+            ladder.id,
+            e
+        );
+    }
+
+    Ok(Json(ladder))
+}
@@ -0,0 +1,32 @@
+use rocket::{get, http::Status, serde::json::Json};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{database::DbConnection, response::ApiError, types::entities::ladder::Ladder, MysqlDb};
+
+#[openapi(ignore = "db", tag = "Ladders")]
+#[get("/api/ladders")]
+/// Fetches all ladders.
+///
+/// Every other route can be used for a specific ladder by prefixing it with /ladders/{ladder},
+/// where {ladder} is the ladder's id or name; e.g. /ladders/casual/players. Routes without the
+/// prefix use the default ladder.
+pub async fn get_ladders(db: Connection<MysqlDb>) -> Json<Vec<Ladder>> {
+    let mut database_connection = DbConnection::from_inner(db);
+
+    Json(database_connection.get_ladders().await)
+}
+
+#[openapi(ignore = "db", tag = "Ladders")]
+#[get("/api/ladders/<query>")]
+/// Fetches a ladder via an id or name.
+///
+/// If no such ladder is found, the ApiError will have code 0 and message "Not Found"
+pub async fn get_ladder(db: Connection<MysqlDb>, query: &str) -> Result<Json<Ladder>, ApiError> {
+    let mut database_connection = DbConnection::from_inner(db);
+
+    match database_connection.get_ladder_by_id_or_name(query).await {
+        None => Err(ApiError::from_status(Status::NotFound)),
+        Some(ladder) => Ok(Json(ladder)),
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Utc};
+use rocket::{get, http::Status, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::{query::QueryParameters, DbConnection},
+    response::ApiError,
+    routes::players::get::get_all_players_live,
+    types::{
+        entities::{ladder::Ladder, player::Player},
+        schema::player::RankedPlayerSchema,
+    },
+    MysqlDb,
+};
+
+/// How many players above and below are returned by default
+pub const DEFAULT_LEADERBOARD_RADIUS: usize = 5;
+/// The most players above and below which can be requested
+pub const MAX_LEADERBOARD_RADIUS: usize = 50;
+
+#[openapi(ignore = "db", tag = "Leaderboard")]
+#[get("/api/leaderboard/around/<query>?<radius>&<live>")]
+/// Fetches a player, found via an id or username, and the players ranked right above and
+/// below them, ordered by rank.
+///
+/// ?radius sets how many players above and below are included; 5 by default, at most 50.
+///
+/// If ?live is true, players are ranked by their live rating, as if the season ended right now.
+///
+/// Returns a 404 if the player does not exist.
+///
+/// Returns an error with code 8 if the radius is too large.
+///
+/// Returns an error with code 14 if the player is suspended or banned, since they are not ranked.
+pub async fn get_leaderboard_around(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    query: &str,
+    radius: Option<usize>,
+    live: Option<bool>,
+) -> Result<Json<Vec<RankedPlayerSchema>>, ApiError> {
+    let radius = radius.unwrap_or(DEFAULT_LEADERBOARD_RADIUS);
+
+    if radius > MAX_LEADERBOARD_RADIUS {
+        return Err(ApiError::invalid_field(&format!(
+            "radius cannot be larger than {}.",
+            MAX_LEADERBOARD_RADIUS
+        )));
+    }
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let players = match live.unwrap_or(false) {
+        true => get_all_players_live(&mut database_connection, clock.as_ref()).await,
+        false => {
+            database_connection
+                .get_players(QueryParameters::default())
+                .await
+        }
+    };
+
+    let ranked = rank_players(players, clock.now());
+
+    let Some(index) = ranked
+        .iter()
+        .position(|x| x.player.id == player.id && x.rank.is_some())
+    else {
+        return Err(ApiError::player_not_active(&player.name));
+    };
+
+    let start = index.saturating_sub(radius);
+    let end = (index + radius + 1).min(ranked.len());
+
+    Ok(Json(
+        ranked
+            .into_iter()
+            .take(end)
+            .skip(start)
+            .filter(|x| x.rank.is_some())
+            .collect(),
+    ))
+}
+
+/// Ranks players by their rating, highest first; ties go to the player who was added first.
+///
+/// Players who are suspended or banned at the given time are not ranked and come last.
+pub fn rank_players(players: Vec<Player>, at: DateTime<Utc>) -> Vec<RankedPlayerSchema> {
+    let (mut ranked, unranked): (Vec<Player>, Vec<Player>) =
+        players.into_iter().partition(|x| x.is_active(at));
+
+    ranked.sort_by(|a, b| {
+        b.get_public_rating()
+            .total_cmp(&a.get_public_rating())
+            .then(a.id.cmp(&b.id))
+    });
+
+    let total_ranked = ranked.len() as u64;
+
+    let ranked = ranked
+        .into_iter()
+        .enumerate()
+        .map(|(i, player)| RankedPlayerSchema::new(player, Some(i as u64 + 1), total_ranked));
+
+    let unranked = unranked
+        .into_iter()
+        .map(|player| RankedPlayerSchema::new(player, None, total_ranked));
+
+    ranked.chain(unranked).collect()
+}
+
+/// Finds where a player places among the stored ratings of the other players, at the given time,
+/// like [rank_players].
+///
+/// Counts in the database instead of fetching every player.
+pub async fn rank_player_by_stored_ratings(
+    database_connection: &mut DbConnection,
+    player: Player,
+    at: DateTime<Utc>,
+) -> RankedPlayerSchema {
+    let total_ranked = database_connection
+        .count_rows(
+            "SELECT COUNT(*) FROM players",
+            QueryParameters {
+                active_at: Some(at),
+                ..Default::default()
+            },
+        )
+        .await;
+
+    if !player.is_active(at) {
+        return RankedPlayerSchema::new(player, None, total_ranked);
+    }
+
+    let ranked_above = database_connection
+        .count_players_ranked_above(&player, at)
+        .await;
+
+    RankedPlayerSchema::new(player, Some(ranked_above + 1), total_ranked)
+}
+
+/// Finds where a player places among all players, at the given time
+pub fn rank_player(
+    player_id: u64,
+    players: Vec<Player>,
+    at: DateTime<Utc>,
+) -> Option<RankedPlayerSchema> {
+    rank_players(players, at)
+        .into_iter()
+        .find(|x| x.player.id == player_id)
+}
+
+#[test]
+fn ranks_highest_rating_first() {
+    use crate::types::entities::player::PlayerStatus;
+
+    let player = |id: u64, rating: f64, status: PlayerStatus| Player {
+        status,
+        ..Player::for_tests(id, rating, 50.0)
+    };
+
+    let ranked = rank_players(
+        vec![
+            player(1, 1500.0, PlayerStatus::Active),
+            player(2, 1700.0, PlayerStatus::Active),
+            player(3, 1900.0, PlayerStatus::Banned),
+            player(4, 1500.0, PlayerStatus::Active),
+        ],
+        Utc::now(),
+    );
+
+    let order = ranked.iter().map(|x| x.player.id).collect::<Vec<u64>>();
+
+    assert_eq!(order, vec![2, 1, 4, 3]);
+    assert_eq!(ranked[0].rank, Some(1));
+    assert_eq!(ranked[0].percentile, Some(100.0));
+    assert_eq!(ranked[3].rank, None);
+    assert_eq!(ranked[3].total_ranked, 3);
+}
@@ -1,40 +1,80 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use log::info;
-use rocket::{http::Status, post, serde::json::Json};
+use rocket::{http::Status, post, serde::json::Json, State};
 use rocket_db_pools::Connection;
 use rocket_okapi::openapi;
 
 use crate::{
-    database::DbConnection,
+    clock::SharedClock,
+    database::{
+        season_handler::{self, reprocess_seasons_from},
+        DbConnection,
+    },
+    glicko::{rating_mode, RatingMode},
+    idempotency::HashedJson,
+    live_ratings::invalidate_live_ratings,
     request_guards::api_key::ApiKey,
-    response::ApiError,
+    response::{ApiError, ApiErrorDetail},
     types::{
-        entities::r#match::Match,
-        schema::r#match::{AddMatchReturnSchema, AddMatchSchema},
+        entities::{ladder::Ladder, player::Player, r#match::Match, season::Season},
+        schema::r#match::{AddMatchReturnSchema, AddMatchSchema, AddMatchesReturnSchema},
     },
+    validation::Validate,
     MysqlDb,
 };
 
 #[openapi(ignore = "db", tag = "Matches")]
 #[post("/api/matches", data = "<schema>")]
 #[allow(unused)]
-/// Adds a match to the latest rating period.
+/// Adds a match to the latest rating period, or to the one containing played_at if it is set.
 ///
 /// Requires authorization.
 ///
 /// Has a special return type which includes the created match
 /// along with the new live ratings of the two players.
 ///
+/// If the instance rates matches instantly (RATING_MODE=instant), the new ratings are
+/// committed right away, so they are also the players' stored ratings.
+///
+/// If the match was played in a rating period that has already been processed, that period
+/// and all later ones are processed again to include it.
+///
 /// Returns a 404 if either one of the two players don't exist.
 ///
 /// Returns an error with code 5 if player_a is player_b, since players usually
 /// do not play against themselves.
+///
+/// Returns an error with code 6 if played_at is in the future, isn't in any rating
+/// period or we do not have the players' ratings from that rating period.
+///
+/// Returns an error with code 14 if either player is suspended or banned.
+///
+/// Returns an error with code 8 if the scores or pings are out of bounds; its details list
+/// each invalid field.
+///
+/// If the match looks like a duplicate of an earlier one (same players, same score and similar
+/// ping within a few minutes), it is still added but also queued for review by an admin; the
+/// review is included in the response.
+///
+/// If the players were paired by the matchmaker shortly before, the match is linked to their
+/// pairing, which is included in the response.
+///
+/// Supports the Idempotency-Key header; retrying with the same key returns the original
+/// response instead of adding it again. Returns an error with code 9 if the key was used for a
+/// different request and code 10 if the original request is still being processed.
 pub async fn add_match(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
+    db_pool: &State<MysqlDb>,
+    clock: &State<SharedClock>,
     api_key: ApiKey,
-    schema: Json<AddMatchSchema>,
+    schema: HashedJson<AddMatchSchema>,
 ) -> Result<Json<AddMatchReturnSchema>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+    let now = clock.now();
+
+    schema.validate(now)?;
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
     let started = std::time::Instant::now();
 
@@ -62,49 +102,96 @@ pub async fn add_match(
         return Err(ApiError::match_player_a_is_player_b());
     }
 
-    let current_rating_period = database_connection
-        .get_latest_active_season()
-        .await
-        .unwrap();
+    check_players_active(&player_a, &player_b, now)?;
 
-    let now = Utc::now();
+    let rating_period =
+        get_rating_period_for_match(&mut database_connection, schema.played_at, now).await?;
 
-    let mut a_match = Match {
-        id: 0,
-        rating_period: current_rating_period.id,
-        player_a: player_a.id,
-        player_b: player_b.id,
-        rating_a: player_a.rating,
-        rating_b: player_b.rating,
-        deviation_a: player_a.deviation,
-        deviation_b: player_b.deviation,
-        volatility_a: player_a.volatility,
-        volatility_b: player_b.volatility,
-        ping_a: schema.ping_a,
-        ping_b: schema.ping_b,
-        score_a: schema.score_a,
-        score_b: schema.score_b,
-        epoch: now,
-    };
+    let mut a_match = create_match(
+        &mut database_connection,
+        &player_a,
+        &player_b,
+        &schema,
+        &rating_period,
+        now,
+    )
+    .await?;
 
-    let result = database_connection.add_match(&a_match).await.unwrap();
+    // Either player may have been merged into another since we fetched them
+    let result = database_connection.add_match(&a_match).await.map_err(|e| {
+        ApiError::from_constraint_violation(&e, ApiError::from_status(Status::NotFound))
+    })?;
 
     a_match.id = result.last_insert_id();
 
-    // Compute live ratings
-    let season_completion = current_rating_period.completion();
-
-    let player_a_matches = database_connection
-        .get_player_matches_for_season(player_a.id, current_rating_period.id)
+    let review = database_connection
+        .flag_possible_duplicate(&a_match, now)
         .await;
-    let player_b_matches = database_connection
-        .get_player_matches_for_season(player_b.id, current_rating_period.id)
+
+    let pairing = database_connection
+        .link_match_to_matchmaking_pairing(&a_match)
         .await;
 
+    invalidate_live_ratings(&[a_match.player_a, a_match.player_b]).await;
+
+    let played_in_processed_period = rating_period.processed;
+
+    let current_rating_period = if rating_period.processed {
+        info!(
+            "Match {} was played in processed season {}, reprocessing",
+            a_match.id, rating_period.id
+        );
+
+        reprocess_seasons_from(db_pool, rating_period.id).await;
+
+        // Their stored ratings may have changed
+        player_a = database_connection
+            .get_player_by_id(player_a.id)
+            .await
+            .unwrap();
+        player_b = database_connection
+            .get_player_by_id(player_b.id)
+            .await
+            .unwrap();
+
+        database_connection
+            .get_latest_active_season(now)
+            .await
+            .unwrap()
+    } else {
+        rating_period
+    };
+
     let math_started = std::time::Instant::now();
 
-    player_a.rate_player_for_elapsed_periods(player_a_matches, season_completion);
-    player_b.rate_player_for_elapsed_periods(player_b_matches, season_completion);
+    match rating_mode() {
+        // Reprocessing already committed the new ratings
+        RatingMode::Instant if played_in_processed_period => {}
+        RatingMode::Instant => {
+            rate_match_instantly(
+                &mut database_connection,
+                db_pool,
+                &mut a_match,
+                [&mut player_a, &mut player_b],
+                &current_rating_period,
+            )
+            .await
+        }
+        RatingMode::Periodic => {
+            // Compute live ratings
+            let season_completion = current_rating_period.completion(clock.as_ref());
+
+            let player_a_matches = database_connection
+                .get_player_matches_for_season(player_a.id, current_rating_period.id)
+                .await;
+            let player_b_matches = database_connection
+                .get_player_matches_for_season(player_b.id, current_rating_period.id)
+                .await;
+
+            player_a.rate_player_for_elapsed_periods(player_a_matches, season_completion);
+            player_b.rate_player_for_elapsed_periods(player_b_matches, season_completion);
+        }
+    }
 
     let math_elapsed = math_started.elapsed();
     let elapsed = started.elapsed();
@@ -118,6 +205,8 @@ pub async fn add_match(
         live_a: player_a,
         live_b: player_b,
         created: a_match,
+        review,
+        pairing,
     };
 
     Ok(Json(return_schema))
@@ -136,12 +225,26 @@ pub async fn add_match(
 /// Returns an error with code 5 if player_a is player_b, since players usually
 /// do not play against themselves.
 ///
+/// Returns an error with code 6 if played_at is invalid, or if it is in a rating period
+/// which was already processed; those cannot be dry-run.
+///
+/// Returns an error with code 14 if either player is suspended or banned.
+///
+/// Returns an error with code 8 if the scores or pings are out of bounds; its details list
+/// each invalid field.
+///
 /// (Behaves similarly to POST /matches/)
 pub async fn add_match_dummy(
     db: Connection<MysqlDb>,
-    schema: Json<AddMatchSchema>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    schema: HashedJson<AddMatchSchema>,
 ) -> Result<Json<AddMatchReturnSchema>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+    let now = clock.now();
+
+    schema.validate(now)?;
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
     let started = std::time::Instant::now();
 
@@ -169,33 +272,29 @@ pub async fn add_match_dummy(
         return Err(ApiError::match_player_a_is_player_b());
     }
 
-    let current_rating_period = database_connection
-        .get_latest_active_season()
-        .await
-        .unwrap();
+    check_players_active(&player_a, &player_b, now)?;
 
-    let now = Utc::now();
+    let current_rating_period =
+        get_rating_period_for_match(&mut database_connection, schema.played_at, now).await?;
 
-    let mut a_match = Match {
-        id: 0,
-        rating_period: current_rating_period.id,
-        player_a: player_a.id,
-        player_b: player_b.id,
-        rating_a: player_a.rating,
-        rating_b: player_b.rating,
-        deviation_a: player_a.deviation,
-        deviation_b: player_b.deviation,
-        volatility_a: player_a.volatility,
-        volatility_b: player_b.volatility,
-        ping_a: schema.ping_a,
-        ping_b: schema.ping_b,
-        score_a: schema.score_a,
-        score_b: schema.score_b,
-        epoch: now,
-    };
+    if current_rating_period.processed {
+        return Err(ApiError::invalid_match(
+            "Cannot dry-run a match in a rating period which was already processed.",
+        ));
+    }
+
+    let a_match = create_match(
+        &mut database_connection,
+        &player_a,
+        &player_b,
+        &schema,
+        &current_rating_period,
+        now,
+    )
+    .await?;
 
     // Compute live ratings
-    let season_completion = current_rating_period.completion();
+    let season_completion = current_rating_period.completion(clock.as_ref());
 
     let mut player_a_matches = database_connection
         .get_player_matches_for_season(player_a.id, current_rating_period.id)
@@ -209,8 +308,16 @@ pub async fn add_match_dummy(
 
     let math_started = std::time::Instant::now();
 
-    player_a.rate_player_for_elapsed_periods(player_a_matches, season_completion);
-    player_b.rate_player_for_elapsed_periods(player_b_matches, season_completion);
+    match rating_mode() {
+        RatingMode::Instant => {
+            player_a.rate_player_instantly(&a_match, &player_a_matches, &current_rating_period);
+            player_b.rate_player_instantly(&a_match, &player_b_matches, &current_rating_period);
+        }
+        RatingMode::Periodic => {
+            player_a.rate_player_for_elapsed_periods(player_a_matches, season_completion);
+            player_b.rate_player_for_elapsed_periods(player_b_matches, season_completion);
+        }
+    }
 
     let math_elapsed = math_started.elapsed();
     let elapsed = started.elapsed();
@@ -224,7 +331,382 @@ pub async fn add_match_dummy(
         live_a: player_a,
         live_b: player_b,
         created: a_match,
+        review: None,
+        pairing: None,
+    };
+
+    Ok(Json(return_schema))
+}
+
+#[openapi(ignore = "db", tag = "Matches")]
+#[post("/api/matches/bulk", data = "<schema>")]
+#[allow(unused)]
+/// Adds several matches at once, each to the latest rating period or the one
+/// containing its played_at.
+///
+/// Requires authorization.
+///
+/// Every match is validated first; the matches are only added if all of them are valid, in a
+/// single transaction.
+///
+/// Has a special return type which includes the created matches
+/// along with the new live ratings of every player involved.
+///
+/// If any match was played in a rating period that has already been processed, that period
+/// and all later ones are processed again to include it.
+///
+/// Matches which look like duplicates of earlier ones are queued for review by an admin, like
+/// with adding a single match.
+///
+/// Returns an error with code 7 if any of the matches are invalid. Its details contain an error
+/// for each invalid match, with the match's index as the location:
+/// - code 0 (Not Found) if either one of the two players don't exist
+/// - code 5 if player_a is player_b
+/// - code 14 if either player is suspended or banned
+/// - code 6 if played_at is invalid
+/// - code 8 if a field is out of bounds; here the location also includes the field, e.g. 3.score_a
+pub async fn add_matches_bulk(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    db_pool: &State<MysqlDb>,
+    clock: &State<SharedClock>,
+    api_key: ApiKey,
+    schema: HashedJson<Vec<AddMatchSchema>>,
+) -> Result<Json<AddMatchesReturnSchema>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let started = std::time::Instant::now();
+
+    let now = clock.now();
+
+    let mut errors = Vec::new();
+    let mut players: Vec<Player> = Vec::new();
+    let mut matches = Vec::new();
+
+    // The first processed rating period we're adding matches to, if any
+    let mut first_processed_period: Option<u64> = None;
+
+    for (index, match_schema) in schema.iter().enumerate() {
+        let violations = match_schema.violations(now);
+
+        if !violations.is_empty() {
+            for mut violation in violations {
+                violation.location = format!("{}.{}", index, violation.location);
+                errors.push(violation);
+            }
+            continue;
+        }
+
+        let player_a_res = database_connection
+            .get_player_by_id_or_name(&match_schema.player_a)
+            .await;
+        let player_b_res = database_connection
+            .get_player_by_id_or_name(&match_schema.player_b)
+            .await;
+
+        let (player_a, player_b) = match (player_a_res, player_b_res) {
+            (Some(player_a), Some(player_b)) => (player_a, player_b),
+            _ => {
+                errors.push(ApiErrorDetail::from_error(
+                    index.to_string(),
+                    &ApiError::from_status(Status::NotFound),
+                ));
+                continue;
+            }
+        };
+
+        if player_a.id == player_b.id {
+            errors.push(ApiErrorDetail::from_error(
+                index.to_string(),
+                &ApiError::match_player_a_is_player_b(),
+            ));
+            continue;
+        }
+
+        if let Err(e) = check_players_active(&player_a, &player_b, now) {
+            errors.push(ApiErrorDetail::from_error(index.to_string(), &e));
+            continue;
+        }
+
+        let rating_period_res =
+            get_rating_period_for_match(&mut database_connection, match_schema.played_at, now)
+                .await;
+
+        let rating_period = match rating_period_res {
+            Ok(rating_period) => rating_period,
+            Err(e) => {
+                errors.push(ApiErrorDetail::from_error(index.to_string(), &e));
+                continue;
+            }
+        };
+
+        let match_res = create_match(
+            &mut database_connection,
+            &player_a,
+            &player_b,
+            match_schema,
+            &rating_period,
+            now,
+        )
+        .await;
+
+        match match_res {
+            Ok(a_match) => matches.push(a_match),
+            Err(e) => {
+                errors.push(ApiErrorDetail::from_error(index.to_string(), &e));
+                continue;
+            }
+        }
+
+        if rating_period.processed {
+            first_processed_period = Some(
+                first_processed_period
+                    .unwrap_or(rating_period.id)
+                    .min(rating_period.id),
+            );
+        }
+
+        for player in [player_a, player_b] {
+            if !players.iter().any(|x| x.id == player.id) {
+                players.push(player);
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        log::warn!(
+            "Tried to submit {} matches in bulk, {} were invalid",
+            schema.len(),
+            errors.len()
+        );
+        return Err(ApiError::bulk_request_invalid(errors));
+    }
+
+    // A player may have been merged into another since we fetched them
+    let reviews = database_connection
+        .add_matches(&mut matches, now)
+        .await
+        .map_err(|e| {
+            ApiError::from_constraint_violation(&e, ApiError::from_status(Status::NotFound))
+        })?;
+
+    invalidate_live_ratings(&players.iter().map(|x| x.id).collect::<Vec<u64>>()).await;
+
+    // With instant ratings, the matches are rated in order from the earliest rating period
+    let first_period_to_reprocess = match rating_mode() {
+        RatingMode::Instant => matches.iter().map(|x| x.rating_period).min(),
+        RatingMode::Periodic => first_processed_period,
+    };
+
+    if let Some(first_period_to_reprocess) = first_period_to_reprocess {
+        info!(
+            "Bulk matches were played in processed seasons or are rated instantly, reprocessing from season {}",
+            first_period_to_reprocess
+        );
+
+        reprocess_seasons_from(db_pool, first_period_to_reprocess).await;
+
+        // Their stored ratings may have changed, along with the ratings saved in the matches
+        for player in &mut players {
+            *player = database_connection
+                .get_player_by_id(player.id)
+                .await
+                .unwrap();
+        }
+
+        for a_match in &mut matches {
+            *a_match = database_connection
+                .get_match_by_id(a_match.id)
+                .await
+                .unwrap();
+        }
+    }
+
+    let current_rating_period = database_connection
+        .get_latest_active_season(now)
+        .await
+        .unwrap();
+
+    // Compute live ratings
+    let season_completion = current_rating_period.completion(clock.as_ref());
+
+    let season_matches = database_connection
+        .get_matches_for_season(current_rating_period.id)
+        .await;
+
+    let math_started = std::time::Instant::now();
+
+    // With instant ratings, their stored ratings are the live ones
+    if rating_mode() == RatingMode::Periodic {
+        for player in &mut players {
+            let player_matches = season_matches
+                .iter()
+                .filter(|a_match| a_match.player_a == player.id || a_match.player_b == player.id)
+                .cloned()
+                .collect::<Vec<Match>>();
+
+            player.rate_player_for_elapsed_periods(player_matches, season_completion);
+        }
+    }
+
+    let math_elapsed = math_started.elapsed();
+    let elapsed = started.elapsed();
+
+    info!(
+        "POST /matches/bulk took {:?}, {:?} of that was math ({} matches)",
+        elapsed,
+        math_elapsed,
+        matches.len()
+    );
+
+    let return_schema = AddMatchesReturnSchema {
+        created: matches,
+        live: players,
+        reviews,
     };
 
     Ok(Json(return_schema))
 }
+
+/// Makes sure neither player is suspended or banned
+fn check_players_active(
+    player_a: &Player,
+    player_b: &Player,
+    now: DateTime<Utc>,
+) -> Result<(), ApiError> {
+    for player in [player_a, player_b] {
+        if !player.is_active(now) {
+            log::warn!(
+                "Tried to submit a match with {} player {}",
+                player.status.as_str(),
+                player.name
+            );
+            return Err(ApiError::player_not_active(&player.name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the rating period a match played at played_at belongs to.
+///
+/// If played_at is None, the match was played now, in the latest active rating period.
+async fn get_rating_period_for_match(
+    database_connection: &mut DbConnection,
+    played_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<Season, ApiError> {
+    let Some(played_at) = played_at else {
+        return Ok(database_connection
+            .get_latest_active_season(now)
+            .await
+            .unwrap());
+    };
+
+    if played_at > now {
+        log::warn!(
+            "Tried to submit a match played in the future: {}",
+            played_at
+        );
+        return Err(ApiError::invalid_match(
+            "A match cannot be played in the future.",
+        ));
+    }
+
+    match database_connection.get_season_at(played_at).await {
+        Some(season) => Ok(season),
+        None => Err(ApiError::invalid_match(
+            "No rating period contains the time the match was played at.",
+        )),
+    }
+}
+
+/// Creates (but does not add) a match between the two players in the given rating period,
+/// using their ratings from the start of it.
+async fn create_match(
+    database_connection: &mut DbConnection,
+    player_a: &Player,
+    player_b: &Player,
+    schema: &AddMatchSchema,
+    rating_period: &Season,
+    now: DateTime<Utc>,
+) -> Result<Match, ApiError> {
+    let player_a_then = database_connection
+        .get_player_at_season_start(player_a, rating_period)
+        .await;
+    let player_b_then = database_connection
+        .get_player_at_season_start(player_b, rating_period)
+        .await;
+
+    let (Some(player_a_then), Some(player_b_then)) = (player_a_then, player_b_then) else {
+        log::warn!(
+            "Tried to submit a match in season {}, but we have no ratings for {} or {} from then",
+            rating_period.id,
+            player_a.name,
+            player_b.name
+        );
+        return Err(ApiError::invalid_match(
+            "We do not have both players' ratings from the rating period the match was played in.",
+        ));
+    };
+
+    Ok(Match {
+        id: 0,
+        rating_period: rating_period.id,
+        player_a: player_a.id,
+        player_b: player_b.id,
+        rating_a: player_a_then.rating,
+        rating_b: player_b_then.rating,
+        deviation_a: player_a_then.deviation,
+        deviation_b: player_b_then.deviation,
+        volatility_a: player_a_then.volatility,
+        volatility_b: player_b_then.volatility,
+        ping_a: schema.ping_a,
+        ping_b: schema.ping_b,
+        score_a: schema.score_a,
+        score_b: schema.score_b,
+        epoch: schema.played_at.unwrap_or(now),
+    })
+}
+
+/// Commits both players' new ratings right after their match was added to the active rating
+/// period, for [RatingMode::Instant].
+///
+/// If either player already has a later match in the rating period, it is rated again from its
+/// start instead, so the matches are rated in the order they were played.
+async fn rate_match_instantly(
+    database_connection: &mut DbConnection,
+    db_pool: &MysqlDb,
+    a_match: &mut Match,
+    players: [&mut Player; 2],
+    rating_period: &Season,
+) {
+    if let Some(rated_players) =
+        season_handler::rate_match_instantly(db_pool, a_match, rating_period).await
+    {
+        for (player, rated) in players.into_iter().zip(rated_players) {
+            *player = rated;
+        }
+
+        return;
+    }
+
+    info!(
+        "Match {} was played before later matches of its players, reprocessing season {}",
+        a_match.id, rating_period.id
+    );
+
+    reprocess_seasons_from(db_pool, rating_period.id).await;
+
+    for player in players {
+        *player = database_connection
+            .get_player_by_id(player.id)
+            .await
+            .unwrap();
+    }
+
+    *a_match = database_connection
+        .get_match_by_id(a_match.id)
+        .await
+        .unwrap();
+}
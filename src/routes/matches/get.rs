@@ -3,22 +3,35 @@ use rocket_db_pools::Connection;
 use rocket_okapi::openapi;
 
 use crate::{
-    database::{query::QueryParameters, DbConnection},
+    database::{
+        query::{epoch_cursor, Keyset, QueryParameters},
+        DbConnection,
+    },
     request_guards::chrono::chrono_timestamp_from_string,
     response::ApiError,
-    types::entities::r#match::Match,
+    types::{
+        entities::{ladder::Ladder, r#match::Match},
+        schema::page::PageSchema,
+    },
     MysqlDb,
 };
 
 #[openapi(ignore = "db", tag = "Matches")]
-#[get("/api/matches?<after>&<before>&<season>&<has_player>&<sort>&<limit>&<offset>")]
-/// Fetches an array of all players.
+#[get("/api/matches?<after>&<before>&<season>&<has_player>&<sort>&<limit>&<offset>&<cursor>")]
+/// Fetches a page of matches.
 ///
 /// Here ?after and ?before can be used to target when the matches were submittewere submitted (in Utc time)
 ///
 /// They can be set to either an rfc3339 (iso) timestamp or unix milliseconds
+///
+/// The response includes the total number of matches matching the filters. If ?limit is set
+/// and there are more matches, it also includes a next_cursor; pass it as ?cursor to get the
+/// next page. Matches are ordered latest first unless ?sort is set.
+///
+/// Returns an error with code 8 if the cursor is invalid or if both ?cursor and ?sort are set.
 pub async fn get_matches(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
     after: Option<String>,
     before: Option<String>,
     season: Option<u64>,
@@ -26,7 +39,18 @@ pub async fn get_matches(
     sort: Option<String>,
     limit: Option<usize>,
     offset: Option<usize>,
-) -> Json<Vec<Match>> {
+    cursor: Option<String>,
+) -> Result<Json<PageSchema<Match>>, ApiError> {
+    let keyset = match (&sort, cursor) {
+        (None, cursor) => Some(Keyset::epoch_from_cursor(cursor.as_deref())?),
+        (Some(_), None) => None,
+        (Some(_), Some(_)) => {
+            return Err(ApiError::invalid_field(
+                "cursor cannot be combined with sort.",
+            ))
+        }
+    };
+
     let mut after_chrono = None;
     let mut before_chrono = None;
 
@@ -44,14 +68,30 @@ pub async fn get_matches(
         season,
         has_player,
         sort,
-        limit,
+        // Fetch one more, to know whether there is a next page
+        limit: limit.map(|x| x + 1),
         offset,
+        keyset,
         ..Default::default()
     };
 
-    let mut database_connection = DbConnection::from_inner(db);
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let total = database_connection
+        .count_rows("SELECT COUNT(*) FROM matches", query_parameters.clone())
+        .await;
 
-    Json(database_connection.get_matches(query_parameters).await)
+    let matches = database_connection
+        .get_matches(query_parameters.clone())
+        .await;
+
+    let mut page = PageSchema::from_items(matches, limit, total, |x| epoch_cursor(x.epoch, x.id));
+
+    if query_parameters.keyset.is_none() {
+        page.next_cursor = None;
+    }
+
+    Ok(Json(page))
 }
 
 #[openapi(ignore = "db", tag = "Matches")]
@@ -59,8 +99,12 @@ pub async fn get_matches(
 /// Fetches a match via its id.
 ///
 /// If no such match is found, the [ApiError] will have code 0 and message "Not Found"
-pub async fn get_match(db: Connection<MysqlDb>, id: u64) -> Result<Json<Match>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+pub async fn get_match(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    id: u64,
+) -> Result<Json<Match>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
     let match_option = database_connection.get_match_by_id(id).await;
 
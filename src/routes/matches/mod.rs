@@ -1,2 +1,3 @@
 pub mod add;
 pub mod get;
+pub mod reviews;
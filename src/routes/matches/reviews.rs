@@ -0,0 +1,181 @@
+use log::info;
+use rocket::{get, http::Status, post, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::{season_handler::reprocess_seasons_from, DbConnection},
+    glicko::{rating_mode, RatingMode},
+    live_ratings::invalidate_live_ratings,
+    request_guards::admin_api_key::AdminApiKey,
+    response::ApiError,
+    types::entities::{
+        ladder::Ladder,
+        match_review::{MatchReview, MatchReviewStatus},
+    },
+    MysqlDb,
+};
+
+#[openapi(ignore = "db", tag = "Match reviews")]
+#[get("/api/matches/reviews?<status>")]
+#[allow(unused)]
+/// Fetches the matches which were flagged as possible duplicates, oldest first.
+///
+/// Requires an admin api key.
+///
+/// ?status can be set to pending, approved or rejected to only fetch those reviews.
+///
+/// Returns an error with code 8 if status is not one of those.
+pub async fn get_match_reviews(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    admin_key: AdminApiKey,
+    status: Option<String>,
+) -> Result<Json<Vec<MatchReview>>, ApiError> {
+    let status = match status {
+        Some(status) => Some(MatchReviewStatus::from_str(&status).ok_or(
+            ApiError::invalid_field("status must be one of pending, approved or rejected."),
+        )?),
+        None => None,
+    };
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    Ok(Json(database_connection.get_match_reviews(status).await))
+}
+
+#[openapi(ignore = "db", tag = "Match reviews")]
+#[get("/api/matches/reviews/<id>")]
+#[allow(unused)]
+/// Fetches a match review via its id.
+///
+/// Requires an admin api key.
+///
+/// If no such review is found, the [ApiError] will have code 0 and message "Not Found"
+pub async fn get_match_review(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    admin_key: AdminApiKey,
+    id: u64,
+) -> Result<Json<MatchReview>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    match database_connection.get_match_review_by_id(id).await {
+        None => Err(ApiError::from_status(Status::NotFound)),
+        Some(review) => Ok(Json(review)),
+    }
+}
+
+#[openapi(ignore = "db", tag = "Match reviews")]
+#[post("/api/matches/reviews/<id>/approve")]
+#[allow(unused)]
+/// Marks a flagged match as legitimate; it is kept as is.
+///
+/// Requires an admin api key.
+///
+/// Returns a 404 if the review does not exist.
+///
+/// Returns an error with code 12 if the review was already approved or rejected.
+pub async fn approve_match_review(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    admin_key: AdminApiKey,
+    id: u64,
+) -> Result<Json<MatchReview>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let mut review = get_pending_review(&mut database_connection, id).await?;
+
+    review.status = MatchReviewStatus::Approved;
+    review.resolved_at = Some(clock.now());
+
+    database_connection
+        .modify_match_review(&review)
+        .await
+        .unwrap();
+
+    info!("Approved match {} (review {})", review.match_id, review.id);
+
+    Ok(Json(review))
+}
+
+#[openapi(ignore = "db", tag = "Match reviews")]
+#[post("/api/matches/reviews/<id>/reject")]
+#[allow(unused)]
+/// Marks a flagged match as a duplicate and removes it.
+///
+/// Requires an admin api key.
+///
+/// If the match was in a rating period that has already been processed, or the instance rates
+/// matches instantly, that period and all later ones are processed again without it.
+///
+/// Returns a 404 if the review does not exist.
+///
+/// Returns an error with code 12 if the review was already approved or rejected.
+pub async fn reject_match_review(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    db_pool: &State<MysqlDb>,
+    clock: &State<SharedClock>,
+    admin_key: AdminApiKey,
+    id: u64,
+) -> Result<Json<MatchReview>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let mut review = get_pending_review(&mut database_connection, id).await?;
+
+    // It may have already been removed some other way
+    if let Some(a_match) = database_connection.get_match_by_id(review.match_id).await {
+        let rating_period = database_connection
+            .get_season_by_id(a_match.rating_period)
+            .await
+            .unwrap();
+
+        database_connection.remove_match(a_match.id).await.unwrap();
+
+        invalidate_live_ratings(&[a_match.player_a, a_match.player_b]).await;
+
+        // Instantly rated matches are already part of the stored ratings
+        if rating_period.processed || rating_mode() == RatingMode::Instant {
+            info!(
+                "Removed match {} from rated season {}, reprocessing",
+                a_match.id, rating_period.id
+            );
+
+            reprocess_seasons_from(db_pool, rating_period.id).await;
+        }
+    }
+
+    review.status = MatchReviewStatus::Rejected;
+    review.resolved_at = Some(clock.now());
+
+    database_connection
+        .modify_match_review(&review)
+        .await
+        .unwrap();
+
+    info!(
+        "Rejected match {} as a duplicate of match {} (review {})",
+        review.match_id, review.duplicate_of, review.id
+    );
+
+    Ok(Json(review))
+}
+
+/// Fetches a review which has not been approved or rejected yet
+async fn get_pending_review(
+    database_connection: &mut DbConnection,
+    id: u64,
+) -> Result<MatchReview, ApiError> {
+    let Some(review) = database_connection.get_match_review_by_id(id).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    if review.status != MatchReviewStatus::Pending {
+        return Err(ApiError::match_review_already_resolved());
+    }
+
+    Ok(review)
+}
@@ -0,0 +1,208 @@
+use log::info;
+use rocket::{delete, get, http::Status, post, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::DbConnection,
+    idempotency::HashedJson,
+    matchmaking::MATCHMAKING_QUEUE,
+    request_guards::api_key::ApiKey,
+    response::ApiError,
+    types::{
+        entities::{
+            ladder::Ladder,
+            matchmaking::{MatchmakingPairing, QueueEntry, MATCHMAKING_PAIRING_LINK_HOURS},
+        },
+        schema::matchmaking::{JoinQueueSchema, MatchmakingStatusSchema},
+    },
+    validation::Validate,
+    MysqlDb,
+};
+
+#[openapi(ignore = "db", tag = "Matchmaking")]
+#[post("/api/matchmaking/queue", data = "<schema>")]
+#[allow(unused)]
+/// Adds a player, found via an id or username, to the matchmaking queue.
+///
+/// Requires authorization.
+///
+/// Every few seconds, queued players are paired with an opponent by match quality; how close
+/// the match is expected to be (with ping) and how much it would tell us about both players.
+/// The longer a player waits, the worse of a match they accept. Players from other regions are
+/// only paired after waiting 5 minutes.
+///
+/// Poll GET /matchmaking/queue/{query} to find out when the player is paired. Once they play,
+/// submit the match via POST /matches as usual; it is linked to the pairing.
+///
+/// If the player is already queued, their region and ping are updated but they keep their place.
+///
+/// Returns the player's place in the queue.
+///
+/// Returns a 404 if the player does not exist.
+///
+/// Returns an error with code 14 if the player is suspended or banned.
+///
+/// Returns an error with code 8 if the ping or region is invalid.
+pub async fn join_matchmaking_queue(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    api_key: ApiKey,
+    schema: HashedJson<JoinQueueSchema>,
+) -> Result<Json<QueueEntry>, ApiError> {
+    let now = clock.now();
+
+    schema.validate(now)?;
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(player) = database_connection
+        .get_player_by_id_or_name(&schema.player)
+        .await
+    else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    if !player.is_active(now) {
+        return Err(ApiError::player_not_active(&player.name));
+    }
+
+    let region = schema.region.clone().or(player.profile.region.clone());
+    let ping = schema.ping.unwrap_or(0);
+
+    let mut queue = MATCHMAKING_QUEUE.lock().await;
+
+    if let Some(entry) = queue.iter_mut().find(|x| x.player_id == player.id) {
+        entry.region = region;
+        entry.ping = ping;
+
+        return Ok(Json(entry.clone()));
+    }
+
+    let entry = QueueEntry {
+        player_id: player.id,
+        ladder: ladder.id,
+        region,
+        ping,
+        joined_at: now,
+    };
+
+    queue.push(entry.clone());
+
+    info!(
+        "Player {} ({}) joined the matchmaking queue",
+        player.id, player.name
+    );
+
+    Ok(Json(entry))
+}
+
+#[openapi(ignore = "db", tag = "Matchmaking")]
+#[delete("/api/matchmaking/queue/<query>")]
+#[allow(unused)]
+/// Removes a player, found via an id or username, from the matchmaking queue.
+///
+/// Requires authorization.
+///
+/// Returns the player's removed place in the queue.
+///
+/// Returns a 404 if the player does not exist or isn't queued.
+pub async fn leave_matchmaking_queue(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    api_key: ApiKey,
+    query: &str,
+) -> Result<Json<QueueEntry>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let mut queue = MATCHMAKING_QUEUE.lock().await;
+
+    let Some(index) = queue.iter().position(|x| x.player_id == player.id) else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    info!(
+        "Player {} ({}) left the matchmaking queue",
+        player.id, player.name
+    );
+
+    Ok(Json(queue.remove(index)))
+}
+
+#[openapi(tag = "Matchmaking")]
+#[get("/api/matchmaking/queue")]
+/// Fetches the players of the ladder waiting in the matchmaking queue, first to join first.
+pub async fn get_matchmaking_queue(ladder: Ladder) -> Json<Vec<QueueEntry>> {
+    let queue = MATCHMAKING_QUEUE.lock().await;
+
+    Json(
+        queue
+            .iter()
+            .filter(|x| x.ladder == ladder.id)
+            .cloned()
+            .collect(),
+    )
+}
+
+#[openapi(ignore = "db", tag = "Matchmaking")]
+#[get("/api/matchmaking/queue/<query>")]
+/// Fetches whether a player, found via an id or username, is waiting in the matchmaking queue
+/// and who they were paired with.
+///
+/// Poll this after joining the queue; once the player is paired, they are no longer queued and
+/// their pairing is returned.
+///
+/// Returns a 404 if the player does not exist.
+pub async fn get_matchmaking_status(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    query: &str,
+) -> Result<Json<MatchmakingStatusSchema>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let queued = MATCHMAKING_QUEUE
+        .lock()
+        .await
+        .iter()
+        .find(|x| x.player_id == player.id)
+        .cloned();
+
+    let since = clock.now() - chrono::TimeDelta::hours(MATCHMAKING_PAIRING_LINK_HOURS);
+
+    let pairing = database_connection
+        .get_open_matchmaking_pairing(player.id, since)
+        .await;
+
+    Ok(Json(MatchmakingStatusSchema { queued, pairing }))
+}
+
+#[openapi(ignore = "db", tag = "Matchmaking")]
+#[get("/api/matchmaking/pairings/<id>")]
+/// Fetches a matchmaking pairing via its id.
+///
+/// Once the players submit their match, it includes the match's id.
+///
+/// Returns a 404 if the pairing does not exist.
+pub async fn get_matchmaking_pairing(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    id: u64,
+) -> Result<Json<MatchmakingPairing>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    match database_connection.get_matchmaking_pairing_by_id(id).await {
+        None => Err(ApiError::from_status(Status::NotFound)),
+        Some(pairing) => Ok(Json(pairing)),
+    }
+}
@@ -3,7 +3,10 @@
 use serde::{Deserialize, Serialize};
 
 pub mod catchers;
+pub mod ladders;
+pub mod leaderboard;
 pub mod matches;
+pub mod matchmaking;
 pub mod players;
 pub mod system;
 
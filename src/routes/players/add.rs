@@ -1,14 +1,22 @@
-use regex::Regex;
-use rocket::{post, serde::json::Json};
+use rocket::{post, serde::json::Json, State};
 use rocket_db_pools::Connection;
 use rocket_okapi::openapi;
 
 use crate::{
+    clock::SharedClock,
     database::DbConnection,
-    glicko::{default_deviation, default_rating, default_volatility},
+    idempotency::HashedJson,
     request_guards::api_key::ApiKey,
     response::ApiError,
-    types::{entities::player::Player, schema::player::AddPlayerSchema},
+    types::{
+        entities::{
+            ladder::Ladder,
+            player::{Player, PlayerProfile, PlayerStatus},
+            rating_snapshot::RatingSnapshot,
+        },
+        schema::player::AddPlayerSchema,
+    },
+    validation::Validate,
     MysqlDb,
 };
 
@@ -23,18 +31,29 @@ use crate::{
 ///
 /// Returns an error with code 3 if the username is already taken.
 ///
-/// Returns an error with code 4 if the username is invalid.
+/// Returns an error with code 8 if the request is invalid. Its details list every problem:
+/// - code 4 if the username is invalid. This can happen for one of two reasons:
+///   - the username does not match warframe's username system (regex: ^[A-Za-z0-9_.-]{2,24}(#\d{3})?$ )
+///   - the username is a valid u64 id. These are not allowed since some endpoints accept either an
+///   id or username
+/// - code 8 if the rating, deviation or volatility is out of bounds, or a profile field is invalid
 ///
-/// This can happen for one of two reasons:
-/// - the username does not match warframe's username system (regex: [A-Za-z0-9_.-]{2,24}(#\d{3})? )
-/// - the username is a valid u64 id. These are not allowed since some endpoints accept either an
-/// id or username
+/// Supports the Idempotency-Key header; retrying with the same key returns the original
+/// response instead of adding the player again. Returns an error with code 9 if the key was used for a
+/// different request and code 10 if the original request is still being processed.
 pub async fn add_player(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
     api_key: ApiKey,
-    schema: Json<AddPlayerSchema>,
+    schema: HashedJson<AddPlayerSchema>,
 ) -> Result<Json<Player>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+    if let Err(e) = schema.validate(clock.now()) {
+        log::warn!("Tried to add invalid player {}: {}", schema.name, e);
+        return Err(e);
+    }
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
     let existing_player_option = database_connection.get_player_by_name(&schema.name).await;
 
@@ -45,37 +64,48 @@ pub async fn add_player(
         }
     }
 
-    if schema.name.parse::<u64>().is_ok() {
-        log::warn!("Tried to add player with numeric username: {}", schema.name);
-        return Err(ApiError::invalid_username("Username cannot be a valid id."));
-    }
-
-    // Validate username
-    if !Regex::new(r#"[A-Za-z0-9_.-]{2,24}(#\d{3})?"#)
-        .unwrap()
-        .is_match(&schema.name)
-    {
-        log::warn!(
-            "Tried to add player with username that doesn't match regex: {}",
-            schema.name
-        );
-        return Err(ApiError::invalid_username(
-            "Username is not a valid warframe username.",
-        ));
-    }
+    let now = clock.now();
 
     let mut player = Player {
         id: 0,
         name: schema.name.clone(),
-        rating: schema.rating.unwrap_or(default_rating()),
-        deviation: schema.deviation.unwrap_or(default_deviation()),
-        volatility: schema.deviation.unwrap_or(default_volatility()),
+        rating: schema.rating.unwrap_or(ladder.default_rating),
+        deviation: schema.deviation.unwrap_or(ladder.default_deviation),
+        volatility: schema.volatility.unwrap_or(ladder.default_volatility),
+        status: PlayerStatus::Active,
+        status_reason: None,
+        status_since: None,
+        status_expires: None,
+        profile: PlayerProfile {
+            joined_at: Some(schema.profile.joined_at.unwrap_or(now)),
+            ..schema.profile.clone()
+        },
     };
 
-    let result = database_connection.add_player(&player).await.unwrap();
+    // Someone may have taken the name since we checked
+    let result = database_connection
+        .add_player(&player, now)
+        .await
+        .map_err(|e| ApiError::from_constraint_violation(&e, ApiError::username_already_taken()))?;
 
     // Return the id of the player we added
     player.id = result.last_insert_id();
 
+    // Their rating at the start of the current season is the one they joined with
+    if let Some(active_season) = database_connection.get_latest_active_season(now).await {
+        let snapshot = RatingSnapshot {
+            player_id: player.id,
+            rating_period: active_season.id,
+            rating: player.rating,
+            deviation: player.deviation,
+            volatility: player.volatility,
+        };
+
+        database_connection
+            .add_rating_snapshot(&snapshot)
+            .await
+            .unwrap();
+    }
+
     Ok(Json(player))
 }
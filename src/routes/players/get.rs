@@ -1,32 +1,91 @@
+use chrono::{DateTime, Utc};
 use log::info;
-use rocket::{get, http::Status, serde::json::Json};
+use rocket::{get, http::Status, serde::json::Json, State};
 use rocket_db_pools::Connection;
 use rocket_okapi::openapi;
 
 use crate::{
-    database::{query::QueryParameters, DbConnection},
+    clock::{Clock, SharedClock},
+    database::{
+        query::{id_cursor, platform_from_parameter, Keyset, QueryParameters},
+        DbConnection,
+    },
+    glicko::{player_at_start_of_period, rate_matches_instantly, rating_mode, RatingMode},
+    live_ratings::apply_live_ratings,
+    request_guards::chrono::chrono_timestamp_from_string,
     response::ApiError,
-    types::entities::{player::Player, r#match::Match},
+    routes::leaderboard::get::{rank_player, rank_player_by_stored_ratings},
+    search::{username_similarity, MIN_FUZZY_SEARCH_SCORE},
+    types::{
+        entities::{ladder::Ladder, player::Player, r#match::Match, season::Season},
+        schema::{
+            page::PageSchema,
+            player::{PlayerSearchResultSchema, RankedPlayerSchema},
+        },
+    },
     MysqlDb,
 };
 
 #[openapi(ignore = "db", tag = "Players")]
-#[get("/api/players?<max_rating>&<min_rating>&<max_deviation>&<min_deviation>&<max_volatility>&<min_volatility>&<sort>&<limit>&<offset>")]
-/// Fetches an array of all players.
+#[get("/api/players?<max_rating>&<min_rating>&<max_deviation>&<min_deviation>&<max_volatility>&<min_volatility>&<include_inactive>&<platform>&<region>&<clan>&<as_of>&<sort>&<limit>&<offset>&<cursor>")]
+/// Fetches a page of players.
+///
+/// Returns their current rating; unless the instance rates matches instantly, it does not include
+/// performance from the latest season
+///
+/// Suspended and banned players are left out, unless ?include_inactive is true.
+///
+/// Players can be filtered by their profile with ?platform (pc, playstation, xbox or switch),
+/// ?region and ?clan.
+///
+/// The response includes the total number of players matching the filters. If ?limit is set
+/// and there are more players, it also includes a next_cursor; pass it as ?cursor to get the
+/// next page. Players are ordered by id unless ?sort is set.
+///
+/// If ?as_of (rfc3339 or unix milliseconds) is set, returns ratings as they stood at that
+/// moment instead, including performance in the rating period until then. Players who weren't
+/// rated yet are left out. Use ?offset instead of ?cursor to page through these.
 ///
-/// Returns their current rating; does not include performance from the latest season
+/// Returns an error with code 8 if the cursor, platform or as_of is invalid, if both ?cursor
+/// and ?sort or ?as_of are set, or if no rating period contains as_of.
 pub async fn get_players(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
     max_rating: Option<f64>,
     min_rating: Option<f64>,
     max_deviation: Option<f64>,
     min_deviation: Option<f64>,
     max_volatility: Option<f64>,
     min_volatility: Option<f64>,
+    include_inactive: Option<bool>,
+    platform: Option<String>,
+    region: Option<String>,
+    clan: Option<String>,
+    as_of: Option<String>,
     sort: Option<String>,
     limit: Option<usize>,
     offset: Option<usize>,
-) -> Json<Vec<Player>> {
+    cursor: Option<String>,
+) -> Result<Json<PageSchema<Player>>, ApiError> {
+    let as_of = as_of_from_parameter(as_of.as_deref())?;
+
+    if as_of.is_some() && cursor.is_some() {
+        return Err(ApiError::invalid_field(
+            "cursor cannot be combined with as_of.",
+        ));
+    }
+
+    let keyset = match (&sort, cursor) {
+        (None, cursor) => Some(Keyset::id_from_cursor(cursor.as_deref())?),
+        (Some(_), None) => None,
+        (Some(_), Some(_)) => {
+            return Err(ApiError::invalid_field(
+                "cursor cannot be combined with sort.",
+            ))
+        }
+    };
+
     let query_parameters = QueryParameters {
         max_rating,
         min_rating,
@@ -34,25 +93,70 @@ pub async fn get_players(
         min_deviation,
         max_volatility,
         min_volatility,
+        active_at: (!include_inactive.unwrap_or(false)).then(|| clock.now()),
+        platform: platform_from_parameter(platform.as_deref())?,
+        region,
+        clan,
         sort,
-        limit,
+        // Fetch one more, to know whether there is a next page
+        limit: limit.map(|x| x + 1),
         offset,
+        keyset,
         ..Default::default()
     };
 
-    let mut database_connection = DbConnection::from_inner(db);
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    if let Some(as_of) = as_of {
+        let players =
+            get_all_players_as_of(&mut database_connection, as_of, clock.as_ref()).await?;
+
+        let unpaged_parameters = QueryParameters {
+            limit: None,
+            offset: None,
+            ..query_parameters.clone()
+        };
+
+        let total = unpaged_parameters
+            .apply_to_players_vec(players.clone())
+            .len() as u64;
+
+        let players = query_parameters.apply_to_players_vec(players);
+
+        let mut page = PageSchema::from_items(players, limit, total, |x| id_cursor(x.id));
+        page.next_cursor = None;
+
+        return Ok(Json(page));
+    }
+
+    let total = database_connection
+        .count_rows("SELECT COUNT(*) FROM players", query_parameters.clone())
+        .await;
+
+    let players = database_connection
+        .get_players(query_parameters.clone())
+        .await;
+
+    let mut page = PageSchema::from_items(players, limit, total, |x| id_cursor(x.id));
 
-    Json(database_connection.get_players(query_parameters).await)
+    if query_parameters.keyset.is_none() {
+        page.next_cursor = None;
+    }
+
+    Ok(Json(page))
 }
 
 #[openapi(ignore = "db", tag = "Players")]
-#[get("/api/players/search?<username>&<max_rating>&<min_rating>&<max_deviation>&<min_deviation>&<max_volatility>&<min_volatility>&<sort>&<limit>&<offset>")]
+#[get("/api/players/search?<username>&<max_rating>&<min_rating>&<max_deviation>&<min_deviation>&<max_volatility>&<min_volatility>&<platform>&<region>&<clan>&<sort>&<limit>&<offset>")]
 /// Searches for players with a similar username to the ?username query parameter.
 ///
 /// Functionally works similar to GET /players/. All query parameters from that endpoint are
 /// supported and the return type is the same.
+///
+/// Returns an error with code 8 if the platform is invalid.
 pub async fn search_players(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
     username: String,
     max_rating: Option<f64>,
     min_rating: Option<f64>,
@@ -60,10 +164,13 @@ pub async fn search_players(
     min_deviation: Option<f64>,
     max_volatility: Option<f64>,
     min_volatility: Option<f64>,
+    platform: Option<String>,
+    region: Option<String>,
+    clan: Option<String>,
     sort: Option<String>,
     limit: Option<usize>,
     offset: Option<usize>,
-) -> Json<Vec<Player>> {
+) -> Result<Json<Vec<Player>>, ApiError> {
     let query_parameters = QueryParameters {
         max_rating,
         min_rating,
@@ -71,62 +178,204 @@ pub async fn search_players(
         min_deviation,
         max_volatility,
         min_volatility,
+        platform: platform_from_parameter(platform.as_deref())?,
+        region,
+        clan,
         sort,
         limit,
         offset,
         ..Default::default()
     };
 
-    let mut database_connection = DbConnection::from_inner(db);
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
-    Json(
+    Ok(Json(
         database_connection
             .search_players(&username, query_parameters)
             .await,
-    )
+    ))
+}
+
+#[openapi(ignore = "db", tag = "Players")]
+#[get("/api/players/search/fuzzy?<username>&<max_rating>&<min_rating>&<max_deviation>&<min_deviation>&<max_volatility>&<min_volatility>&<platform>&<region>&<clan>&<limit>&<offset>")]
+/// Searches for players with a username or old name similar to the ?username query parameter,
+/// allowing for typos.
+///
+/// Casing and #123 discriminators are ignored. Results are ordered by relevance, best first, and
+/// include a score from 0 to 1. Players with a score below 0.5 are left out.
+///
+/// Supports the filters, ?limit and ?offset of GET /players/search, but not ?sort.
+///
+/// Returns an error with code 8 if the platform is invalid.
+pub async fn fuzzy_search_players(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    username: String,
+    max_rating: Option<f64>,
+    min_rating: Option<f64>,
+    max_deviation: Option<f64>,
+    min_deviation: Option<f64>,
+    max_volatility: Option<f64>,
+    min_volatility: Option<f64>,
+    platform: Option<String>,
+    region: Option<String>,
+    clan: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Json<Vec<PlayerSearchResultSchema>>, ApiError> {
+    let platform = platform_from_parameter(platform.as_deref())?;
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    // Current and old names which could be similar enough
+    let candidates = database_connection
+        .get_fuzzy_search_candidates(&username)
+        .await;
+
+    let mut player_ids = candidates.iter().map(|x| x.0).collect::<Vec<u64>>();
+    player_ids.sort();
+    player_ids.dedup();
+
+    let query_parameters = QueryParameters {
+        max_rating,
+        min_rating,
+        max_deviation,
+        min_deviation,
+        max_volatility,
+        min_volatility,
+        platform,
+        region,
+        clan,
+        player_ids: Some(player_ids),
+        ..Default::default()
+    };
+
+    let players = database_connection.get_players(query_parameters).await;
+
+    let mut results = Vec::new();
+
+    for player in players {
+        let mut best_name = player.name.clone();
+        let mut best_score = username_similarity(&username, &player.name);
+
+        // Includes their current name if it was a candidate, which stays preferred on ties
+        for (_, name) in candidates.iter().filter(|x| x.0 == player.id) {
+            let score = username_similarity(&username, name);
+
+            if score > best_score {
+                best_score = score;
+                best_name = name.clone();
+            }
+        }
+
+        if best_score < MIN_FUZZY_SEARCH_SCORE {
+            continue;
+        }
+
+        results.push(PlayerSearchResultSchema {
+            player,
+            score: best_score,
+            matched_name: best_name,
+        });
+    }
+
+    results.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.player.id.cmp(&b.player.id))
+    });
+
+    let results = results
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(Json(results))
 }
 
 #[openapi(ignore = "db", tag = "Players")]
-#[get("/api/players/<query>")]
+#[get("/api/players/<query>?<as_of>")]
 /// Fetches a player via an id or username.
 ///
-/// Returns their current rating; does not include performance from the latest season.
+/// Returns their current rating; unless the instance rates matches instantly, it does not include
+/// performance from the latest season.
+///
+/// Also returns where they place among all ranked players. Suspended and banned players are
+/// not ranked.
+///
+/// If ?as_of (rfc3339 or unix milliseconds) is set, returns their rating and rank as they
+/// stood at that moment instead, including performance in the rating period until then.
 ///
 /// If the query is a valid id, it will take precedence over the uesrname.
 ///
 /// (This is why usernames shouldn't be valid ids)
 ///
-/// If no such player is found, the ApiError will have code 0 and message "Not Found"
-pub async fn get_player(db: Connection<MysqlDb>, query: &str) -> Result<Json<Player>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+/// If no such player is found, or they weren't rated yet at as_of, the ApiError will have
+/// code 0 and message "Not Found"
+///
+/// Returns an error with code 8 if as_of is invalid, in the future or no rating period
+/// contains it.
+pub async fn get_player(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    query: &str,
+    as_of: Option<String>,
+) -> Result<Json<RankedPlayerSchema>, ApiError> {
+    let as_of = as_of_from_parameter(as_of.as_deref())?;
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let Some(as_of) = as_of else {
+        return Ok(Json(
+            rank_player_by_stored_ratings(&mut database_connection, player, clock.now()).await,
+        ));
+    };
 
-    let player_option = database_connection.get_player_by_id_or_name(query).await;
+    let players = get_all_players_as_of(&mut database_connection, as_of, clock.as_ref()).await?;
 
-    match player_option {
+    match rank_player(player.id, players, as_of) {
         None => Err(ApiError::from_status(Status::NotFound)),
-        Some(player) => Ok(Json(player)),
+        Some(ranked) => Ok(Json(ranked)),
     }
 }
 
 #[openapi(ignore = "db", tag = "Players")]
-#[get("/api/players/live?<max_rating>&<min_rating>&<max_deviation>&<min_deviation>&<max_volatility>&<min_volatility>&<sort>&<limit>&<offset>")]
+#[get("/api/players/live?<max_rating>&<min_rating>&<max_deviation>&<min_deviation>&<max_volatility>&<min_volatility>&<include_inactive>&<platform>&<region>&<clan>&<sort>&<limit>&<offset>")]
 /// Fetches an array of all players.
 ///
-/// Returns their new live rating, if the season hypothetically ended right now.
+/// Returns their new live rating, if the season hypothetically ended right now. The ratings of
+/// suspended and banned players do not change.
+///
+/// What players' matches of the rating period add up to is cached until one of their matches
+/// changes; the growth of their deviation is applied on every request.
 ///
 /// (It is otherwise the same as GET /players)
+///
+/// Returns an error with code 8 if the platform is invalid.
 pub async fn get_players_live(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
     max_rating: Option<f64>,
     min_rating: Option<f64>,
     max_deviation: Option<f64>,
     min_deviation: Option<f64>,
     max_volatility: Option<f64>,
     min_volatility: Option<f64>,
+    include_inactive: Option<bool>,
+    platform: Option<String>,
+    region: Option<String>,
+    clan: Option<String>,
     sort: Option<String>,
     limit: Option<usize>,
     offset: Option<usize>,
-) -> Json<Vec<Player>> {
+) -> Result<Json<Vec<Player>>, ApiError> {
     let query_parameters = QueryParameters {
         max_rating,
         min_rating,
@@ -134,42 +383,21 @@ pub async fn get_players_live(
         min_deviation,
         max_volatility,
         min_volatility,
+        active_at: (!include_inactive.unwrap_or(false)).then(|| clock.now()),
+        platform: platform_from_parameter(platform.as_deref())?,
+        region,
+        clan,
         sort,
         limit,
         offset,
         ..Default::default()
     };
 
-    let mut database_connection = DbConnection::from_inner(db);
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
     let started = std::time::Instant::now();
 
-    let active_season_res = database_connection.get_latest_active_season().await;
-    if active_season_res.is_none() {
-        return Json(database_connection.get_players(query_parameters).await);
-    }
-
-    let active_season = active_season_res.unwrap();
-
-    let season_completion = active_season.completion();
-
-    let season_matches = database_connection
-        .get_matches_for_season(active_season.id)
-        .await;
-
-    let mut players = database_connection
-        .get_players(QueryParameters::default())
-        .await;
-
-    for player in &mut players {
-        let player_matches = season_matches
-            .iter()
-            .filter(|a_match| a_match.player_a == player.id || a_match.player_b == player.id)
-            .cloned()
-            .collect::<Vec<Match>>();
-
-        player.rate_player_for_elapsed_periods(player_matches, season_completion);
-    }
+    let players = get_all_players_live(&mut database_connection, clock.as_ref()).await;
 
     let elapsed_math = started.elapsed();
 
@@ -182,57 +410,225 @@ pub async fn get_players_live(
         elapsed, elapsed_math
     );
 
-    Json(sorted)
+    Ok(Json(sorted))
 }
 
 #[openapi(ignore = "db", tag = "Players")]
 #[get("/api/players/<query>/live")]
 /// Fetches a player via an id or username.
 ///
-/// Returns their new live rating, if the season hypothetically ended right now.
+/// Returns their new live rating, if the season hypothetically ended right now. The rating of
+/// a suspended or banned player does not change.
+///
+/// What the player's matches of the rating period add up to is cached until one of them
+/// changes; the growth of their deviation is applied on every request.
+///
+/// Their live rating is ranked among the other players' live ratings, like GET
+/// /leaderboard/around/{query}?live=true.
 ///
 /// (It is otherwise the same as GET /players/{query})
 pub async fn get_player_live(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
     query: &str,
-) -> Result<Json<Player>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+) -> Result<Json<RankedPlayerSchema>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
     let started = std::time::Instant::now();
 
-    let player_option = database_connection.get_player_by_id_or_name(query).await;
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let players = get_all_players_live(&mut database_connection, clock.as_ref()).await;
 
-    let mut player = match player_option {
-        None => {
-            return Err(ApiError::from_status(Status::NotFound));
-        }
-        Some(player) => player,
+    let elapsed = started.elapsed();
+
+    info!("GET /players/<query>/live took {:?}", elapsed);
+
+    match rank_player(player.id, players, clock.now()) {
+        None => Err(ApiError::from_status(Status::NotFound)),
+        Some(ranked) => Ok(Json(ranked)),
+    }
+}
+
+/// Returns the player with their new live rating, if the season hypothetically ended right now
+///
+/// With [RatingMode::Instant], their stored rating already is the live one.
+pub async fn get_live_player(
+    database_connection: &mut DbConnection,
+    player: Player,
+    clock: &dyn Clock,
+) -> Player {
+    if rating_mode() == RatingMode::Instant {
+        return player;
+    }
+
+    let Some(active_season) = database_connection
+        .get_latest_active_season(clock.now())
+        .await
+    else {
+        return player;
     };
 
-    let active_season_res = database_connection.get_latest_active_season().await;
-    if active_season_res.is_none() {
-        return Ok(Json(player));
+    let mut players = [player];
+
+    apply_live_ratings(database_connection, &mut players, &active_season, clock).await;
+
+    let [player] = players;
+
+    player
+}
+
+/// Fetches every player with their new live rating, if the season hypothetically ended right now
+///
+/// The ratings of suspended and banned players do not change. With [RatingMode::Instant], the
+/// stored ratings already are the live ones; otherwise they are cached, see [apply_live_ratings].
+pub async fn get_all_players_live(
+    database_connection: &mut DbConnection,
+    clock: &dyn Clock,
+) -> Vec<Player> {
+    let mut players = database_connection
+        .get_players(QueryParameters::default())
+        .await;
+
+    if rating_mode() == RatingMode::Instant {
+        return players;
     }
 
-    let active_season = active_season_res.unwrap();
+    let Some(active_season) = database_connection
+        .get_latest_active_season(clock.now())
+        .await
+    else {
+        return players;
+    };
+
+    apply_live_ratings(database_connection, &mut players, &active_season, clock).await;
+
+    players
+}
+
+/// Fetches every player with the rating they had at the given time.
+///
+/// Starts from their ratings at the start of the rating period containing the time and rates
+/// the matches they played in it until then. Players who weren't added yet are left out, and the
+/// ratings of players who were suspended or banned at the time do not change.
+pub async fn get_all_players_as_of(
+    database_connection: &mut DbConnection,
+    as_of: DateTime<Utc>,
+    clock: &dyn Clock,
+) -> Result<Vec<Player>, ApiError> {
+    if as_of > clock.now() {
+        return Err(ApiError::invalid_field("as_of cannot be in the future."));
+    }
+
+    let Some(season) = database_connection.get_season_at(as_of).await else {
+        return Err(ApiError::invalid_field("No rating period contains as_of."));
+    };
+
+    let players = database_connection.get_players_existing_at(as_of).await;
 
-    let season_completion = active_season.completion();
+    if rating_mode() == RatingMode::Instant {
+        return Ok(
+            get_all_players_as_of_instantly(database_connection, players, &season, as_of).await,
+        );
+    }
+
+    // Ratings only change when a rating period is processed, so for the active one the stored
+    // ratings are the ones from its start
+    let mut players = match season.processed {
+        false => players,
+        true => {
+            let snapshots = database_connection
+                .get_rating_snapshots_for_season(season.id)
+                .await;
+
+            players
+                .into_iter()
+                .filter_map(|mut player| {
+                    let snapshot = snapshots.iter().find(|x| x.player_id == player.id)?;
+
+                    player.rating = snapshot.rating;
+                    player.deviation = snapshot.deviation;
+                    player.volatility = snapshot.volatility;
+
+                    Some(player)
+                })
+                .collect::<Vec<Player>>()
+        }
+    };
+
+    let season_matches = database_connection
+        .get_matches_for_season(season.id)
+        .await
+        .into_iter()
+        .filter(|x| x.epoch <= as_of)
+        .collect::<Vec<Match>>();
+
+    let season_completion = season.completion_at(as_of);
+
+    for player in &mut players {
+        // Their rating was frozen
+        if player.is_frozen_at(as_of) {
+            continue;
+        }
 
-    let matches_for_player = database_connection
-        .get_player_matches_for_season(player.id, active_season.id)
+        let player_matches = season_matches
+            .iter()
+            .filter(|a_match| a_match.player_a == player.id || a_match.player_b == player.id)
+            .cloned()
+            .collect::<Vec<Match>>();
+
+        player.rate_player_for_elapsed_periods(player_matches, season_completion);
+    }
+
+    Ok(players)
+}
+
+/// [get_all_players_as_of] for [RatingMode::Instant]; replays the rating period's matches until
+/// the given time from everyone's rating at its start.
+///
+/// Players who never played in it and had no rating snapshot for it keep their stored rating.
+async fn get_all_players_as_of_instantly(
+    database_connection: &mut DbConnection,
+    players: Vec<Player>,
+    season: &Season,
+    as_of: DateTime<Utc>,
+) -> Vec<Player> {
+    let snapshots = database_connection
+        .get_rating_snapshots_for_season(season.id)
         .await;
 
-    let math_started = std::time::Instant::now();
+    let season_matches = database_connection.get_matches_for_season(season.id).await;
 
-    player.rate_player_for_elapsed_periods(matches_for_player, season_completion);
+    let mut players = players
+        .into_iter()
+        .map(|player| {
+            player_at_start_of_period(&player, &snapshots, &season_matches).unwrap_or(player)
+        })
+        .collect::<Vec<Player>>();
 
-    let math_elapsed = math_started.elapsed();
-    let elapsed = started.elapsed();
+    let mut matches_until = season_matches
+        .into_iter()
+        .filter(|x| x.epoch <= as_of)
+        .collect::<Vec<Match>>();
 
-    info!(
-        "GET /players/<query>/live took {:?}, {:?} of that was math",
-        elapsed, math_elapsed
-    );
+    rate_matches_instantly(&mut players, &mut matches_until, season, None);
 
-    Ok(Json(player))
+    players
+}
+
+/// Parses the ?as_of url parameter
+fn as_of_from_parameter(as_of: Option<&str>) -> Result<Option<DateTime<Utc>>, ApiError> {
+    let Some(as_of) = as_of else {
+        return Ok(None);
+    };
+
+    match chrono_timestamp_from_string(as_of) {
+        Some(as_of) => Ok(Some(as_of)),
+        None => Err(ApiError::invalid_field(
+            "Invalid as_of, expected an rfc3339 timestamp or unix milliseconds.",
+        )),
+    }
 }
@@ -1,2 +1,6 @@
 pub mod add;
 pub mod get;
+pub mod modify;
+pub mod opponents;
+pub mod stats;
+pub mod status;
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use log::info;
+use rocket::{get, http::Status, patch, post, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::{season_handler::reprocess_seasons_from, DbConnection},
+    idempotency::HashedJson,
+    live_ratings::invalidate_live_ratings,
+    request_guards::{admin_api_key::AdminApiKey, api_key::ApiKey},
+    response::ApiError,
+    types::{
+        entities::{ladder::Ladder, player::Player, player_alias::PlayerAlias},
+        schema::player::ModifyPlayerSchema,
+    },
+    validation::Validate,
+    MysqlDb,
+};
+
+#[openapi(ignore = "db", tag = "Players")]
+#[patch("/api/players/<query>", data = "<schema>")]
+#[allow(unused)]
+/// Changes a player, found via an id or username.
+///
+/// Requires authorization.
+///
+/// Used to rename players and change their profile. When renamed, their old name is kept as an
+/// alias, so it can still be used to find them in other endpoints. Fields which aren't set are
+/// left as they are; profile fields set to null are cleared.
+///
+/// Returns the changed player.
+///
+/// Returns a 404 if the player does not exist.
+///
+/// Returns an error with code 3 if another player already uses the new username.
+///
+/// Returns an error with code 8 if the request is invalid. Its details list every problem:
+/// - code 4 if the new username is invalid, see POST /players
+/// - code 8 if a profile field is invalid
+pub async fn modify_player(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    api_key: ApiKey,
+    query: &str,
+    schema: HashedJson<ModifyPlayerSchema>,
+) -> Result<Json<Player>, ApiError> {
+    let now = clock.now();
+
+    schema.validate(now)?;
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(mut player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    if let Some(new_name) = &schema.name {
+        if *new_name != player.name {
+            rename_player(&mut database_connection, &mut player, new_name, now).await?;
+        }
+    }
+
+    let old_profile = player.profile.clone();
+
+    schema.apply_to_profile(&mut player.profile);
+
+    if player.profile != old_profile {
+        database_connection
+            .modify_player(&player)
+            .await
+            .map_err(|e| {
+                ApiError::from_constraint_violation(&e, ApiError::username_already_taken())
+            })?;
+    }
+
+    Ok(Json(player))
+}
+
+#[openapi(ignore = "db", tag = "Players")]
+#[get("/api/players/<query>/aliases")]
+/// Fetches the old names of a player via an id or username, latest first.
+///
+/// If no such player is found, the ApiError will have code 0 and message "Not Found"
+pub async fn get_player_aliases(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    query: &str,
+) -> Result<Json<Vec<PlayerAlias>>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    Ok(Json(
+        database_connection.get_player_aliases(player.id).await,
+    ))
+}
+
+#[openapi(ignore = "db", tag = "Players")]
+#[post("/api/players/<keep>/merge/<remove>")]
+#[allow(unused)]
+/// Merges a duplicate player into another one, both found via an id or username.
+///
+/// Requires an admin api key.
+///
+/// All of the removed player's matches and matchmaking pairings are moved to the kept player,
+/// the removed player's name becomes an alias of the kept one and the removed player is deleted.
+/// Matches and pairings of the two against each other are deleted, since a player cannot play
+/// against themselves, along with pending reviews of those matches.
+///
+/// Ratings are then processed again from the first rating period with a moved match, so the
+/// kept player's history includes the moved matches.
+///
+/// Returns the kept player, with their recomputed rating.
+///
+/// Returns a 404 if either one of the two players don't exist.
+///
+/// Returns an error with code 13 if keep and remove are the same player.
+pub async fn merge_players(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    db_pool: &State<MysqlDb>,
+    clock: &State<SharedClock>,
+    admin_key: AdminApiKey,
+    keep: &str,
+    remove: &str,
+) -> Result<Json<Player>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(kept_player) = database_connection.get_player_by_id_or_name(keep).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let Some(removed_player) = database_connection.get_player_by_id_or_name(remove).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    if kept_player.id == removed_player.id {
+        return Err(ApiError::merge_player_into_itself());
+    }
+
+    let first_season = database_connection
+        .merge_players(&kept_player, &removed_player, clock.now())
+        .await
+        .unwrap();
+
+    info!(
+        "Merged player {} ({}) into {} ({})",
+        removed_player.id, removed_player.name, kept_player.id, kept_player.name
+    );
+
+    invalidate_live_ratings(&[kept_player.id, removed_player.id]).await;
+
+    if let Some(first_season) = first_season {
+        reprocess_seasons_from(db_pool, first_season).await;
+    }
+
+    let kept_player = database_connection
+        .get_player_by_id(kept_player.id)
+        .await
+        .unwrap();
+
+    Ok(Json(kept_player))
+}
+
+/// Renames a player, keeping their current name as an alias from the given time
+async fn rename_player(
+    database_connection: &mut DbConnection,
+    player: &mut Player,
+    new_name: &str,
+    now: DateTime<Utc>,
+) -> Result<(), ApiError> {
+    if let Some(existing) = database_connection.get_player_by_name(new_name).await {
+        if existing.id != player.id {
+            return Err(ApiError::username_already_taken());
+        }
+    }
+
+    let alias = PlayerAlias {
+        id: 0,
+        player_id: player.id,
+        name: player.name.clone(),
+        epoch: now,
+    };
+
+    let renamed = Player {
+        name: new_name.to_string(),
+        ..player.clone()
+    };
+
+    // Someone may have taken the name since we checked
+    database_connection
+        .rename_player(&renamed, &alias)
+        .await
+        .map_err(|e| ApiError::from_constraint_violation(&e, ApiError::username_already_taken()))?;
+
+    info!(
+        "Renamed player {} from {} to {}",
+        player.id, player.name, new_name
+    );
+
+    *player = renamed;
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use rocket::{get, http::Status, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::{query::QueryParameters, DbConnection},
+    glicko::{
+        calculate_expected_a_score, calculate_match_fairness, calculate_match_information,
+        calculate_match_quality,
+    },
+    response::ApiError,
+    types::{entities::ladder::Ladder, schema::stats::SuggestedOpponentSchema},
+    MysqlDb,
+};
+
+/// How many opponents are suggested by default
+pub const DEFAULT_SUGGESTED_OPPONENTS: usize = 10;
+/// The most opponents which can be suggested at once
+pub const MAX_SUGGESTED_OPPONENTS: usize = 100;
+
+#[openapi(ignore = "db", tag = "Players")]
+#[get("/api/players/<query>/suggested-opponents?<ping>&<opponent_ping>&<region>&<limit>")]
+/// Suggests opponents for a player, found via an id or username, best match first.
+///
+/// Other active players are ranked by match quality: how close to even the match is expected to
+/// be (with ping) and how much it would tell us about both players' ratings.
+///
+/// ?ping and ?opponent_ping set the pings the player and their opponent are expected to play
+/// with; 0 by default. ?region only suggests players from that region. ?limit sets how many
+/// opponents are suggested; 10 by default, at most 100.
+///
+/// Returns a 404 if the player does not exist.
+///
+/// Returns an error with code 8 if the limit is too large.
+///
+/// Returns an error with code 14 if the player is suspended or banned.
+pub async fn get_suggested_opponents(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    query: &str,
+    ping: Option<u16>,
+    opponent_ping: Option<u16>,
+    region: Option<String>,
+    limit: Option<usize>,
+) -> Result<Json<Vec<SuggestedOpponentSchema>>, ApiError> {
+    let limit = limit.unwrap_or(DEFAULT_SUGGESTED_OPPONENTS);
+
+    if limit > MAX_SUGGESTED_OPPONENTS {
+        return Err(ApiError::invalid_field(&format!(
+            "limit cannot be larger than {}.",
+            MAX_SUGGESTED_OPPONENTS
+        )));
+    }
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let now = clock.now();
+
+    if !player.is_active(now) {
+        return Err(ApiError::player_not_active(&player.name));
+    }
+
+    let query_parameters = QueryParameters {
+        active_at: Some(now),
+        region,
+        ..Default::default()
+    };
+
+    let candidates = database_connection.get_players(query_parameters).await;
+
+    let ping = ping.unwrap_or(0);
+    let opponent_ping = opponent_ping.unwrap_or(0);
+
+    let mut opponents = candidates
+        .into_iter()
+        .filter(|x| x.id != player.id)
+        .map(|opponent| {
+            let expected_score =
+                calculate_expected_a_score(&player, &opponent, ping, opponent_ping);
+
+            let fairness = calculate_match_fairness(expected_score);
+            let information = calculate_match_information(&player, &opponent, expected_score);
+
+            SuggestedOpponentSchema {
+                player: opponent,
+                quality: calculate_match_quality(fairness, information),
+                fairness,
+                information,
+                expected_score,
+            }
+        })
+        .collect::<Vec<SuggestedOpponentSchema>>();
+
+    opponents.sort_by(|a, b| {
+        b.quality
+            .total_cmp(&a.quality)
+            .then(a.player.id.cmp(&b.player.id))
+    });
+
+    opponents.truncate(limit);
+
+    Ok(Json(opponents))
+}
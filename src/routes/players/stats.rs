@@ -0,0 +1,317 @@
+use log::info;
+use rocket::{get, http::Status, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::{query::QueryParameters, DbConnection},
+    glicko::predict_match_a_score,
+    request_guards::chrono::chrono_timestamp_from_string,
+    response::ApiError,
+    routes::players::get::get_live_player,
+    types::{
+        entities::{ladder::Ladder, r#match::Match},
+        schema::stats::{
+            HeadToHeadRatingPoint, HeadToHeadSchema, OpponentStatsSchema, PlayerStatsSchema,
+            PredictionSchema, RecordSchema, SeasonStatsSchema,
+        },
+    },
+    MysqlDb,
+};
+
+/// How many opponents are included in a player's most played opponents
+pub const MOST_PLAYED_OPPONENTS_COUNT: usize = 5;
+
+#[openapi(ignore = "db", tag = "Players")]
+#[get("/api/players/<a>/vs/<b>")]
+/// Fetches head to head statistics for two players, via ids or usernames.
+///
+/// Includes all of their matches against each other (with a always as player_a), their wins,
+/// losses and draws, total points, average pings, how their ratings changed across their
+/// matches and the predicted outcome of their next match.
+///
+/// The players and the prediction use their live ratings.
+///
+/// Returns a 404 if either one of the two players don't exist.
+///
+/// Returns an error with code 5 if a is b.
+pub async fn get_head_to_head(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    a: &str,
+    b: &str,
+) -> Result<Json<HeadToHeadSchema>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let started = std::time::Instant::now();
+
+    let Some(player_a) = database_connection.get_player_by_id_or_name(a).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let Some(player_b) = database_connection.get_player_by_id_or_name(b).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    if player_a.id == player_b.id {
+        return Err(ApiError::match_player_a_is_player_b());
+    }
+
+    let matches = database_connection
+        .get_head_to_head_matches(player_a.id, player_b.id)
+        .await
+        .into_iter()
+        .map(|a_match| a_match.sorted_by_player_id(player_a.id))
+        .collect::<Vec<Match>>();
+
+    let player_a = get_live_player(&mut database_connection, player_a, clock.as_ref()).await;
+    let player_b = get_live_player(&mut database_connection, player_b, clock.as_ref()).await;
+
+    let mut wins_a = 0;
+    let mut wins_b = 0;
+    let mut draws = 0;
+
+    let mut goals_a = 0;
+    let mut goals_b = 0;
+
+    let mut total_ping_a = 0;
+    let mut total_ping_b = 0;
+
+    let mut rating_trend = Vec::with_capacity(matches.len());
+
+    for a_match in &matches {
+        if a_match.score_a > a_match.score_b {
+            wins_a += 1;
+        } else if a_match.score_b > a_match.score_a {
+            wins_b += 1;
+        } else {
+            draws += 1;
+        }
+
+        goals_a += a_match.score_a as u64;
+        goals_b += a_match.score_b as u64;
+
+        total_ping_a += a_match.ping_a as u64;
+        total_ping_b += a_match.ping_b as u64;
+
+        rating_trend.push(HeadToHeadRatingPoint {
+            match_id: a_match.id,
+            epoch: a_match.epoch,
+            rating_a: a_match.rating_a,
+            rating_b: a_match.rating_b,
+        });
+    }
+
+    let (average_ping_a, average_ping_b) = if matches.is_empty() {
+        (None, None)
+    } else {
+        (
+            Some(total_ping_a as f64 / matches.len() as f64),
+            Some(total_ping_b as f64 / matches.len() as f64),
+        )
+    };
+
+    let ping_a = average_ping_a.unwrap_or_default().round() as u16;
+    let ping_b = average_ping_b.unwrap_or_default().round() as u16;
+
+    let expected_score_a = predict_match_a_score(&player_a, &player_b, ping_a, ping_b);
+
+    let prediction = PredictionSchema {
+        expected_score_a,
+        expected_score_b: 1.0 - expected_score_a,
+        ping_a,
+        ping_b,
+    };
+
+    let elapsed = started.elapsed();
+
+    info!(
+        "GET /players/<a>/vs/<b> took {:?} ({} matches)",
+        elapsed,
+        matches.len()
+    );
+
+    Ok(Json(HeadToHeadSchema {
+        player_a,
+        player_b,
+        matches,
+        wins_a,
+        wins_b,
+        draws,
+        goals_a,
+        goals_b,
+        average_ping_a,
+        average_ping_b,
+        rating_trend,
+        prediction,
+    }))
+}
+
+#[openapi(ignore = "db", tag = "Players")]
+#[get("/api/players/<query>/stats?<after>&<before>&<season>")]
+/// Fetches career statistics for a player via an id or username.
+///
+/// Includes their wins, losses and draws, points for and against, average share of the points,
+/// current and longest win streaks, average ping, most played opponents and a breakdown per
+/// rating period.
+///
+/// ?after, ?before and ?season can be used to only count some matches, like with
+/// GET /matches. ?after and ?before can be set to either an rfc3339 (iso) timestamp or unix
+/// milliseconds.
+///
+/// Returns the player's current rating; unless the instance rates matches instantly, it does not
+/// include performance from the latest season.
+///
+/// If no such player is found, the ApiError will have code 0 and message "Not Found"
+pub async fn get_player_stats(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    query: &str,
+    after: Option<String>,
+    before: Option<String>,
+    season: Option<u64>,
+) -> Result<Json<PlayerStatsSchema>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let started = std::time::Instant::now();
+
+    let Some(player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    let mut after_chrono = None;
+    let mut before_chrono = None;
+
+    if let Some(after_timestamp) = after {
+        after_chrono = chrono_timestamp_from_string(&after_timestamp);
+    }
+
+    if let Some(before_timestamp) = before {
+        before_chrono = chrono_timestamp_from_string(&before_timestamp);
+    }
+
+    let query_parameters = QueryParameters {
+        after: after_chrono,
+        before: before_chrono,
+        season,
+        has_player: Some(vec![player.id.to_string()]),
+        ..Default::default()
+    };
+
+    let mut matches = database_connection
+        .get_matches(query_parameters)
+        .await
+        .into_iter()
+        .map(|a_match| a_match.sorted_by_player_id(player.id))
+        .collect::<Vec<Match>>();
+
+    matches.sort_by(|a, b| a.epoch.cmp(&b.epoch).then_with(|| a.id.cmp(&b.id)));
+
+    let mut record = RecordSchema::default();
+
+    let mut total_goal_share = 0.0;
+    let mut total_ping = 0;
+
+    let mut current_win_streak = 0;
+    let mut longest_win_streak = 0;
+
+    // (opponent id, record against them)
+    let mut opponents: Vec<(u64, RecordSchema)> = Vec::new();
+    let mut seasons: Vec<SeasonStatsSchema> = Vec::new();
+
+    for a_match in &matches {
+        record.add_match(a_match);
+
+        let total_score = a_match.score_a as u16 + a_match.score_b as u16;
+
+        total_goal_share += match total_score {
+            0 => 0.5,
+            _ => a_match.score_a as f64 / total_score as f64,
+        };
+
+        total_ping += a_match.ping_a as u64;
+
+        if a_match.score_a > a_match.score_b {
+            current_win_streak += 1;
+            longest_win_streak = longest_win_streak.max(current_win_streak);
+        } else {
+            current_win_streak = 0;
+        }
+
+        match opponents.iter_mut().find(|(id, _)| *id == a_match.player_b) {
+            Some((_, opponent_record)) => opponent_record.add_match(a_match),
+            None => {
+                let mut opponent_record = RecordSchema::default();
+                opponent_record.add_match(a_match);
+                opponents.push((a_match.player_b, opponent_record));
+            }
+        }
+
+        match seasons
+            .iter_mut()
+            .find(|x| x.season == a_match.rating_period)
+        {
+            Some(season_stats) => season_stats.record.add_match(a_match),
+            None => {
+                let mut season_record = RecordSchema::default();
+                season_record.add_match(a_match);
+                seasons.push(SeasonStatsSchema {
+                    season: a_match.rating_period,
+                    record: season_record,
+                });
+            }
+        }
+    }
+
+    seasons.sort_by_key(|x| x.season);
+
+    // Most played first, ties stay in the order they were first played
+    opponents.sort_by(|a, b| b.1.games_played.cmp(&a.1.games_played));
+    opponents.truncate(MOST_PLAYED_OPPONENTS_COUNT);
+
+    let mut most_played_opponents = Vec::with_capacity(opponents.len());
+
+    for (id, opponent_record) in opponents {
+        let name = database_connection
+            .get_player_by_id(id)
+            .await
+            .map(|x| x.name)
+            .unwrap_or_default();
+
+        most_played_opponents.push(OpponentStatsSchema {
+            id,
+            name,
+            record: opponent_record,
+        });
+    }
+
+    let (average_goal_share, average_ping) = if matches.is_empty() {
+        (None, None)
+    } else {
+        (
+            Some(total_goal_share / matches.len() as f64),
+            Some(total_ping as f64 / matches.len() as f64),
+        )
+    };
+
+    let elapsed = started.elapsed();
+
+    info!(
+        "GET /players/<query>/stats took {:?} ({} matches)",
+        elapsed,
+        matches.len()
+    );
+
+    Ok(Json(PlayerStatsSchema {
+        player,
+        record,
+        average_goal_share,
+        current_win_streak,
+        longest_win_streak,
+        average_ping,
+        most_played_opponents,
+        seasons,
+    }))
+}
@@ -0,0 +1,89 @@
+use log::info;
+use rocket::{http::Status, put, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::DbConnection,
+    idempotency::HashedJson,
+    request_guards::admin_api_key::AdminApiKey,
+    response::ApiError,
+    types::{
+        entities::{
+            ladder::Ladder,
+            player::{Player, PlayerStatus},
+        },
+        schema::player::SetPlayerStatusSchema,
+    },
+    validation::Validate,
+    MysqlDb,
+};
+
+#[openapi(ignore = "db", tag = "Players")]
+#[put("/api/players/<query>/status", data = "<schema>")]
+#[allow(unused)]
+/// Sets whether a player, found via an id or username, is active, suspended or banned.
+///
+/// Requires an admin api key.
+///
+/// Suspended and banned players keep their history, but cannot play matches, are hidden from
+/// GET /players by default and their rating does not change in seasons they were suspended
+/// for. Suspensions can have an expiry, after which the player is active again.
+///
+/// Setting a player back to active clears the reason and expiry.
+///
+/// Returns the changed player.
+///
+/// Returns a 404 if the player does not exist.
+///
+/// Returns an error with code 8 if the request is invalid, such as an expiry in the past or
+/// on a ban; its details list every problem.
+pub async fn set_player_status(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    admin_key: AdminApiKey,
+    query: &str,
+    schema: HashedJson<SetPlayerStatusSchema>,
+) -> Result<Json<Player>, ApiError> {
+    let now = clock.now();
+
+    schema.validate(now)?;
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let Some(mut player) = database_connection.get_player_by_id_or_name(query).await else {
+        return Err(ApiError::from_status(Status::NotFound));
+    };
+
+    player.status = schema.status;
+
+    match schema.status {
+        PlayerStatus::Active => {
+            player.status_reason = None;
+            player.status_since = None;
+            player.status_expires = None;
+        }
+        _ => {
+            player.status_reason = schema.reason.clone();
+            player.status_since = Some(now);
+            player.status_expires = schema.expires;
+        }
+    }
+
+    database_connection
+        .set_player_status(&player)
+        .await
+        .unwrap();
+
+    info!(
+        "Set status of player {} ({}) to {} - {:?}",
+        player.id,
+        player.name,
+        player.status.as_str(),
+        player.status_reason
+    );
+
+    Ok(Json(player))
+}
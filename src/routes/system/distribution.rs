@@ -0,0 +1,101 @@
+use rocket::{get, serde::json::Json, State};
+use rocket_db_pools::Connection;
+use rocket_okapi::openapi;
+
+use crate::{
+    clock::SharedClock,
+    database::{query::QueryParameters, DbConnection},
+    response::ApiError,
+    routes::players::get::get_all_players_live,
+    types::{
+        entities::{ladder::Ladder, league::League, player::Player},
+        schema::distribution::{
+            DistributionSchema, HistogramBucketSchema, LeagueCountSchema, SummaryStatisticsSchema,
+            DEFAULT_BUCKET_SIZE, MIN_BUCKET_SIZE,
+        },
+    },
+    MysqlDb,
+};
+
+#[openapi(ignore = "db", tag = "System")]
+#[get("/api/system/distribution?<bucket_size>&<live>&<include_inactive>")]
+/// Returns how ratings are distributed among players; a histogram of public ratings, summary
+/// statistics of ratings, deviations and volatilities and how many players are in each league.
+///
+/// ?bucket_size sets the width of the histogram buckets; 100 by default, at least 10.
+///
+/// If ?live is true, live ratings are used, as if the season ended right now.
+///
+/// Suspended and banned players are left out, unless ?include_inactive is true.
+///
+/// Returns an error with code 8 if the bucket size is too small.
+pub async fn get_rating_distribution(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+    bucket_size: Option<f64>,
+    live: Option<bool>,
+    include_inactive: Option<bool>,
+) -> Result<Json<DistributionSchema>, ApiError> {
+    let bucket_size = bucket_size.unwrap_or(DEFAULT_BUCKET_SIZE);
+
+    if !bucket_size.is_finite() || bucket_size < MIN_BUCKET_SIZE {
+        return Err(ApiError::invalid_field(&format!(
+            "bucket_size must be at least {}.",
+            MIN_BUCKET_SIZE
+        )));
+    }
+
+    let live = live.unwrap_or(false);
+
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let players = match live {
+        true => get_all_players_live(&mut database_connection, clock.as_ref()).await,
+        false => {
+            database_connection
+                .get_players(QueryParameters::default())
+                .await
+        }
+    };
+
+    let now = clock.now();
+
+    let players = players
+        .into_iter()
+        .filter(|x| include_inactive.unwrap_or(false) || x.is_active(now))
+        .collect::<Vec<Player>>();
+
+    let ratings = players
+        .iter()
+        .map(|x| x.get_public_rating())
+        .collect::<Vec<f64>>();
+    let deviations = players
+        .iter()
+        .map(|x| x.get_public_deviation())
+        .collect::<Vec<f64>>();
+    let volatilities = players.iter().map(|x| x.volatility).collect::<Vec<f64>>();
+
+    let leagues = League::ALL
+        .into_iter()
+        .map(|league| LeagueCountSchema {
+            league,
+            min_rating: league.min_rating(),
+            count: ratings
+                .iter()
+                .filter(|x| League::from_rating(**x) == league)
+                .count() as u64,
+        })
+        .collect();
+
+    Ok(Json(DistributionSchema {
+        live,
+        player_count: players.len() as u64,
+        bucket_size,
+        histogram: HistogramBucketSchema::from_values(&ratings, bucket_size),
+        rating: SummaryStatisticsSchema::from_values(&ratings),
+        deviation: SummaryStatisticsSchema::from_values(&deviations),
+        volatility: SummaryStatisticsSchema::from_values(&volatilities),
+        leagues,
+    }))
+}
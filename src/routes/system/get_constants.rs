@@ -2,11 +2,11 @@ use rocket::get;
 use rocket::serde::json::Json;
 use rocket_okapi::openapi;
 
-use crate::types::schema::info::InstanceConstants;
+use crate::types::{entities::ladder::Ladder, schema::info::InstanceConstants};
 
 #[openapi(tag = "System")]
 #[get("/api/system/constants")]
-/// Returns the system constants used for the ranking system.
-pub async fn get_system_constants() -> Json<InstanceConstants> {
-    Json(InstanceConstants::default())
+/// Returns the constants used for the ranking system of the ladder.
+pub async fn get_system_constants(ladder: Ladder) -> Json<InstanceConstants> {
+    Json(ladder.constants())
 }
@@ -1,2 +1,3 @@
+pub mod distribution;
 pub mod get_constants;
 pub mod seasons;
@@ -1,26 +1,40 @@
-use rocket::{get, http::Status, serde::json::Json};
+use rocket::{get, http::Status, serde::json::Json, State};
 use rocket_db_pools::Connection;
 use rocket_okapi::openapi;
 
 use crate::{
-    database::{query::QueryParameters, DbConnection},
+    clock::SharedClock,
+    database::{
+        query::{id_cursor, Keyset, QueryParameters},
+        DbConnection,
+    },
     request_guards::chrono::chrono_timestamp_from_string,
     response::ApiError,
-    types::entities::season::Season,
+    types::{
+        entities::{ladder::Ladder, season::Season},
+        schema::page::PageSchema,
+    },
     MysqlDb,
 };
 
 #[openapi(ignore = "db", tag = "System")]
 #[get(
-    "/api/system/seasons?<start_before>&<start_after>&<end_before>&<end_after>&<sort>&<limit>&<offset>"
+    "/api/system/seasons?<start_before>&<start_after>&<end_before>&<end_after>&<sort>&<limit>&<offset>&<cursor>"
 )]
-/// Fetches an array of all rating periods.
+/// Fetches a page of rating periods.
 ///
 /// Here ?start_before, ?start_after, ?end_before and ?end_after can be used to target the start and end point of periods (in Utc time)
 ///
 /// They can be set to either an rfc3339 (iso) timestamp or unix milliseconds
+///
+/// The response includes the total number of rating periods matching the filters. If ?limit is
+/// set and there are more rating periods, it also includes a next_cursor; pass it as ?cursor to
+/// get the next page. Rating periods are ordered by id unless ?sort is set.
+///
+/// Returns an error with code 8 if the cursor is invalid or if both ?cursor and ?sort are set.
 pub async fn get_seasons(
     db: Connection<MysqlDb>,
+    ladder: Ladder,
     start_after: Option<String>,
     start_before: Option<String>,
     end_after: Option<String>,
@@ -28,7 +42,18 @@ pub async fn get_seasons(
     sort: Option<String>,
     limit: Option<usize>,
     offset: Option<usize>,
-) -> Json<Vec<Season>> {
+    cursor: Option<String>,
+) -> Result<Json<PageSchema<Season>>, ApiError> {
+    let keyset = match (&sort, cursor) {
+        (None, cursor) => Some(Keyset::id_from_cursor(cursor.as_deref())?),
+        (Some(_), None) => None,
+        (Some(_), Some(_)) => {
+            return Err(ApiError::invalid_field(
+                "cursor cannot be combined with sort.",
+            ))
+        }
+    };
+
     let mut start_after_chrono = None;
     let mut start_before_chrono = None;
     let mut end_after_chrono = None;
@@ -56,14 +81,33 @@ pub async fn get_seasons(
         start_after: start_after_chrono,
         start_before: start_before_chrono,
         sort,
-        limit,
+        // Fetch one more, to know whether there is a next page
+        limit: limit.map(|x| x + 1),
         offset,
+        keyset,
         ..Default::default()
     };
 
-    let mut database_connection = DbConnection::from_inner(db);
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
+
+    let total = database_connection
+        .count_rows(
+            "SELECT COUNT(*) FROM rating_periods",
+            query_parameters.clone(),
+        )
+        .await;
+
+    let seasons = database_connection
+        .get_seasons(query_parameters.clone())
+        .await;
 
-    Json(database_connection.get_seasons(query_parameters).await)
+    let mut page = PageSchema::from_items(seasons, limit, total, |x| id_cursor(x.id));
+
+    if query_parameters.keyset.is_none() {
+        page.next_cursor = None;
+    }
+
+    Ok(Json(page))
 }
 
 #[openapi(ignore = "db", tag = "System")]
@@ -71,8 +115,12 @@ pub async fn get_seasons(
 /// Fetches a rating period via its id.
 ///
 /// If no such rating period is found, the ApiError will have code 0 and message "Not Found"
-pub async fn get_season(db: Connection<MysqlDb>, id: u64) -> Result<Json<Season>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+pub async fn get_season(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    id: u64,
+) -> Result<Json<Season>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
     let season_option = database_connection.get_season_by_id(id).await;
 
@@ -87,10 +135,16 @@ pub async fn get_season(db: Connection<MysqlDb>, id: u64) -> Result<Json<Season>
 /// Fetches the latest rating period.
 ///
 /// If the system currently has no active rating period, returns a 404 error
-pub async fn get_latest_season(db: Connection<MysqlDb>) -> Result<Json<Season>, ApiError> {
-    let mut database_connection = DbConnection::from_inner(db);
+pub async fn get_latest_season(
+    db: Connection<MysqlDb>,
+    ladder: Ladder,
+    clock: &State<SharedClock>,
+) -> Result<Json<Season>, ApiError> {
+    let mut database_connection = DbConnection::for_ladder(db, &ladder);
 
-    let season_option = database_connection.get_latest_active_season().await;
+    let season_option = database_connection
+        .get_latest_active_season(clock.now())
+        .await;
 
     match season_option {
         None => Err(ApiError::from_status(Status::NotFound)),
@@ -0,0 +1,102 @@
+// search.rs: fuzzy matching of usernames, for searching players when the exact name isn't known
+// -----------------------
+
+/// The lowest relevance a fuzzy search result can have
+pub const MIN_FUZZY_SEARCH_SCORE: f64 = 0.5;
+
+/// Normalizes a username for comparing; lowercases it and removes the #123 discriminator
+pub fn normalize_username(username: &str) -> String {
+    let lowercase = username.trim().to_lowercase();
+
+    match lowercase.rsplit_once('#') {
+        Some((name, discriminator))
+            if !name.is_empty()
+                && discriminator.len() == 3
+                && discriminator.chars().all(|x| x.is_ascii_digit()) =>
+        {
+            name.to_string()
+        }
+        _ => lowercase,
+    }
+}
+
+/// Calculates the Levenshtein distance between two strings; how many characters have to be
+/// inserted, removed or replaced to turn one into the other
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+
+    // Only keep the previous row of the distance matrix
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == b_char { 0 } else { 1 };
+
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// The range of lengths (without the #123 discriminator) a normalized username can have to be
+/// similar enough to a normalized search without containing it.
+///
+/// Their edit distance is at least the difference in length, so longer or shorter names are
+/// less than [MIN_FUZZY_SEARCH_SCORE] alike.
+pub fn fuzzy_search_length_range(search: &str) -> (usize, usize) {
+    let search_length = search.chars().count();
+
+    (search_length.div_ceil(2), search_length * 2)
+}
+
+/// Scores how relevant a username is to a search, from 0 (nothing alike) to 1 (the same name).
+///
+/// Casing and discriminators are ignored. Names which contain the search are always
+/// relevant, so partially typed names still find the player.
+pub fn username_similarity(search: &str, username: &str) -> f64 {
+    let search = normalize_username(search);
+    let username = normalize_username(username);
+
+    let search_length = search.chars().count();
+    let username_length = username.chars().count();
+
+    let longest = search_length.max(username_length);
+
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let edit_similarity = 1.0 - levenshtein(&search, &username) as f64 / longest as f64;
+
+    if search_length > 0 && username.contains(&search) {
+        let substring_similarity = 0.5 + 0.5 * search_length as f64 / username_length as f64;
+
+        return edit_similarity.max(substring_similarity);
+    }
+
+    edit_similarity
+}
+
+#[test]
+fn username_similarity_ignores_case_and_discriminator() {
+    assert_eq!(levenshtein("kitten", "sitting"), 3);
+    assert_eq!(normalize_username("Toucan175#123"), "toucan175");
+
+    assert_eq!(username_similarity("toucan175", "Toucan175#123"), 1.0);
+    assert!(username_similarity("tuocan175", "toucan175") >= MIN_FUZZY_SEARCH_SCORE);
+    assert!(username_similarity("touc", "toucan175") >= MIN_FUZZY_SEARCH_SCORE);
+    assert!(username_similarity("someone", "toucan175") < MIN_FUZZY_SEARCH_SCORE);
+
+    // Names outside of the length range are only found if they contain the search
+    assert_eq!(fuzzy_search_length_range("toucan"), (3, 12));
+    assert!(username_similarity("toucan", "tou") >= MIN_FUZZY_SEARCH_SCORE);
+    assert!(username_similarity("toucan", "to") < MIN_FUZZY_SEARCH_SCORE);
+    assert!(username_similarity("toucan", "abcdefghijklm") < MIN_FUZZY_SEARCH_SCORE);
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{mysql::MySqlRow, FromRow, Row};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd)]
+/// A mutating request which was sent with an Idempotency-Key header, along with its response
+pub struct IdempotencyKey {
+    /// The sha256 hash of the api key that made the request
+    pub api_key_hash: String,
+
+    /// The value of the Idempotency-Key header
+    pub idempotency_key: String,
+
+    /// The method and uri of the request, a key can only be used for one request
+    pub method: String,
+    pub uri: String,
+
+    /// The sha256 hash of the request body, None if the route did not read one
+    pub body_hash: Option<String>,
+
+    /// The status code of the response, None if the request is still being processed
+    pub status: Option<u16>,
+
+    /// The body of the response, None if the request is still being processed
+    pub body: Option<String>,
+
+    /// When the request was made, Utc time.
+    pub epoch: DateTime<Utc>,
+}
+
+impl<'r> FromRow<'r, MySqlRow> for IdempotencyKey {
+    fn from_row(row: &'r MySqlRow) -> Result<Self, sqlx::Error> {
+        let api_key_hash = row.try_get("api_key_hash")?;
+        let idempotency_key = row.try_get("idempotency_key")?;
+
+        let method = row.try_get("method")?;
+        let uri = row.try_get("uri")?;
+        let body_hash = row.try_get("body_hash")?;
+
+        let status = row.try_get("status")?;
+        let body = row.try_get("body")?;
+
+        let epoch = row.try_get("epoch")?;
+
+        Ok(Self {
+            api_key_hash,
+            idempotency_key,
+            method,
+            uri,
+            body_hash,
+            status,
+            body,
+            epoch,
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{mysql::MySqlRow, FromRow, Row};
+
+use crate::{
+    glicko::{ping_influence, rating_mode, tau},
+    types::schema::info::InstanceConstants,
+};
+
+/// Id of the ladder which existed before there were several; routes without a ladder use it
+pub const DEFAULT_LADDER_ID: u64 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, JsonSchema)]
+/// An independent ranking, such as ranked 1v1 or a tournament.
+///
+/// Players, matches and rating periods belong to one ladder; each ladder has its own players
+/// and season schedule.
+pub struct Ladder {
+    pub id: u64,
+
+    /// Unique name of the ladder, used in /ladders/{ladder}/... routes
+    pub name: String,
+
+    /// The rating new players start with
+    pub default_rating: f64,
+    /// The rating deviation new players start with
+    pub default_deviation: f64,
+    /// The rating volatility new players start with
+    pub default_volatility: f64,
+
+    /// How long each rating period (season) of the ladder lasts
+    pub rating_period_duration_days: u64,
+}
+
+impl<'r> FromRow<'r, MySqlRow> for Ladder {
+    fn from_row(row: &'r MySqlRow) -> Result<Self, sqlx::Error> {
+        let id = row.try_get("id")?;
+        let name = row.try_get("name")?;
+
+        let default_rating = row.try_get("default_rating")?;
+        let default_deviation = row.try_get("default_deviation")?;
+        let default_volatility = row.try_get("default_volatility")?;
+
+        let rating_period_duration_days: u32 = row.try_get("rating_period_duration_days")?;
+
+        Ok(Ladder {
+            id,
+            name,
+            default_rating,
+            default_deviation,
+            default_volatility,
+            rating_period_duration_days: rating_period_duration_days as u64,
+        })
+    }
+}
+
+impl Ladder {
+    /// How long each rating period of the ladder lasts
+    pub fn rating_period_duration(&self) -> chrono::TimeDelta {
+        chrono::TimeDelta::days(self.rating_period_duration_days as i64)
+    }
+
+    /// The ladder's constants.
+    ///
+    /// Tau, ping influence and the rating mode are part of the rating algorithm and are the same
+    /// for every ladder.
+    pub fn constants(&self) -> InstanceConstants {
+        InstanceConstants {
+            default_rating: self.default_rating,
+            default_deviation: self.default_deviation,
+            default_volatility: self.default_volatility,
+            tau: tau(),
+            ping_influence: ping_influence(),
+            rating_period_duration_days: self.rating_period_duration_days,
+            rating_mode: rating_mode(),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+/// A range of ratings players are placed in, as in Lunars v1
+pub enum League {
+    Neophyte,
+    Padawan,
+    Amateur,
+    Skilled,
+    Pro,
+    Master,
+    Champion,
+}
+
+impl League {
+    /// Every league, lowest first
+    pub const ALL: [League; 7] = [
+        League::Neophyte,
+        League::Padawan,
+        League::Amateur,
+        League::Skilled,
+        League::Pro,
+        League::Master,
+        League::Champion,
+    ];
+
+    /// The lowest public rating in the league.
+    ///
+    /// Players rated below the lowest league are still placed in it.
+    pub fn min_rating(&self) -> f64 {
+        match self {
+            League::Neophyte => 1000.0,
+            League::Padawan => 1500.0,
+            League::Amateur => 1750.0,
+            League::Skilled => 2000.0,
+            League::Pro => 2250.0,
+            League::Master => 2500.0,
+            League::Champion => 2750.0,
+        }
+    }
+
+    /// Returns the league a public rating places a player in
+    pub fn from_rating(rating: f64) -> League {
+        League::ALL
+            .into_iter()
+            .rev()
+            .find(|x| rating >= x.min_rating())
+            .unwrap_or(League::Neophyte)
+    }
+}
+
+#[test]
+fn leagues_from_rating() {
+    assert_eq!(League::from_rating(400.0), League::Neophyte);
+    assert_eq!(League::from_rating(1500.0), League::Padawan);
+    assert_eq!(League::from_rating(2749.9), League::Master);
+    assert_eq!(League::from_rating(4000.0), League::Champion);
+}
@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{mysql::MySqlRow, FromRow, Row};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+/// Where a [MatchReview] is in the review queue
+pub enum MatchReviewStatus {
+    /// Not looked at yet
+    Pending,
+    /// The match is legitimate and was kept
+    Approved,
+    /// The match was a duplicate and was removed
+    Rejected,
+}
+
+impl MatchReviewStatus {
+    /// Returns the status as it is stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchReviewStatus::Pending => "pending",
+            MatchReviewStatus::Approved => "approved",
+            MatchReviewStatus::Rejected => "rejected",
+        }
+    }
+
+    /// Parses a status as it is stored in the database
+    pub fn from_str(status: &str) -> Option<Self> {
+        match status {
+            "pending" => Some(MatchReviewStatus::Pending),
+            "approved" => Some(MatchReviewStatus::Approved),
+            "rejected" => Some(MatchReviewStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, JsonSchema)]
+/// A match which looks like a duplicate of an earlier one, waiting for an admin to look at it
+pub struct MatchReview {
+    pub id: u64,
+
+    /// Id of the flagged match
+    pub match_id: u64,
+
+    /// Id of the earlier match it looks like a duplicate of
+    pub duplicate_of: u64,
+
+    /// Why the match was flagged
+    pub reason: String,
+
+    pub status: MatchReviewStatus,
+
+    /// When the match was flagged, Utc time.
+    pub epoch: DateTime<Utc>,
+
+    /// When an admin approved or rejected the match, Utc time.
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl<'r> FromRow<'r, MySqlRow> for MatchReview {
+    fn from_row(row: &'r MySqlRow) -> Result<Self, sqlx::Error> {
+        let id = row.try_get("id")?;
+
+        let match_id = row.try_get("match_id")?;
+        let duplicate_of = row.try_get("duplicate_of")?;
+
+        let reason = row.try_get("reason")?;
+
+        let status_string: String = row.try_get("status")?;
+        let status = MatchReviewStatus::from_str(&status_string).ok_or_else(|| {
+            sqlx::Error::ColumnDecode {
+                index: "status".to_string(),
+                source: format!("Unknown match review status {:?}", status_string).into(),
+            }
+        })?;
+
+        let epoch = row.try_get("epoch")?;
+        let resolved_at = row.try_get("resolved_at")?;
+
+        Ok(MatchReview {
+            id,
+            match_id,
+            duplicate_of,
+            reason,
+            status,
+            epoch,
+            resolved_at,
+        })
+    }
+}
+
+/// How far apart two matches can be played to count as possible duplicates, in minutes
+pub const DUPLICATE_MATCH_WINDOW_MINUTES: i64 = 10;
+
+/// How much each player's ping can differ between two matches for them to count as possible
+/// duplicates
+pub const DUPLICATE_MATCH_PING_TOLERANCE: u16 = 25;
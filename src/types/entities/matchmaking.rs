@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{mysql::MySqlRow, FromRow, Row};
+
+/// How often queued players are paired, in seconds
+pub const MATCHMAKING_INTERVAL_SECONDS: u64 = 5;
+
+/// The lowest match quality two players are paired at, right after joining the queue
+pub const MIN_MATCHMAKING_QUALITY: f64 = 0.4;
+/// How much the lowest accepted match quality drops for every minute a player waits
+pub const MATCHMAKING_QUALITY_RELAXATION_PER_MINUTE: f64 = 0.05;
+/// How long a player waits before they can be paired with players from other regions
+pub const MATCHMAKING_REGION_WAIT_MINUTES: i64 = 5;
+
+/// How long after being paired a submitted match is still linked to the pairing
+pub const MATCHMAKING_PAIRING_LINK_HOURS: i64 = 6;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, JsonSchema)]
+/// A player waiting in the matchmaking queue
+pub struct QueueEntry {
+    /// Id of the player
+    pub player_id: u64,
+
+    /// Id of the ladder the player is queued in; they are only paired with players of the same
+    /// ladder
+    pub ladder: u64,
+
+    /// The region the player wants to play in. Players from other regions are only
+    /// suggested after a while.
+    pub region: Option<String>,
+
+    /// The ping the player expects to play with
+    pub ping: u16,
+
+    /// When the player joined the queue, Utc time.
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, JsonSchema)]
+/// Two players the matchmaker paired to play a match
+pub struct MatchmakingPairing {
+    pub id: u64,
+
+    /// Id of the first player
+    pub player_a: u64,
+    /// Id of the second player
+    pub player_b: u64,
+
+    /// Ping of the first player, as they queued with
+    pub ping_a: u16,
+    /// Ping of the second player, as they queued with
+    pub ping_b: u16,
+
+    /// How good of a match the matchmaker expected it to be, 0 - 1
+    pub quality: f64,
+
+    /// When the players were paired, Utc time.
+    pub epoch: DateTime<Utc>,
+
+    /// Id of the match the players submitted, once they do.
+    ///
+    /// Matches between the two players submitted within 6 hours of being paired are linked.
+    pub match_id: Option<u64>,
+}
+
+impl<'r> FromRow<'r, MySqlRow> for MatchmakingPairing {
+    fn from_row(row: &'r MySqlRow) -> Result<Self, sqlx::Error> {
+        let id = row.try_get("id")?;
+        let player_a = row.try_get("player_a")?;
+        let player_b = row.try_get("player_b")?;
+        let ping_a = row.try_get("ping_a")?;
+        let ping_b = row.try_get("ping_b")?;
+        let quality = row.try_get("quality")?;
+        let epoch = row.try_get("epoch")?;
+        let match_id = row.try_get("match_id")?;
+
+        Ok(MatchmakingPairing {
+            id,
+            player_a,
+            player_b,
+            ping_a,
+            ping_b,
+            quality,
+            epoch,
+            match_id,
+        })
+    }
+}
@@ -1,4 +1,11 @@
+pub mod idempotency_key;
+pub mod ladder;
+pub mod league;
 pub mod r#match;
+pub mod match_review;
+pub mod matchmaking;
 pub mod player;
+pub mod player_alias;
+pub mod rating_snapshot;
 pub mod recent_request;
 pub mod season;
@@ -1,8 +1,128 @@
+use chrono::{DateTime, Utc};
 use rocket_okapi::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::{mysql::MySqlRow, FromRow, Row};
 
-use crate::glicko::{default_deviation, default_rating, default_volatility};
+use crate::{
+    glicko::{default_deviation, default_rating, default_volatility},
+    types::{
+        entities::season::Season,
+        schema::player::{DISCORD_ID_REGEX, MAX_CLAN_LENGTH, MAX_REGION_LENGTH},
+    },
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+/// The platform a player plays on
+pub enum Platform {
+    Pc,
+    PlayStation,
+    Xbox,
+    Switch,
+}
+
+impl Platform {
+    /// Returns the platform as it is stored in the database and used in url parameters
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Pc => "pc",
+            Platform::PlayStation => "playstation",
+            Platform::Xbox => "xbox",
+            Platform::Switch => "switch",
+        }
+    }
+
+    /// Parses a platform as it is stored in the database and used in url parameters
+    pub fn from_str(platform: &str) -> Option<Self> {
+        match platform {
+            "pc" => Some(Platform::Pc),
+            "playstation" => Some(Platform::PlayStation),
+            "xbox" => Some(Platform::Xbox),
+            "switch" => Some(Platform::Switch),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, PartialOrd, JsonSchema)]
+/// Optional information about a player, which does not affect their rating
+pub struct PlayerProfile {
+    /// The platform the player plays on
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    /// The region the player plays in, such as EU or NA
+    #[serde(default)]
+    #[schemars(length(min = 1, max = "MAX_REGION_LENGTH"))]
+    pub region: Option<String>,
+    /// The clan the player is in
+    #[serde(default)]
+    #[schemars(length(min = 1, max = "MAX_CLAN_LENGTH"))]
+    pub clan: Option<String>,
+    /// The player's Discord user id.
+    ///
+    /// A string, since Discord ids are too large for some json parsers
+    #[serde(default)]
+    #[schemars(regex = "DISCORD_ID_REGEX")]
+    pub discord_id: Option<String>,
+    /// When the player joined, Utc time. None for players added before this was recorded.
+    #[serde(default)]
+    pub joined_at: Option<DateTime<Utc>>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+/// Whether a player is allowed to play ranked matches
+pub enum PlayerStatus {
+    /// The player can play normally
+    #[default]
+    Active,
+    /// The player is temporarily not allowed to play, until the status expires (if it does)
+    Suspended,
+    /// The player is not allowed to play
+    Banned,
+}
+
+#[cfg(test)]
+impl Player {
+    /// Creates an active player with the given rating and deviation, for tests
+    pub fn for_tests(id: u64, rating: f64, deviation: f64) -> Self {
+        Player {
+            id,
+            name: format!("player{}", id),
+            rating,
+            deviation,
+            volatility: default_volatility(),
+            status: PlayerStatus::Active,
+            status_reason: None,
+            status_since: None,
+            status_expires: None,
+            profile: PlayerProfile::default(),
+        }
+    }
+}
+
+impl PlayerStatus {
+    /// Returns the status as it is stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayerStatus::Active => "active",
+            PlayerStatus::Suspended => "suspended",
+            PlayerStatus::Banned => "banned",
+        }
+    }
+
+    /// Parses a status as it is stored in the database
+    pub fn from_str(status: &str) -> Option<Self> {
+        match status {
+            "active" => Some(PlayerStatus::Active),
+            "suspended" => Some(PlayerStatus::Suspended),
+            "banned" => Some(PlayerStatus::Banned),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd, JsonSchema)]
 #[schemars(example = "player_example_default_glicko")]
@@ -26,6 +146,49 @@ pub struct Player {
     #[schemars(example = "default_volatility")]
     /// A measure of how (in)consistent the player is
     pub volatility: f64,
+
+    /// Whether the player is allowed to play.
+    ///
+    /// Suspended and banned players cannot play matches, are hidden from the player list
+    /// by default and their rating does not change.
+    #[serde(default)]
+    pub status: PlayerStatus,
+    /// Why the player was suspended or banned
+    #[serde(default)]
+    pub status_reason: Option<String>,
+    /// When the status was set, Utc time.
+    #[serde(default)]
+    pub status_since: Option<DateTime<Utc>>,
+    /// When a suspension ends, Utc time. None if it doesn't.
+    #[serde(default)]
+    pub status_expires: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub profile: PlayerProfile,
+}
+
+impl Player {
+    /// Whether the player is allowed to play at the given time; a suspension that has expired
+    /// no longer counts
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        match self.status {
+            PlayerStatus::Active => true,
+            _ => self.status_expires.is_some_and(|expires| expires <= at),
+        }
+    }
+
+    /// Whether the player was suspended or banned at the end of a season, so their rating
+    /// should not change for it
+    pub fn is_frozen_for(&self, season: &Season) -> bool {
+        self.is_frozen_at(season.end)
+    }
+
+    /// Whether the player was suspended or banned at the given time, so their rating
+    /// was not changing
+    pub fn is_frozen_at(&self, at: DateTime<Utc>) -> bool {
+        let status_set_before = self.status_since.map_or(true, |x| x < at);
+
+        !self.is_active(at) && status_set_before
+    }
 }
 
 impl<'r> FromRow<'r, MySqlRow> for Player {
@@ -36,12 +199,49 @@ impl<'r> FromRow<'r, MySqlRow> for Player {
         let deviation = row.try_get("deviation")?;
         let volatility = row.try_get("volatility")?;
 
+        let status_string: String = row.try_get("status")?;
+        let status =
+            PlayerStatus::from_str(&status_string).ok_or_else(|| sqlx::Error::ColumnDecode {
+                index: "status".to_string(),
+                source: format!("Unknown player status {:?}", status_string).into(),
+            })?;
+
+        let status_reason = row.try_get("status_reason")?;
+        let status_since = row.try_get("status_since")?;
+        let status_expires = row.try_get("status_expires")?;
+
+        let platform_string: Option<String> = row.try_get("platform")?;
+        let platform = match platform_string {
+            Some(platform_string) => {
+                Some(Platform::from_str(&platform_string).ok_or_else(|| {
+                    sqlx::Error::ColumnDecode {
+                        index: "platform".to_string(),
+                        source: format!("Unknown platform {:?}", platform_string).into(),
+                    }
+                })?)
+            }
+            None => None,
+        };
+
+        let profile = PlayerProfile {
+            platform,
+            region: row.try_get("region")?,
+            clan: row.try_get("clan")?,
+            discord_id: row.try_get("discord_id")?,
+            joined_at: row.try_get("joined_at")?,
+        };
+
         Ok(Player {
             id,
             name,
             rating,
             deviation,
             volatility,
+            status,
+            status_reason,
+            status_since,
+            status_expires,
+            profile,
         })
     }
 }
@@ -61,5 +261,10 @@ fn player_example_default_glicko() -> Player {
         rating: default_rating(),
         deviation: default_deviation(),
         volatility: default_volatility(),
+        status: PlayerStatus::Active,
+        status_reason: None,
+        status_since: None,
+        status_expires: None,
+        profile: PlayerProfile::default(),
     }
 }
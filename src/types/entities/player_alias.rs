@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{mysql::MySqlRow, FromRow, Row};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, JsonSchema)]
+/// A name a player used before they were renamed.
+///
+/// Endpoints which accept a username also accept a player's old names.
+pub struct PlayerAlias {
+    pub id: u64,
+
+    /// Id of the player
+    pub player_id: u64,
+
+    /// The old name
+    pub name: String,
+
+    /// When the player stopped using the name, Utc time.
+    pub epoch: DateTime<Utc>,
+}
+
+impl<'r> FromRow<'r, MySqlRow> for PlayerAlias {
+    fn from_row(row: &'r MySqlRow) -> Result<Self, sqlx::Error> {
+        let id = row.try_get("id")?;
+        let player_id = row.try_get("player_id")?;
+        let name = row.try_get("name")?;
+        let epoch = row.try_get("epoch")?;
+
+        Ok(PlayerAlias {
+            id,
+            player_id,
+            name,
+            epoch,
+        })
+    }
+}
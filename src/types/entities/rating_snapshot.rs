@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{mysql::MySqlRow, FromRow, Row};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd)]
+/// A player's rating at the start of a rating period.
+///
+/// Used to rate matches which are added after their rating period was already processed.
+pub struct RatingSnapshot {
+    /// Id of the player
+    pub player_id: u64,
+    /// Id of the rating period
+    pub rating_period: u64,
+    /// The player's rating at the start of the rating period
+    pub rating: f64,
+    /// The player's rating deviation at the start of the rating period
+    pub deviation: f64,
+    /// The player's rating volatility at the start of the rating period
+    pub volatility: f64,
+}
+
+impl<'r> FromRow<'r, MySqlRow> for RatingSnapshot {
+    fn from_row(row: &'r MySqlRow) -> Result<Self, sqlx::Error> {
+        let player_id = row.try_get("player_id")?;
+        let rating_period = row.try_get("rating_period")?;
+
+        let rating = row.try_get("rating")?;
+        let deviation = row.try_get("deviation")?;
+        let volatility = row.try_get("volatility")?;
+
+        Ok(RatingSnapshot {
+            player_id,
+            rating_period,
+            rating,
+            deviation,
+            volatility,
+        })
+    }
+}
@@ -3,6 +3,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::{mysql::MySqlRow, FromRow, Row};
 
+use crate::{clock::Clock, types::entities::ladder::DEFAULT_LADDER_ID};
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, PartialOrd, JsonSchema)]
 /// A rating period of the ranking system;
 ///
@@ -18,6 +20,8 @@ pub struct Season {
     /// Whether or not the ranked data from this season
     /// has been processed and written to the database yet
     pub processed: bool,
+    /// Id of the ladder the rating period is a part of
+    pub ladder: u64,
 }
 
 impl<'r> FromRow<'r, MySqlRow> for Season {
@@ -30,47 +34,56 @@ impl<'r> FromRow<'r, MySqlRow> for Season {
 
         let processed = row.try_get("processed")?;
 
+        let ladder = row.try_get("ladder")?;
+
         Ok(Season {
             id,
             start,
             end,
             processed,
+            ladder,
         })
     }
 }
 
 impl Season {
     /// Returns whether we are within the time bounds of the rating period
-    pub fn is_active(&self) -> bool {
-        let current_time = Utc::now();
+    pub fn is_active(&self, clock: &dyn Clock) -> bool {
+        self.is_active_at(clock.now())
+    }
 
-        current_time >= self.start && current_time < self.end
+    /// Returns whether the given time is within the time bounds of the rating period
+    pub fn is_active_at(&self, at: DateTime<Utc>) -> bool {
+        at >= self.start && at < self.end
     }
 
+    /// Creates a new rating period of the default ladder
     pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
         Self {
             start,
             end,
             id: 0,
             processed: false,
+            ladder: DEFAULT_LADDER_ID,
         }
     }
 
-    /// Creates a new rating period starting now and ending at the given end
-    pub fn new_starting_now_until(end: DateTime<Utc>) -> Self {
-        let now = Utc::now();
+    /// Creates a new rating period of the default ladder starting now and ending at the given end
+    pub fn new_starting_now_until(end: DateTime<Utc>, clock: &dyn Clock) -> Self {
+        let now = clock.now();
 
         Self {
             start: now,
             end,
             id: 0,
             processed: false,
+            ladder: DEFAULT_LADDER_ID,
         }
     }
 
-    /// Creates a new rating period starting now and ending after the duration
-    pub fn from_duration(duration: chrono::Duration) -> Self {
-        let now = Utc::now();
+    /// Creates a new rating period of the default ladder starting now and ending after the duration
+    pub fn from_duration(duration: chrono::Duration, clock: &dyn Clock) -> Self {
+        let now = clock.now();
 
         let end = now + duration;
 
@@ -79,6 +92,7 @@ impl Season {
             end,
             id: 0,
             processed: false,
+            ladder: DEFAULT_LADDER_ID,
         }
     }
 
@@ -88,28 +102,73 @@ impl Season {
     }
 
     /// Returns how much time has elapsed since the start of the season
-    pub fn elapsed_since_start(&self) -> chrono::Duration {
-        let now = Utc::now();
+    pub fn elapsed_since_start(&self, clock: &dyn Clock) -> chrono::Duration {
+        self.elapsed_since_start_at(clock.now())
+    }
 
-        now - self.start
+    /// Returns how much time had elapsed since the start of the season at the given time
+    pub fn elapsed_since_start_at(&self, at: DateTime<Utc>) -> chrono::Duration {
+        at - self.start
     }
 
     /// Returns how much time has elapsed since the end of the season
-    pub fn elapsed_since_end(&self) -> chrono::Duration {
-        let now = Utc::now();
+    pub fn elapsed_since_end(&self, clock: &dyn Clock) -> chrono::Duration {
+        self.elapsed_since_end_at(clock.now())
+    }
 
-        now - self.end
+    /// Returns how much time had elapsed since the end of the season at the given time
+    pub fn elapsed_since_end_at(&self, at: DateTime<Utc>) -> chrono::Duration {
+        at - self.end
     }
 
     /// Returns how many times the season has completed over.
     ///
     /// At self.start, this returns 0.0
     /// At self.end, this returns 1.0
-    pub fn completion(&self) -> f64 {
-        let since_start = self.elapsed_since_start();
+    pub fn completion(&self, clock: &dyn Clock) -> f64 {
+        self.completion_at(clock.now())
+    }
+
+    /// Returns how many times the season had completed over at the given time.
+    ///
+    /// See [Self::completion]
+    pub fn completion_at(&self, at: DateTime<Utc>) -> f64 {
+        let since_start = self.elapsed_since_start_at(at);
 
         let duration = self.duration();
 
         since_start.num_milliseconds() as f64 / duration.num_milliseconds() as f64
     }
+
+    /// Returns how many times the season's duration fits between the two times.
+    ///
+    /// Never negative, so a match submitted out of order adds no time.
+    pub fn periods_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+        let between = (to - from).num_milliseconds().max(0);
+
+        between as f64 / self.duration().num_milliseconds() as f64
+    }
+}
+
+#[test]
+fn completion_at_a_time() {
+    let start = Utc::now();
+    let season = Season::new(start, start + chrono::TimeDelta::days(20));
+
+    assert_eq!(season.completion_at(start), 0.0);
+    assert_eq!(
+        season.completion_at(start + chrono::TimeDelta::days(5)),
+        0.25
+    );
+    assert!(season.is_active_at(start + chrono::TimeDelta::days(19)));
+    assert!(!season.is_active_at(start + chrono::TimeDelta::days(20)));
+
+    let clock = crate::clock::FakeClock::new(start);
+    clock.advance(chrono::TimeDelta::days(10));
+
+    assert_eq!(season.completion(&clock), 0.5);
+    assert_eq!(
+        season.elapsed_since_end(&clock),
+        -chrono::TimeDelta::days(10)
+    );
 }
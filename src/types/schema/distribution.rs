@@ -0,0 +1,137 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::types::entities::league::League;
+
+/// The default width of a rating histogram bucket
+pub const DEFAULT_BUCKET_SIZE: f64 = 100.0;
+/// The narrowest a rating histogram bucket can be, so a histogram can't get too large
+pub const MIN_BUCKET_SIZE: f64 = 10.0;
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, PartialOrd, Debug, JsonSchema)]
+/// Schema for how ratings are distributed among players
+pub struct DistributionSchema {
+    /// Whether live ratings were used, as if the season ended right now
+    pub live: bool,
+    /// How many players are included
+    pub player_count: u64,
+    /// The width of each histogram bucket
+    pub bucket_size: f64,
+    /// How many players have a public rating in each range, lowest first.
+    ///
+    /// Buckets with no players between the lowest and highest rating are included.
+    pub histogram: Vec<HistogramBucketSchema>,
+    /// Statistics of the players' public ratings. None if there are no players.
+    pub rating: Option<SummaryStatisticsSchema>,
+    /// Statistics of the players' public rating deviations. None if there are no players.
+    pub deviation: Option<SummaryStatisticsSchema>,
+    /// Statistics of the players' rating volatilities. None if there are no players.
+    pub volatility: Option<SummaryStatisticsSchema>,
+    /// How many players are in each league, lowest first
+    pub leagues: Vec<LeagueCountSchema>,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, PartialOrd, Debug, JsonSchema)]
+/// A range of ratings and how many players are in it
+pub struct HistogramBucketSchema {
+    /// The lowest rating in the bucket
+    pub from: f64,
+    /// The rating the bucket ends at; not included in it
+    pub to: f64,
+    pub count: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, PartialOrd, Debug, JsonSchema)]
+/// A league and how many players are in it
+pub struct LeagueCountSchema {
+    pub league: League,
+    /// The lowest rating in the league
+    pub min_rating: f64,
+    pub count: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, PartialOrd, Debug, JsonSchema)]
+/// Summary statistics of a set of values
+pub struct SummaryStatisticsSchema {
+    pub mean: f64,
+    pub median: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The population standard deviation; how spread out the values are
+    pub standard_deviation: f64,
+}
+
+impl SummaryStatisticsSchema {
+    /// Calculates statistics of the values; None if there are none
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let count = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / count;
+
+        let middle = sorted.len() / 2;
+        let median = match sorted.len() % 2 {
+            0 => (sorted[middle - 1] + sorted[middle]) / 2.0,
+            _ => sorted[middle],
+        };
+
+        let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / count;
+
+        Some(SummaryStatisticsSchema {
+            mean,
+            median,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            standard_deviation: variance.sqrt(),
+        })
+    }
+}
+
+impl HistogramBucketSchema {
+    /// Counts the values in buckets of bucket_size, from the lowest to the highest value
+    pub fn from_values(values: &[f64], bucket_size: f64) -> Vec<Self> {
+        let Some(statistics) = SummaryStatisticsSchema::from_values(values) else {
+            return Vec::new();
+        };
+
+        let first_bucket = (statistics.min / bucket_size).floor() as i64;
+        let last_bucket = (statistics.max / bucket_size).floor() as i64;
+
+        let mut buckets = (first_bucket..=last_bucket)
+            .map(|x| HistogramBucketSchema {
+                from: x as f64 * bucket_size,
+                to: (x + 1) as f64 * bucket_size,
+                count: 0,
+            })
+            .collect::<Vec<HistogramBucketSchema>>();
+
+        for value in values {
+            let index = ((value / bucket_size).floor() as i64 - first_bucket) as usize;
+            buckets[index].count += 1;
+        }
+
+        buckets
+    }
+}
+
+#[test]
+fn distribution_statistics() {
+    let statistics =
+        SummaryStatisticsSchema::from_values(&[1400.0, 1600.0, 1500.0, 1700.0]).unwrap();
+
+    assert_eq!(statistics.mean, 1550.0);
+    assert_eq!(statistics.median, 1550.0);
+    assert_eq!(statistics.min, 1400.0);
+    assert_eq!(statistics.max, 1700.0);
+
+    let histogram = HistogramBucketSchema::from_values(&[1410.0, 1450.0, 1690.0], 100.0);
+    let counts = histogram.iter().map(|x| x.count).collect::<Vec<u64>>();
+
+    assert_eq!(histogram[0].from, 1400.0);
+    assert_eq!(counts, vec![2, 0, 1]);
+}
@@ -2,8 +2,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::glicko::{
-    default_deviation, default_rating, default_volatility, ping_influence,
-    rating_period_duration_days, tau,
+    default_deviation, default_rating, default_volatility, ping_influence, rating_mode,
+    rating_period_duration_days, tau, RatingMode,
 };
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, PartialOrd, Debug, JsonSchema)]
@@ -22,6 +22,9 @@ pub struct InstanceConstants {
     pub ping_influence: f64,
     #[schemars(example = "rating_period_duration_days")]
     pub rating_period_duration_days: u64,
+    #[schemars(example = "rating_mode")]
+    /// Whether ratings are committed at the end of each rating period or after each match
+    pub rating_mode: RatingMode,
 }
 
 impl Default for InstanceConstants {
@@ -33,6 +36,7 @@ impl Default for InstanceConstants {
             ping_influence: ping_influence(),
             tau: tau(),
             rating_period_duration_days: rating_period_duration_days(),
+            rating_mode: rating_mode(),
         }
     }
 }
@@ -0,0 +1,118 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    glicko::{default_deviation, default_rating, default_volatility, rating_period_duration_days},
+    response::ApiError,
+    types::{
+        entities::ladder::Ladder,
+        schema::player::{
+            MAX_DEVIATION, MAX_RATING, MAX_VOLATILITY, MIN_DEVIATION, MIN_RATING, MIN_VOLATILITY,
+        },
+    },
+    validation::{Validate, Violations},
+};
+
+/// Ladder names; letters, numbers, _ and -, since they are used in uris
+pub const LADDER_NAME_REGEX: &str = r"^[A-Za-z0-9_-]{1,64}$";
+
+/// The shortest rating periods a ladder can have, in days
+pub const MIN_RATING_PERIOD_DURATION_DAYS: u64 = 1;
+/// The longest rating periods a ladder can have, in days
+pub const MAX_RATING_PERIOD_DURATION_DAYS: u64 = 365;
+
+// Struct of a ladder we add
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct AddLadderSchema {
+    #[schemars(regex = "LADDER_NAME_REGEX")]
+    /// Name of the ladder, used in /ladders/{ladder}/... routes.
+    ///
+    /// (must be unique, shouldn't be a valid integer)
+    pub name: String,
+    #[schemars(range(min = "MIN_RATING", max = "MAX_RATING"))]
+    /// The rating new players start with; the system's default if not set
+    pub default_rating: Option<f64>,
+    #[schemars(range(min = "MIN_DEVIATION", max = "MAX_DEVIATION"))]
+    /// The rating deviation new players start with; the system's default if not set
+    pub default_deviation: Option<f64>,
+    #[schemars(range(min = "MIN_VOLATILITY", max = "MAX_VOLATILITY"))]
+    /// The rating volatility new players start with; the system's default if not set
+    pub default_volatility: Option<f64>,
+    #[schemars(range(
+        min = "MIN_RATING_PERIOD_DURATION_DAYS",
+        max = "MAX_RATING_PERIOD_DURATION_DAYS"
+    ))]
+    /// How long each rating period lasts, in days; the system's default if not set
+    pub rating_period_duration_days: Option<u64>,
+}
+
+impl AddLadderSchema {
+    /// Returns the ladder to add, with the system's defaults for fields which aren't set
+    pub fn to_ladder(&self) -> Ladder {
+        Ladder {
+            id: 0,
+            name: self.name.clone(),
+            default_rating: self.default_rating.unwrap_or(default_rating()),
+            default_deviation: self.default_deviation.unwrap_or(default_deviation()),
+            default_volatility: self.default_volatility.unwrap_or(default_volatility()),
+            rating_period_duration_days: self
+                .rating_period_duration_days
+                .unwrap_or(rating_period_duration_days()),
+        }
+    }
+}
+
+impl Validate for AddLadderSchema {
+    fn check(&self, violations: &mut Violations) {
+        violations.require(
+            "name",
+            self.name.parse::<u64>().is_err(),
+            ApiError::invalid_field("Ladder name cannot be a valid id."),
+        );
+    }
+
+    fn field_error(field: &str, description: &str) -> ApiError {
+        match field {
+            "name" => ApiError::invalid_field(
+                "Ladder name must be 1 to 64 letters, numbers, underscores or dashes.",
+            ),
+            _ => ApiError::invalid_field(description),
+        }
+    }
+}
+
+#[test]
+fn add_ladder_schema_validation() {
+    let valid = AddLadderSchema {
+        name: "ranked-1v1".to_string(),
+        default_rating: Some(1200.0),
+        default_deviation: None,
+        default_volatility: None,
+        rating_period_duration_days: Some(7),
+    };
+
+    assert!(valid.validate(chrono::Utc::now()).is_ok());
+    assert_eq!(valid.to_ladder().rating_period_duration_days, 7);
+    assert_eq!(valid.to_ladder().default_deviation, default_deviation());
+
+    let invalid = AddLadderSchema {
+        name: "123".to_string(),
+        rating_period_duration_days: Some(0),
+        ..valid.clone()
+    };
+
+    let violations = invalid.violations(chrono::Utc::now());
+
+    assert_eq!(violations.len(), 2);
+    assert!(violations.iter().any(|x| x.location == "name"));
+    assert!(violations
+        .iter()
+        .any(|x| x.location == "rating_period_duration_days"));
+
+    let invalid = AddLadderSchema {
+        name: "with spaces".to_string(),
+        ..valid
+    };
+
+    assert!(invalid.validate(chrono::Utc::now()).is_err());
+}
@@ -1,7 +1,20 @@
+use chrono::{DateTime, Utc};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::types::entities::{player::Player, r#match::Match};
+use crate::{
+    response::ApiError,
+    types::entities::{
+        match_review::MatchReview, matchmaking::MatchmakingPairing, player::Player, r#match::Match,
+    },
+    validation::{Validate, Violations},
+};
+
+/// The highest score a player can have in a match
+pub const MAX_SCORE: u8 = 22;
+
+/// The highest ping a player can report for a match
+pub const MAX_PING: u16 = 65000;
 
 // Struct of a match to add
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
@@ -15,13 +28,37 @@ pub struct AddMatchSchema {
     /// id takes priority over username, like the GET /players/{query} endpoint
     pub player_b: String,
     /// Ping of the first player. 0 - 65000
+    #[schemars(range(max = "MAX_PING"))]
     pub ping_a: u16,
     /// Ping of the second player. 0 - 65000
+    #[schemars(range(max = "MAX_PING"))]
     pub ping_b: u16,
     /// Score of the first player. 0 - 22
+    ///
+    /// At least one of the players must have scored.
+    #[schemars(range(max = "MAX_SCORE"))]
     pub score_a: u8,
     /// Score of the second player. 0 - 22
+    #[schemars(range(max = "MAX_SCORE"))]
     pub score_b: u8,
+    /// When the match was played, Utc time.
+    ///
+    /// Optional, for results reported late. If none is provided, the match is played now.
+    ///
+    /// The match is added to the rating period containing this time.
+    #[serde(default)]
+    pub played_at: Option<DateTime<Utc>>,
+}
+
+impl Validate for AddMatchSchema {
+    fn check(&self, violations: &mut Violations) {
+        // Otherwise the match has no outcome
+        violations.require(
+            "score_a",
+            self.score_a > 0 || self.score_b > 0,
+            ApiError::invalid_match("At least one of the players must have scored."),
+        );
+    }
 }
 
 // Return type of the add match endpoint.
@@ -33,4 +70,24 @@ pub struct AddMatchReturnSchema {
     pub live_a: Player,
     /// Player_b's new live rating
     pub live_b: Player,
+    /// If the match looks like a duplicate of an earlier one, the review it was queued for.
+    ///
+    /// The match is still added and rated until an admin rejects it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<MatchReview>,
+    /// If the players were paired by the matchmaker, the pairing the match was linked to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pairing: Option<MatchmakingPairing>,
+}
+
+// Return type of the bulk add matches endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct AddMatchesReturnSchema {
+    /// The created matches, in the order they were submitted
+    pub created: Vec<Match>,
+    /// New live ratings of every player that took part in the matches
+    pub live: Vec<Player>,
+    /// Reviews for the matches which look like duplicates of earlier ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reviews: Vec<MatchReview>,
 }
@@ -0,0 +1,39 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    types::{
+        entities::matchmaking::{MatchmakingPairing, QueueEntry},
+        schema::{player::MAX_REGION_LENGTH, r#match::MAX_PING},
+    },
+    validation::Validate,
+};
+
+// Struct of a player joining the matchmaking queue
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct JoinQueueSchema {
+    /// Username or id of the player joining the queue
+    pub player: String,
+    #[schemars(length(min = 1, max = "MAX_REGION_LENGTH"))]
+    /// The region the player wants to play in.
+    ///
+    /// If none is provided, the region from the player's profile is used.
+    #[serde(default)]
+    pub region: Option<String>,
+    #[schemars(range(max = "MAX_PING"))]
+    /// The ping the player expects to play with. 0 - 65000, 0 by default
+    #[serde(default)]
+    pub ping: Option<u16>,
+}
+
+impl Validate for JoinQueueSchema {}
+
+// Return type of the matchmaking status endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct MatchmakingStatusSchema {
+    /// The player's place in the queue, if they are waiting to be paired
+    pub queued: Option<QueueEntry>,
+    /// The player's latest pairing which they haven't submitted a match for, if they were
+    /// paired in the last 6 hours
+    pub pairing: Option<MatchmakingPairing>,
+}
@@ -1,3 +1,8 @@
+pub mod distribution;
 pub mod info;
+pub mod ladder;
 pub mod r#match;
+pub mod matchmaking;
+pub mod page;
 pub mod player;
+pub mod stats;
@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Return type of paginated list endpoints.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct PageSchema<T> {
+    /// The items on this page
+    pub items: Vec<T>,
+    /// How many items match the request in total, across all pages
+    pub total: u64,
+    /// Pass this as ?cursor to get the next page.
+    ///
+    /// None if this is the last page, or if the request set ?sort (cursors only work with the
+    /// default order).
+    pub next_cursor: Option<String>,
+}
+
+impl<T> PageSchema<T> {
+    /// Creates a page from items fetched with a limit one higher than requested.
+    ///
+    /// If the extra item was fetched there is a next page; its cursor is created from the last
+    /// item on this page.
+    pub fn from_items(
+        mut items: Vec<T>,
+        limit: Option<usize>,
+        total: u64,
+        cursor: impl Fn(&T) -> String,
+    ) -> Self {
+        let mut next_cursor = None;
+
+        if let Some(limit) = limit {
+            if items.len() > limit {
+                items.truncate(limit);
+                next_cursor = items.last().map(cursor);
+            }
+        }
+
+        PageSchema {
+            items,
+            total,
+            next_cursor,
+        }
+    }
+}
@@ -1,26 +1,319 @@
+use chrono::{DateTime, Utc};
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::types::entities::player::example_username;
+use crate::{
+    glicko::DEFAULT_DEVIATION,
+    response::ApiError,
+    types::entities::player::{example_username, Platform, Player, PlayerProfile, PlayerStatus},
+    validation::{Validate, Violations},
+};
+
+/// Warframe's username format; letters, numbers, _ . and -, optionally followed by a
+/// #123 discriminator
+pub const USERNAME_REGEX: &str = r"^[A-Za-z0-9_.-]{2,24}(#\d{3})?$";
+
+/// The lowest rating a player can be given
+pub const MIN_RATING: f64 = 0.0;
+/// The highest rating a player can be given
+pub const MAX_RATING: f64 = 5000.0;
+
+/// The lowest rating deviation a player can be given
+pub const MIN_DEVIATION: f64 = 1.0;
+/// The highest rating deviation a player can be given; that of a new player
+pub const MAX_DEVIATION: f64 = DEFAULT_DEVIATION as f64;
+
+/// The lowest rating volatility a player can be given
+pub const MIN_VOLATILITY: f64 = 0.001;
+/// The highest rating volatility a player can be given
+pub const MAX_VOLATILITY: f64 = 1.0;
+
+/// The longest region a player can be given
+pub const MAX_REGION_LENGTH: usize = 32;
+/// The longest clan name a player can be given
+pub const MAX_CLAN_LENGTH: usize = 64;
+/// Discord user ids (snowflakes)
+pub const DISCORD_ID_REGEX: &str = r"^\d{17,20}$";
 
 // Struct of a player we add
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
 pub struct AddPlayerSchema {
-    #[schemars(example = "example_username")]
+    #[schemars(example = "example_username", regex = "USERNAME_REGEX")]
     /// Warframe username of the player we're adding.
     ///
     /// (must be unique, shouldn't be a valid integer)
     pub name: String,
+    #[schemars(range(min = "MIN_RATING", max = "MAX_RATING"))]
     /// Optionally you can provide the rating of the player.
     ///
-    /// If none is provided, the default of the system will be used.
+    /// If none is provided, the default of the ladder will be used.
     pub rating: Option<f64>,
+    #[schemars(range(min = "MIN_DEVIATION", max = "MAX_DEVIATION"))]
     /// Optionally you can provide the rating deviation of the player.
     ///
-    /// If none is provided, the default of the system will be used.
+    /// If none is provided, the default of the ladder will be used.
     pub deviation: Option<f64>,
+    #[schemars(range(min = "MIN_VOLATILITY", max = "MAX_VOLATILITY"))]
     /// Optionally you can provide the rating volatility of the player.
     ///
-    /// If none is provided, the default of the system will be used.
+    /// If none is provided, the default of the ladder will be used.
     pub volatility: Option<f64>,
+    /// Optionally you can provide profile information about the player.
+    ///
+    /// If joined_at is not provided, it is set to now.
+    #[serde(flatten)]
+    pub profile: PlayerProfile,
+}
+
+impl Validate for AddPlayerSchema {
+    fn check(&self, violations: &mut Violations) {
+        check_username(violations, "name", &self.name);
+        check_joined_at(violations, self.profile.joined_at);
+    }
+
+    fn field_error(field: &str, description: &str) -> ApiError {
+        player_field_error(field, description)
+    }
+}
+
+// Struct of the changes to a player
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, PartialOrd, JsonSchema)]
+pub struct ModifyPlayerSchema {
+    #[schemars(example = "example_username", regex = "USERNAME_REGEX")]
+    /// The player's new warframe username.
+    ///
+    /// Their old username is kept as an alias. (must be unique, shouldn't be a valid integer)
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// The platform the player plays on, null to clear it
+    #[serde(default, deserialize_with = "deserialize_set_or_clear")]
+    pub platform: Option<Option<Platform>>,
+    #[schemars(length(min = 1, max = "MAX_REGION_LENGTH"))]
+    /// The region the player plays in, null to clear it
+    #[serde(default, deserialize_with = "deserialize_set_or_clear")]
+    pub region: Option<Option<String>>,
+    #[schemars(length(min = 1, max = "MAX_CLAN_LENGTH"))]
+    /// The clan the player is in, null to clear it
+    #[serde(default, deserialize_with = "deserialize_set_or_clear")]
+    pub clan: Option<Option<String>>,
+    #[schemars(regex = "DISCORD_ID_REGEX")]
+    /// The player's Discord user id, null to clear it
+    #[serde(default, deserialize_with = "deserialize_set_or_clear")]
+    pub discord_id: Option<Option<String>>,
+    /// When the player joined, Utc time.
+    #[serde(default)]
+    pub joined_at: Option<DateTime<Utc>>,
+}
+
+impl Validate for ModifyPlayerSchema {
+    fn check(&self, violations: &mut Violations) {
+        if let Some(name) = &self.name {
+            check_username(violations, "name", name);
+        }
+
+        check_joined_at(violations, self.joined_at);
+    }
+
+    fn field_error(field: &str, description: &str) -> ApiError {
+        player_field_error(field, description)
+    }
+}
+
+impl ModifyPlayerSchema {
+    /// Applies the changes to a player's profile
+    pub fn apply_to_profile(&self, profile: &mut PlayerProfile) {
+        if let Some(platform) = self.platform {
+            profile.platform = platform;
+        }
+
+        if let Some(region) = &self.region {
+            profile.region = region.clone();
+        }
+
+        if let Some(clan) = &self.clan {
+            profile.clan = clan.clone();
+        }
+
+        if let Some(discord_id) = &self.discord_id {
+            profile.discord_id = discord_id.clone();
+        }
+
+        if let Some(joined_at) = self.joined_at {
+            profile.joined_at = Some(joined_at);
+        }
+    }
+}
+
+/// Deserializes a field that can be left out (None), set to null (Some(None)) or set to a value
+fn deserialize_set_or_clear<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Returns the error for a field of a player which breaks a rule set on it; an invalid username
+/// has its own error code
+fn player_field_error(field: &str, description: &str) -> ApiError {
+    match field {
+        "name" => ApiError::invalid_username("Username is not a valid warframe username."),
+        "discord_id" => ApiError::invalid_field("discord_id is not a valid Discord user id."),
+        _ => ApiError::invalid_field(description),
+    }
+}
+
+/// Checks that a player didn't join in the future
+fn check_joined_at(violations: &mut Violations, joined_at: Option<DateTime<Utc>>) {
+    if let Some(joined_at) = joined_at {
+        violations.require(
+            "joined_at",
+            joined_at <= violations.now(),
+            ApiError::invalid_field("joined_at cannot be in the future."),
+        );
+    }
+}
+
+// Struct of a player and where they place on the leaderboard
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct RankedPlayerSchema {
+    #[serde(flatten)]
+    pub player: Player,
+    /// The player's place on the leaderboard, 1 being the highest rated player.
+    ///
+    /// None for suspended and banned players, who are not ranked.
+    pub rank: Option<u64>,
+    /// The percentage of ranked players the player is rated at least as high as, from 0 to 100.
+    ///
+    /// None for suspended and banned players, who are not ranked.
+    pub percentile: Option<f64>,
+    /// How many players are ranked
+    pub total_ranked: u64,
+}
+
+impl RankedPlayerSchema {
+    /// Creates the schema for a player with the given rank, or None if they are not ranked
+    pub fn new(player: Player, rank: Option<u64>, total_ranked: u64) -> Self {
+        RankedPlayerSchema {
+            player,
+            rank,
+            percentile: rank
+                .map(|rank| (total_ranked - rank + 1) as f64 / total_ranked as f64 * 100.0),
+            total_ranked,
+        }
+    }
+}
+
+// Struct of a player found by a fuzzy search
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct PlayerSearchResultSchema {
+    #[serde(flatten)]
+    pub player: Player,
+    /// How relevant the player is to the search, from 0 to 1. 1 means the name matched exactly
+    /// (ignoring casing and the discriminator).
+    pub score: f64,
+    /// The name or old name of the player which matched the search best
+    pub matched_name: String,
+}
+
+/// The longest reason a player can be suspended or banned for
+pub const MAX_STATUS_REASON_LENGTH: usize = 1000;
+
+// Struct of a player's new status
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct SetPlayerStatusSchema {
+    /// The player's new status
+    pub status: PlayerStatus,
+    #[schemars(length(max = "MAX_STATUS_REASON_LENGTH"))]
+    /// Why the player was suspended or banned
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// When a suspension ends, Utc time. Leave unset for an indefinite suspension.
+    ///
+    /// Can only be set for suspensions, and must be in the future.
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Validate for SetPlayerStatusSchema {
+    fn check(&self, violations: &mut Violations) {
+        if let Some(expires) = self.expires {
+            violations.require(
+                "expires",
+                self.status == PlayerStatus::Suspended,
+                ApiError::invalid_field("expires can only be set for suspensions."),
+            );
+
+            violations.require(
+                "expires",
+                expires > violations.now(),
+                ApiError::invalid_field("expires must be in the future."),
+            );
+        }
+    }
+}
+
+/// Checks that a player's username is not a valid u64 id, since some endpoints accept either an
+/// id or username.
+///
+/// That it matches warframe's username system ([USERNAME_REGEX]) is set on the schema's field.
+pub fn check_username(violations: &mut Violations, field: &str, username: &str) {
+    violations.require(
+        field,
+        username.parse::<u64>().is_err(),
+        ApiError::invalid_username("Username cannot be a valid id."),
+    );
+}
+
+#[test]
+fn add_player_schema_validation() {
+    let valid = AddPlayerSchema {
+        name: "toucan175#123".to_string(),
+        rating: Some(1500.0),
+        deviation: None,
+        volatility: Some(crate::glicko::DEFAULT_VOLATILITY),
+        profile: PlayerProfile::default(),
+    };
+
+    assert!(valid.validate(Utc::now()).is_ok());
+
+    let invalid = AddPlayerSchema {
+        name: "not a warframe name!".to_string(),
+        rating: Some(-10.0),
+        deviation: Some(0.0),
+        volatility: None,
+        profile: PlayerProfile::default(),
+    };
+
+    let violations = invalid.violations(Utc::now());
+
+    let locations = violations
+        .iter()
+        .map(|x| x.location.as_str())
+        .collect::<Vec<&str>>();
+
+    assert_eq!(locations, vec!["deviation", "name", "rating"]);
+    assert_eq!(violations[1].code, 4);
+
+    // The regex has to match the whole username
+    let partial = AddPlayerSchema {
+        name: "ok_name but then spaces".to_string(),
+        rating: None,
+        deviation: None,
+        volatility: None,
+        profile: PlayerProfile::default(),
+    };
+
+    assert!(partial.validate(Utc::now()).is_err());
+
+    let numeric = AddPlayerSchema {
+        name: "12345".to_string(),
+        rating: None,
+        deviation: None,
+        volatility: None,
+        profile: PlayerProfile::default(),
+    };
+
+    assert!(numeric.validate(Utc::now()).is_err());
 }
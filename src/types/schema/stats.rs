@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::types::entities::{player::Player, r#match::Match};
+
+// Return type of the head to head endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct HeadToHeadSchema {
+    /// Player a's current live rating
+    pub player_a: Player,
+    /// Player b's current live rating
+    pub player_b: Player,
+
+    /// Every match the two played against each other, oldest first.
+    ///
+    /// Player a is always player_a in these.
+    pub matches: Vec<Match>,
+
+    /// How many matches player a won
+    pub wins_a: u64,
+    /// How many matches player b won
+    pub wins_b: u64,
+    /// How many matches ended with the same score
+    pub draws: u64,
+
+    /// Total points player a scored against player b
+    pub goals_a: u64,
+    /// Total points player b scored against player a
+    pub goals_b: u64,
+
+    /// Player a's average ping in their matches, None if they haven't played
+    pub average_ping_a: Option<f64>,
+    /// Player b's average ping in their matches, None if they haven't played
+    pub average_ping_b: Option<f64>,
+
+    /// Both players' ratings before each of their matches, oldest first
+    pub rating_trend: Vec<HeadToHeadRatingPoint>,
+
+    /// The predicted outcome of their next match
+    pub prediction: PredictionSchema,
+}
+
+/// The two players' ratings for one of their matches
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct HeadToHeadRatingPoint {
+    /// Id of the match
+    pub match_id: u64,
+    /// When the match took place, Utc time.
+    pub epoch: DateTime<Utc>,
+    /// Player a's rating at the start of the match's rating period, which the match is rated from
+    pub rating_a: f64,
+    /// Player b's rating at the start of the match's rating period, which the match is rated from
+    pub rating_b: f64,
+}
+
+/// A predicted match outcome, from the players' current live ratings
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct PredictionSchema {
+    /// The share of points player a is expected to score, 0 - 1
+    pub expected_score_a: f64,
+    /// The share of points player b is expected to score, 0 - 1
+    pub expected_score_b: f64,
+    /// Ping assumed for player a; their average ping against player b, or 0 if they haven't played
+    pub ping_a: u16,
+    /// Ping assumed for player b; their average ping against player a, or 0 if they haven't played
+    pub ping_b: u16,
+}
+
+/// A player who would make a good opponent, and how good of a match it would be
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct SuggestedOpponentSchema {
+    #[serde(flatten)]
+    pub player: Player,
+    /// How good of a match it would be, 0 - 1. Opponents are ordered by this.
+    pub quality: f64,
+    /// How close the match is expected to be, 0 - 1. 1 means both players are expected to score
+    /// as much.
+    pub fairness: f64,
+    /// How much the match would tell us about both players' ratings, 0 - 1
+    pub information: f64,
+    /// The share of points the player is expected to score against this opponent, 0 - 1
+    pub expected_score: f64,
+}
+
+/// Wins, losses and points over a set of matches
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, PartialOrd, JsonSchema)]
+pub struct RecordSchema {
+    /// How many matches were played
+    pub games_played: u64,
+    /// How many matches the player won
+    pub wins: u64,
+    /// How many matches the player lost
+    pub losses: u64,
+    /// How many matches ended with the same score
+    pub draws: u64,
+    /// Total points the player scored
+    pub goals_for: u64,
+    /// Total points scored against the player
+    pub goals_against: u64,
+}
+
+impl RecordSchema {
+    /// Adds a match to the record, where the player is player_a
+    pub fn add_match(&mut self, a_match: &Match) {
+        self.games_played += 1;
+
+        if a_match.score_a > a_match.score_b {
+            self.wins += 1;
+        } else if a_match.score_b > a_match.score_a {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+
+        self.goals_for += a_match.score_a as u64;
+        self.goals_against += a_match.score_b as u64;
+    }
+}
+
+// Return type of the player stats endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct PlayerStatsSchema {
+    /// The player, with their current rating
+    pub player: Player,
+
+    /// The player's record over all the matches
+    #[serde(flatten)]
+    pub record: RecordSchema,
+
+    /// The average share of the points in a match the player scored, 0 - 1.
+    ///
+    /// None if they haven't played.
+    pub average_goal_share: Option<f64>,
+
+    /// How many of the player's latest matches in a row they won
+    pub current_win_streak: u64,
+    /// The most matches in a row the player has won
+    pub longest_win_streak: u64,
+
+    /// The player's average reported ping, None if they haven't played
+    pub average_ping: Option<f64>,
+
+    /// The players they played the most matches against, most played first
+    pub most_played_opponents: Vec<OpponentStatsSchema>,
+
+    /// The player's record in each rating period they played in, oldest first
+    pub seasons: Vec<SeasonStatsSchema>,
+}
+
+/// A player's record against one opponent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct OpponentStatsSchema {
+    /// Id of the opponent
+    pub id: u64,
+    /// Username of the opponent
+    pub name: String,
+
+    #[serde(flatten)]
+    pub record: RecordSchema,
+}
+
+/// A player's record in one rating period
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd, JsonSchema)]
+pub struct SeasonStatsSchema {
+    /// Id of the rating period
+    pub season: u64,
+
+    #[serde(flatten)]
+    pub record: RecordSchema,
+}
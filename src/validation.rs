@@ -0,0 +1,218 @@
+//! Validation of request schemas
+//!
+//! The rules of a schema's fields are set once, with #[schemars(...)] attributes; ranges, lengths
+//! and patterns. They show up in the OpenAPI spec and [Validate] checks them, all at once so every
+//! problem with a request can be reported together.
+//!
+//! Rules which the attributes can't describe, such as ones involving several fields or the
+//! current time, are checked by [Validate::check].
+
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::Serialize;
+
+use crate::response::{ApiError, ApiErrorDetail};
+
+/// A request schema with rules its values must follow
+pub trait Validate: JsonSchema + Serialize + Sized + 'static {
+    /// Checks the rules which aren't set on the schema's fields, adding the ones which are
+    /// broken to violations
+    fn check(&self, _violations: &mut Violations) {}
+
+    /// Returns the error for a field which breaks a rule set on it, which the description
+    /// explains
+    fn field_error(_field: &str, description: &str) -> ApiError {
+        ApiError::invalid_field(description)
+    }
+
+    /// Returns every rule the value breaks, with rules about time checked against now
+    fn violations(&self, now: DateTime<Utc>) -> Vec<ApiErrorDetail> {
+        let mut violations = Violations::at(now);
+
+        let value = serde_json::to_value(self).unwrap();
+
+        for rule in schema_rules::<Self>() {
+            let Some(field_value) = value.get(&rule.field) else {
+                continue;
+            };
+
+            if let Some(description) = rule.broken_by(field_value) {
+                violations.add(&rule.field, Self::field_error(&rule.field, &description));
+            }
+        }
+
+        self.check(&mut violations);
+        violations.inner
+    }
+
+    /// Validates the value, with rules about time checked against now.
+    ///
+    /// Returns an error with code 8 which lists all violations if it is invalid.
+    fn validate(&self, now: DateTime<Utc>) -> Result<(), ApiError> {
+        let violations = self.violations(now);
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(ApiError::validation_failed(violations))
+    }
+}
+
+/// The rules set on a field of a schema
+#[derive(Debug)]
+struct FieldRule {
+    field: String,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    min_length: Option<u32>,
+    max_length: Option<u32>,
+    pattern: Option<Regex>,
+}
+
+impl FieldRule {
+    /// Reads the rules of every field from a schema's JSON schema
+    fn from_schema<T: JsonSchema>() -> Vec<FieldRule> {
+        let root = SchemaGenerator::default().into_root_schema_for::<T>();
+
+        let Some(object) = root.schema.object else {
+            return Vec::new();
+        };
+
+        object
+            .properties
+            .into_iter()
+            .filter_map(|(field, schema)| {
+                let Schema::Object(schema) = schema else {
+                    return None;
+                };
+
+                let number = schema.number.unwrap_or_default();
+                let string = schema.string.unwrap_or_default();
+
+                Some(FieldRule {
+                    field,
+                    minimum: number.minimum,
+                    maximum: number.maximum,
+                    min_length: string.min_length,
+                    max_length: string.max_length,
+                    pattern: string.pattern.map(|pattern| Regex::new(&pattern).unwrap()),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a description of the rule the field's value breaks, if it breaks one.
+    ///
+    /// Unset (null) values don't break any.
+    fn broken_by(&self, value: &serde_json::Value) -> Option<String> {
+        let field = &self.field;
+
+        match value {
+            serde_json::Value::Number(number) => {
+                let number = number.as_f64()?;
+
+                match (self.minimum, self.maximum) {
+                    (Some(min), Some(max)) if number < min || number > max => {
+                        Some(format!("{} must be between {} and {}.", field, min, max))
+                    }
+                    (Some(min), None) if number < min => {
+                        Some(format!("{} must be at least {}.", field, min))
+                    }
+                    (None, Some(max)) if number > max => {
+                        Some(format!("{} cannot be more than {}.", field, max))
+                    }
+                    _ => None,
+                }
+            }
+            serde_json::Value::String(string) => {
+                let length = string.chars().count() as u32;
+
+                match (self.min_length, self.max_length) {
+                    (Some(min), Some(max)) if length < min || length > max => {
+                        return Some(format!(
+                            "{} must be between {} and {} characters long.",
+                            field, min, max
+                        ));
+                    }
+                    (Some(min), None) if length < min => {
+                        return Some(format!(
+                            "{} must be at least {} characters long.",
+                            field, min
+                        ));
+                    }
+                    (None, Some(max)) if length > max => {
+                        return Some(format!(
+                            "{} cannot be longer than {} characters.",
+                            field, max
+                        ));
+                    }
+                    _ => {}
+                }
+
+                match &self.pattern {
+                    Some(pattern) if !pattern.is_match(string) => {
+                        Some(format!("{} must match {}.", field, pattern.as_str()))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The rules of each schema, read once; patterns are only compiled then
+static SCHEMA_RULES: LazyLock<Mutex<HashMap<TypeId, &'static [FieldRule]>>> =
+    LazyLock::new(Default::default);
+
+/// Returns the rules set on a schema's fields
+fn schema_rules<T: JsonSchema + 'static>() -> &'static [FieldRule] {
+    let mut rules = SCHEMA_RULES.lock().unwrap();
+
+    rules
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| Box::leak(FieldRule::from_schema::<T>().into_boxed_slice()))
+}
+
+#[derive(Debug, Clone)]
+/// Broken rules, collected while validating
+pub struct Violations {
+    inner: Vec<ApiErrorDetail>,
+    now: DateTime<Utc>,
+}
+
+impl Violations {
+    /// Creates an empty list of violations, for validating at the given time
+    pub fn at(now: DateTime<Utc>) -> Self {
+        Violations {
+            inner: Vec::new(),
+            now,
+        }
+    }
+
+    /// The time the value is validated at; when checking whether it is in the past or future
+    pub fn now(&self) -> DateTime<Utc> {
+        self.now
+    }
+
+    /// Adds a violation of the field if the condition does not hold
+    pub fn require(&mut self, field: &str, condition: bool, error: ApiError) {
+        if !condition {
+            self.add(field, error);
+        }
+    }
+
+    /// Adds a violation of the field
+    fn add(&mut self, field: &str, error: ApiError) {
+        self.inner
+            .push(ApiErrorDetail::from_error(field.to_string(), &error));
+    }
+}